@@ -0,0 +1,273 @@
+use cgmath::prelude::*;
+use cgmath::{Matrix4, Point3, Vector3};
+
+/// Axis-aligned bounding box. An empty box has `min` above `max` on every axis so that growing
+/// it by the first point makes it exactly that point.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Point3<f32>,
+    pub max: Point3<f32>,
+}
+
+impl Default for Aabb {
+    fn default() -> Aabb {
+        Aabb::empty()
+    }
+}
+
+impl Aabb {
+    pub fn new(min: Point3<f32>, max: Point3<f32>) -> Aabb {
+        Aabb { min, max }
+    }
+
+    pub fn empty() -> Aabb {
+        Aabb {
+            min: Point3::new(f32::MAX, f32::MAX, f32::MAX),
+            max: Point3::new(f32::MIN, f32::MIN, f32::MIN),
+        }
+    }
+
+    pub fn from_points<I: IntoIterator<Item = Point3<f32>>>(points: I) -> Aabb {
+        let mut aabb = Aabb::empty();
+        for point in points {
+            aabb.grow(point);
+        }
+        aabb
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    pub fn grow(&mut self, point: Point3<f32>) {
+        self.min = Point3::new(
+            self.min.x.min(point.x),
+            self.min.y.min(point.y),
+            self.min.z.min(point.z),
+        );
+        self.max = Point3::new(
+            self.max.x.max(point.x),
+            self.max.y.max(point.y),
+            self.max.z.max(point.z),
+        );
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        let mut aabb = *self;
+        if !other.is_empty() {
+            aabb.grow(other.min);
+            aabb.grow(other.max);
+        }
+        aabb
+    }
+
+    pub fn center(&self) -> Point3<f32> {
+        self.min.midpoint(self.max)
+    }
+
+    /// full edge lengths
+    pub fn size(&self) -> Vector3<f32> {
+        self.max - self.min
+    }
+
+    /// half edge lengths
+    pub fn extents(&self) -> Vector3<f32> {
+        self.size() / 2.0
+    }
+
+    #[cfg(test)]
+    pub fn contains(&self, point: Point3<f32>) -> bool {
+        (self.min.x..=self.max.x).contains(&point.x)
+            && (self.min.y..=self.max.y).contains(&point.y)
+            && (self.min.z..=self.max.z).contains(&point.z)
+    }
+
+    #[cfg(test)]
+    pub fn intersects(&self, other: &Aabb) -> bool {
+        self.min.x <= other.max.x
+            && other.min.x <= self.max.x
+            && self.min.y <= other.max.y
+            && other.min.y <= self.max.y
+            && self.min.z <= other.max.z
+            && other.min.z <= self.max.z
+    }
+
+    /// The eight corners, bit 0 of the index picking max x, bit 1 max y and bit 2 max z.
+    /// ------------------------------------------------------------------------
+    #[cfg(test)]
+    pub fn corners(&self) -> [Point3<f32>; 8] {
+        let mut corners = [self.min; 8];
+        for (i, corner) in corners.iter_mut().enumerate() {
+            *corner = Point3::new(
+                if i & 1 == 0 { self.min.x } else { self.max.x },
+                if i & 2 == 0 { self.min.y } else { self.max.y },
+                if i & 4 == 0 { self.min.z } else { self.max.z },
+            );
+        }
+        corners
+    }
+
+    /// Box around this one after an affine transform (Arvo's method, no corner loop).
+    /// ------------------------------------------------------------------------
+    pub fn transformed(&self, transform: &Matrix4<f32>) -> Aabb {
+        if self.is_empty() {
+            return *self;
+        }
+        let center = transform.transform_point(self.center());
+        let extents = self.extents();
+        let mut half = Vector3::zero();
+        for row in 0..3 {
+            for column in 0..3 {
+                half[row] += transform[column][row].abs() * extents[column];
+            }
+        }
+        Aabb::new(center - half, center + half)
+    }
+}
+
+/// Sphere enclosing a set of points.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingSphere {
+    pub center: Point3<f32>,
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+    /// Ritter's approximation: start from two far apart points and grow the sphere over the
+    /// points outside it. Within a few percent of the smallest sphere; a single point gives a
+    /// zero radius and no points a negative one.
+    /// ------------------------------------------------------------------------
+    pub fn from_points(points: &[Point3<f32>]) -> BoundingSphere {
+        let Some(&first) = points.first() else {
+            return BoundingSphere {
+                center: Point3::origin(),
+                radius: -1.0,
+            };
+        };
+        let farthest = |from: Point3<f32>| {
+            points
+                .iter()
+                .copied()
+                .max_by(|a, b| from.distance2(*a).total_cmp(&from.distance2(*b)))
+                .unwrap_or(from)
+        };
+        let a = farthest(first);
+        let b = farthest(a);
+        let mut sphere = BoundingSphere {
+            center: a.midpoint(b),
+            radius: a.distance(b) / 2.0,
+        };
+        for &point in points {
+            sphere.grow(point);
+        }
+        sphere
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.radius < 0.0
+    }
+
+    /// Grow just enough to enclose `point`, moving the centre towards it.
+    /// ------------------------------------------------------------------------
+    pub fn grow(&mut self, point: Point3<f32>) {
+        if self.is_empty() {
+            *self = BoundingSphere {
+                center: point,
+                radius: 0.0,
+            };
+            return;
+        }
+        let distance = self.center.distance(point);
+        if distance > self.radius {
+            let radius = (self.radius + distance) / 2.0;
+            self.center += (point - self.center) * ((radius - self.radius) / distance);
+            self.radius = radius;
+        }
+    }
+
+    /// Sphere after an affine transform; non-uniform scales use the largest axis scale.
+    /// ------------------------------------------------------------------------
+    pub fn transformed(&self, transform: &Matrix4<f32>) -> BoundingSphere {
+        let scale = (0..3)
+            .map(|axis| transform[axis].truncate().magnitude())
+            .fold(0.0, f32::max);
+        BoundingSphere {
+            center: transform.transform_point(self.center),
+            radius: self.radius * scale,
+        }
+    }
+}
+
+impl From<Aabb> for BoundingSphere {
+    /// the sphere through the box corners
+    fn from(aabb: Aabb) -> BoundingSphere {
+        if aabb.is_empty() {
+            return BoundingSphere {
+                center: Point3::origin(),
+                radius: -1.0,
+            };
+        }
+        BoundingSphere {
+            center: aabb.center(),
+            radius: aabb.extents().magnitude(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::{vec3, Deg};
+
+    #[test]
+    fn aabb_grows_and_transforms() {
+        let mut aabb = Aabb::empty();
+        assert!(aabb.is_empty());
+        aabb.grow(Point3::new(1.0, 2.0, 3.0));
+        assert_eq!(aabb.size(), Vector3::zero());
+        aabb.grow(Point3::new(-1.0, 0.0, 5.0));
+        assert_eq!(aabb.center(), Point3::new(0.0, 1.0, 4.0));
+        assert!(aabb.contains(Point3::new(0.5, 1.5, 4.5)));
+        assert!(!aabb.contains(Point3::new(0.5, 2.5, 4.5)));
+
+        // a quarter turn about Z swaps the X and Y extents
+        let turned = aabb.transformed(
+            &(Matrix4::from_translation(vec3(10.0, 0.0, 0.0)) * Matrix4::from_angle_z(Deg(90.0))),
+        );
+        let expected = Aabb::from_points(
+            aabb.corners()
+                .iter()
+                .map(|&p| Point3::new(10.0 - p.y, p.x, p.z)),
+        );
+        assert!((turned.min - expected.min).magnitude() < 1e-5);
+        assert!((turned.max - expected.max).magnitude() < 1e-5);
+
+        assert_eq!(Aabb::empty().union(&aabb), aabb);
+        assert!(aabb.intersects(&turned.union(&aabb)));
+        assert!(!aabb.intersects(&turned));
+    }
+
+    #[test]
+    fn sphere_encloses_every_point() {
+        let points: Vec<Point3<f32>> = (0..50)
+            .map(|i| {
+                let t = i as f32 * 0.7;
+                Point3::new(t.sin() * 3.0, (t * 1.3).cos() * 2.0, (t * 0.4).sin())
+            })
+            .collect();
+        let sphere = BoundingSphere::from_points(&points);
+        for point in &points {
+            assert!(sphere.center.distance(*point) <= sphere.radius + 1e-5);
+        }
+        // never worse than the sphere around the bounding box
+        let around_box = BoundingSphere::from(Aabb::from_points(points.iter().copied()));
+        assert!(sphere.radius <= around_box.radius);
+
+        let single = BoundingSphere::from_points(&points[..1]);
+        assert_eq!((single.center, single.radius), (points[0], 0.0));
+        assert!(BoundingSphere::from_points(&[]).is_empty());
+
+        let scaled = sphere.transformed(&Matrix4::from_nonuniform_scale(1.0, 3.0, 2.0));
+        assert!((scaled.radius - sphere.radius * 3.0).abs() < 1e-5);
+    }
+}
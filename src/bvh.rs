@@ -0,0 +1,424 @@
+use cgmath::prelude::*;
+use cgmath::Point3;
+
+use crate::bounds::Aabb;
+use crate::mesh::MeshData;
+use crate::ray::{Ray, RayHit, TriangleHit};
+use crate::scene::{NodeId, Scene};
+
+/// most items kept in a leaf before it is split
+const LEAF_SIZE: usize = 4;
+
+/// A node of the hierarchy. Leaves hold `count` items starting at `first` in `Bvh::items`,
+/// inner nodes (`count == 0`) have their two children at `first` and `first + 1`.
+#[derive(Debug, Clone, Copy)]
+struct BvhNode {
+    bounds: Aabb,
+    first: usize,
+    count: usize,
+}
+
+/// Bounding volume hierarchy over a list of boxes, split at the median of the box centres
+/// along the longest axis. Items are referred to by their index in the list it was built
+/// from.
+#[derive(Debug, Clone, Default)]
+pub struct Bvh {
+    nodes: Vec<BvhNode>,
+    items: Vec<usize>,
+}
+
+impl Bvh {
+    /// Build over `bounds`; empty boxes are left out.
+    /// ------------------------------------------------------------------------
+    pub fn build(bounds: &[Aabb]) -> Bvh {
+        let mut bvh = Bvh {
+            nodes: Vec::with_capacity(bounds.len() / LEAF_SIZE * 2 + 1),
+            items: (0..bounds.len())
+                .filter(|&i| !bounds[i].is_empty())
+                .collect(),
+        };
+        if !bvh.items.is_empty() {
+            let centers: Vec<Point3<f32>> = bounds.iter().map(Aabb::center).collect();
+            bvh.nodes.push(BvhNode {
+                bounds: Aabb::empty(),
+                first: 0,
+                count: 0,
+            });
+            bvh.split(0, 0, bvh.items.len(), bounds, &centers);
+        }
+        bvh
+    }
+
+    fn split(
+        &mut self,
+        node: usize,
+        first: usize,
+        count: usize,
+        bounds: &[Aabb],
+        centers: &[Point3<f32>],
+    ) {
+        let items = &mut self.items[first..first + count];
+        let node_bounds = items
+            .iter()
+            .fold(Aabb::empty(), |all, &item| all.union(&bounds[item]));
+        let spread = Aabb::from_points(items.iter().map(|&item| centers[item])).size();
+        let axis = (0..3)
+            .max_by(|&a, &b| spread[a].total_cmp(&spread[b]))
+            .unwrap_or(0);
+        // small enough, or every centre in the same spot so no split would separate them
+        if count <= LEAF_SIZE || spread[axis] <= 0.0 {
+            self.nodes[node] = BvhNode {
+                bounds: node_bounds,
+                first,
+                count,
+            };
+            return;
+        }
+        let middle = count / 2;
+        items.select_nth_unstable_by(middle, |&a, &b| {
+            centers[a][axis].total_cmp(&centers[b][axis])
+        });
+        let children = self.nodes.len();
+        let placeholder = BvhNode {
+            bounds: Aabb::empty(),
+            first: 0,
+            count: 0,
+        };
+        self.nodes.extend_from_slice(&[placeholder, placeholder]);
+        self.nodes[node] = BvhNode {
+            bounds: node_bounds,
+            first: children,
+            count: 0,
+        };
+        self.split(children, first, middle, bounds, centers);
+        self.split(
+            children + 1,
+            first + middle,
+            count - middle,
+            bounds,
+            centers,
+        );
+    }
+
+    #[cfg(test)]
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// bounds of everything in the hierarchy
+    #[cfg(test)]
+    pub fn bounds(&self) -> Aabb {
+        self.nodes.first().map_or(Aabb::empty(), |root| root.bounds)
+    }
+
+    /// Closest hit along `ray`. `hit(item)` tests one item whose node the ray enters and
+    /// returns its distance, if it is hit at all; nodes further away than the closest hit so
+    /// far are skipped and nearer children are visited first.
+    /// ------------------------------------------------------------------------
+    pub fn raycast<F>(&self, ray: &Ray, mut hit: F) -> Option<(usize, f32)>
+    where
+        F: FnMut(usize) -> Option<f32>,
+    {
+        let mut closest: Option<(usize, f32)> = None;
+        let mut stack: Vec<(usize, f32)> = Vec::new();
+        if let Some(t) = self
+            .nodes
+            .first()
+            .and_then(|root| ray.intersect_aabb(&root.bounds))
+        {
+            stack.push((0, t));
+        }
+        while let Some((index, entry)) = stack.pop() {
+            let limit = closest.map_or(f32::INFINITY, |(_, t)| t);
+            if entry > limit {
+                continue;
+            }
+            let node = self.nodes[index];
+            if node.count > 0 {
+                for &item in &self.items[node.first..node.first + node.count] {
+                    let limit = closest.map_or(f32::INFINITY, |(_, t)| t);
+                    if let Some(t) = hit(item).filter(|&t| t < limit) {
+                        closest = Some((item, t));
+                    }
+                }
+                continue;
+            }
+            let mut children: Vec<(usize, f32)> = (node.first..node.first + 2)
+                .filter_map(|child| Some((child, ray.intersect_aabb(&self.nodes[child].bounds)?)))
+                .collect();
+            // the nearer child goes on top of the stack
+            children.sort_by(|a, b| b.1.total_cmp(&a.1));
+            stack.extend(children);
+        }
+        closest
+    }
+
+    /// number of levels, 1 for a single leaf
+    #[cfg(test)]
+    pub fn depth(&self) -> usize {
+        fn depth(nodes: &[BvhNode], index: usize) -> usize {
+            let node = nodes[index];
+            if node.count > 0 {
+                1
+            } else {
+                1 + depth(nodes, node.first).max(depth(nodes, node.first + 1))
+            }
+        }
+        if self.is_empty() {
+            0
+        } else {
+            depth(&self.nodes, 0)
+        }
+    }
+}
+
+/// The triangles of a mesh with a hierarchy over them, kept on the CPU for ray picking.
+#[derive(Debug, Clone, Default)]
+pub struct MeshBvh {
+    positions: Vec<Point3<f32>>,
+    indices: Vec<u32>,
+    bvh: Bvh,
+}
+
+impl MeshBvh {
+    pub fn new(mesh: &MeshData) -> MeshBvh {
+        let positions: Vec<Point3<f32>> = mesh
+            .vertices
+            .iter()
+            .map(|vertex| Point3::from_vec(vertex.position))
+            .collect();
+        let bounds: Vec<Aabb> = mesh
+            .triangles()
+            .map(|triangle| Aabb::from_points(triangle.map(|index| positions[index as usize])))
+            .collect();
+        MeshBvh {
+            bvh: Bvh::build(&bounds),
+            positions,
+            indices: mesh.indices.clone(),
+        }
+    }
+
+    #[cfg(test)]
+    pub fn bounds(&self) -> Aabb {
+        self.bvh.bounds()
+    }
+
+    fn corners(&self, triangle: usize) -> [Point3<f32>; 3] {
+        let index = |corner: usize| self.indices[triangle * 3 + corner] as usize;
+        [0, 1, 2].map(|corner| self.positions[index(corner)])
+    }
+
+    /// Closest triangle along `ray`, in the mesh's own space.
+    /// ------------------------------------------------------------------------
+    pub fn raycast(&self, ray: &Ray) -> Option<RayHit> {
+        let (triangle, t) = self.bvh.raycast(ray, |triangle| {
+            let [a, b, c] = self.corners(triangle);
+            Some(ray.intersect_triangle(a, b, c)?.0)
+        })?;
+        // the weights of the closest triangle only, the test is cheap to repeat
+        let [a, b, c] = self.corners(triangle);
+        let (_, barycentric) = ray.intersect_triangle(a, b, c)?;
+        Some(RayHit {
+            t,
+            triangle: Some(TriangleHit {
+                mesh: 0,
+                triangle,
+                barycentric,
+            }),
+        })
+    }
+}
+
+/// What `SceneBvh::pick` found: the node, the distance along the ray, the world space point
+/// and the triangle when the node's drawable keeps its triangles.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PickHit {
+    pub node: NodeId,
+    pub t: f32,
+    pub point: Point3<f32>,
+    pub triangle: Option<TriangleHit>,
+}
+
+/// Hierarchy over the world bounds of every visible node with a renderable. Nodes move, so
+/// it is rebuilt when needed rather than kept up to date.
+#[derive(Debug, Clone, Default)]
+pub struct SceneBvh {
+    bvh: Bvh,
+    nodes: Vec<NodeId>,
+}
+
+impl SceneBvh {
+    pub fn build(scene: &Scene) -> SceneBvh {
+        let (nodes, _) = scene.draw_list(None);
+        let bounds: Vec<Aabb> = nodes.iter().map(|&id| scene.world_bounds(id)).collect();
+        SceneBvh {
+            bvh: Bvh::build(&bounds),
+            nodes,
+        }
+    }
+
+    /// Closest node along a world space ray. Each candidate is tested in its own space with
+    /// `Drawable::raycast`, down to the triangle when the drawable supports it.
+    /// ------------------------------------------------------------------------
+    pub fn pick(&self, scene: &Scene, ray: &Ray) -> Option<PickHit> {
+        let mut triangles = vec![None; self.nodes.len()];
+        let (item, t) = self.bvh.raycast(ray, |item| {
+            let id = self.nodes[item];
+            let renderable = scene.node(id).renderable.as_ref()?;
+            let to_local = scene.world_matrix(id).invert()?;
+            let hit = renderable.drawable.raycast(&ray.transformed(&to_local))?;
+            triangles[item] = hit.triangle;
+            Some(hit.t)
+        })?;
+        Some(PickHit {
+            node: self.nodes[item],
+            t,
+            point: ray.at(t),
+            triangle: triangles[item],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Material;
+    use crate::scene::{Drawable, Renderable, Transform};
+    use crate::shader::Shader;
+    use cgmath::{vec3, Matrix4, Vector3};
+    use std::rc::Rc;
+
+    /// deterministic directions over the sphere
+    fn rays(count: usize, origin: Point3<f32>) -> Vec<Ray> {
+        let golden = std::f32::consts::PI * (3.0 - 5.0f32.sqrt());
+        (0..count)
+            .map(|i| {
+                let y = 1.0 - 2.0 * (i as f32 + 0.5) / count as f32;
+                let radius = (1.0 - y * y).sqrt();
+                let angle = golden * i as f32;
+                let target = Point3::new(angle.cos() * radius, y, angle.sin() * radius) * 0.9;
+                Ray::new(origin, target - origin)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn tree_keeps_every_item_once() {
+        let boxes: Vec<Aabb> = (0..100)
+            .map(|i| {
+                let p = Point3::new((i % 10) as f32 * 2.0, (i / 10) as f32 * 2.0, 0.0);
+                Aabb::new(p, p + vec3(1.0, 1.0, 1.0))
+            })
+            .chain(std::iter::once(Aabb::empty()))
+            .collect();
+        let bvh = Bvh::build(&boxes);
+        let mut items = bvh.items.clone();
+        items.sort_unstable();
+        assert_eq!(items, (0..100).collect::<Vec<_>>());
+        // 100 items in leaves of at most 4 need 5 levels of halving
+        assert_eq!(bvh.depth(), 6);
+        for node in &bvh.nodes {
+            assert!(node.count <= LEAF_SIZE);
+            if node.count == 0 {
+                for child in [node.first, node.first + 1] {
+                    assert_eq!(node.bounds.union(&bvh.nodes[child].bounds), node.bounds);
+                }
+            }
+        }
+        // identical boxes can't be split, they stay in one big leaf
+        let same = Bvh::build(&vec![boxes[0]; 9]);
+        assert_eq!(same.depth(), 1);
+        assert!(Bvh::build(&[])
+            .raycast(&rays(1, Point3::origin())[0], |_| None)
+            .is_none());
+    }
+
+    #[test]
+    fn mesh_raycast_matches_brute_force() {
+        let mesh = MeshData::icosphere(1.0, 3);
+        let bvh = MeshBvh::new(&mesh);
+        let origin = Point3::new(0.3, 2.5, 4.0);
+        let mut hits = 0;
+        for ray in rays(200, origin) {
+            let expected = mesh
+                .triangles()
+                .enumerate()
+                .filter_map(|(triangle, corners)| {
+                    let [a, b, c] = corners
+                        .map(|index| Point3::from_vec(mesh.vertices[index as usize].position));
+                    Some((triangle, ray.intersect_triangle(a, b, c)?))
+                })
+                .min_by(|a, b| a.1 .0.total_cmp(&b.1 .0));
+            let hit = bvh.raycast(&ray);
+            assert_eq!(hit.is_some(), expected.is_some());
+            if let (Some(hit), Some((triangle, (t, barycentric)))) = (hit, expected) {
+                hits += 1;
+                assert!((hit.t - t).abs() < 1e-5);
+                let found = hit.triangle.unwrap();
+                assert_eq!(found.triangle, triangle);
+                assert!((found.barycentric - barycentric).magnitude() < 1e-5);
+                // the closest hit is on the side facing the origin
+                assert!(ray.at(hit.t).distance(origin) < origin.distance(Point3::origin()));
+            }
+        }
+        // every ray aims inside the sphere
+        assert_eq!(hits, 200);
+    }
+
+    /// unit cube keeping its triangles for picking
+    struct PickableCube(MeshBvh);
+
+    impl Drawable for PickableCube {
+        unsafe fn draw(&self, _shader: &Shader, _model: &Matrix4<f32>) {}
+
+        fn bounds(&self) -> Aabb {
+            self.0.bounds()
+        }
+
+        fn raycast(&self, ray: &Ray) -> Option<RayHit> {
+            self.0.raycast(ray)
+        }
+    }
+
+    #[test]
+    fn picks_the_closest_node() {
+        let mut scene = Scene::new();
+        let material = Rc::new(Material::new(Rc::new(Shader { ID: 0 })));
+        let cube: Rc<dyn Drawable> = Rc::new(PickableCube(MeshBvh::new(&MeshData::cube(1.0))));
+        let mut add = |name: &str, x: f32, z: f32| {
+            let id = scene.add_node(name, Transform::from_translation(vec3(x, 0.0, z)), None);
+            scene.set_renderable(id, Some(Renderable::new(cube.clone(), material.clone())));
+            id
+        };
+        let near = add("near", 0.0, 0.0);
+        let far = add("far", 0.0, -5.0);
+        let aside = add("aside", 3.0, 0.0);
+        scene.set_scale(far, vec3(4.0, 4.0, 4.0));
+
+        let bvh = SceneBvh::build(&scene);
+        let ray = Ray::new(Point3::new(0.25, 0.0, 10.0), -Vector3::unit_z());
+        let hit = bvh.pick(&scene, &ray).unwrap();
+        assert_eq!(hit.node, near);
+        assert!((hit.t - 9.5).abs() < 1e-5);
+        assert!((hit.point - Point3::new(0.25, 0.0, 0.5)).magnitude() < 1e-5);
+        assert!(hit.triangle.is_some());
+
+        // past the small cube only the big one is in the way, its t is in world units
+        let ray = Ray::new(Point3::new(1.5, 1.5, 10.0), -Vector3::unit_z());
+        let hit = bvh.pick(&scene, &ray).unwrap();
+        assert_eq!(hit.node, far);
+        assert!((hit.t - 13.0).abs() < 1e-5);
+
+        let sideways = Ray::new(Point3::new(-10.0, 0.0, 0.0), Vector3::unit_x());
+        assert_eq!(bvh.pick(&scene, &sideways).unwrap().node, near);
+        scene.set_visible(near, false);
+        let bvh = SceneBvh::build(&scene);
+        assert_eq!(bvh.pick(&scene, &sideways).unwrap().node, aside);
+        assert!(bvh
+            .pick(
+                &scene,
+                &Ray::new(Point3::new(0.0, 5.0, 0.0), Vector3::unit_y())
+            )
+            .is_none());
+    }
+}
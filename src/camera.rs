@@ -0,0 +1,186 @@
+use cgmath::prelude::*;
+use cgmath::{perspective, vec3, Deg, Matrix4, Point3, Vector3};
+
+use glfw::{CursorMode, Window};
+
+/// What the render loop needs from a camera, shared by the fly and orbit cameras.
+pub trait CameraView {
+    fn position(&self) -> Point3<f32>;
+    fn view_matrix(&self) -> Matrix4<f32>;
+    /// perspective projection for a framebuffer with the given aspect ratio
+    fn projection(&self, aspect: f32) -> Matrix4<f32>;
+}
+
+/// Defines several possible options for camera movement. Used as abstraction to stay away from
+/// window-system specific input methods; negative amounts move backward, left and down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CameraMovement {
+    Forward,
+    Right,
+    Up,
+}
+
+// Default camera values
+const YAW: f32 = -90.0;
+const PITCH: f32 = 0.0;
+const SPEED: f32 = 2.5;
+const SENSITIVITY: f32 = 0.1;
+const ZOOM: f32 = 45.0;
+const MIN_ZOOM: f32 = 1.0;
+const MAX_ZOOM: f32 = 45.0;
+/// stay just short of straight up/down so `front` never becomes parallel to `world_up`
+const MAX_PITCH: f32 = 89.0;
+
+/// First-person fly camera: processes input and calculates the corresponding Euler angles,
+/// vectors and matrices for use in OpenGL
+pub struct Camera {
+    // camera attributes
+    pub position: Point3<f32>,
+    pub front: Vector3<f32>,
+    pub up: Vector3<f32>,
+    pub right: Vector3<f32>,
+    pub world_up: Vector3<f32>,
+    // euler angles, in degrees
+    pub yaw: f32,
+    pub pitch: f32,
+    // camera options
+    pub movement_speed: f32,
+    pub mouse_sensitivity: f32,
+    /// vertical field of view in degrees
+    pub zoom: f32,
+    pub near: f32,
+    pub far: f32,
+}
+
+impl Default for Camera {
+    fn default() -> Camera {
+        Camera::new(Point3::new(0.0, 0.0, 3.0), YAW, PITCH)
+    }
+}
+
+impl Camera {
+    pub fn new(position: Point3<f32>, yaw: f32, pitch: f32) -> Camera {
+        let mut camera = Camera {
+            position,
+            front: vec3(0.0, 0.0, -1.0),
+            up: Vector3::unit_y(),
+            right: Vector3::unit_x(),
+            world_up: Vector3::unit_y(),
+            yaw,
+            pitch: pitch.clamp(-MAX_PITCH, MAX_PITCH),
+            movement_speed: SPEED,
+            mouse_sensitivity: SENSITIVITY,
+            zoom: ZOOM,
+            near: 0.1,
+            far: 100.0,
+        };
+        camera.update_camera_vectors();
+        camera
+    }
+
+    /// Moves the camera; `delta_time` keeps the speed independent of the frame rate
+    /// ------------------------------------------------------------------------
+    pub fn process_keyboard(&mut self, direction: CameraMovement, delta_time: f32) {
+        let velocity = self.movement_speed * delta_time;
+        match direction {
+            CameraMovement::Forward => self.position += self.front * velocity,
+            CameraMovement::Right => self.position += self.right * velocity,
+            CameraMovement::Up => self.position += self.world_up * velocity,
+        }
+    }
+
+    /// Processes mouse movement, offsets are in screen coordinates
+    /// ------------------------------------------------------------------------
+    pub fn process_mouse_movement(&mut self, xoffset: f32, yoffset: f32) {
+        self.yaw += xoffset * self.mouse_sensitivity;
+        self.pitch += yoffset * self.mouse_sensitivity;
+        self.pitch = self.pitch.clamp(-MAX_PITCH, MAX_PITCH);
+        self.update_camera_vectors();
+    }
+
+    /// Processes scroll wheel input, only the vertical axis zooms
+    /// ------------------------------------------------------------------------
+    pub fn process_mouse_scroll(&mut self, yoffset: f32) {
+        self.zoom = (self.zoom - yoffset).clamp(MIN_ZOOM, MAX_ZOOM);
+    }
+
+    /// Jump to another camera's position and look the way it does, e.g. a camera placed in a
+    /// model file. A perspective view also hands over its field of view.
+    /// ------------------------------------------------------------------------
+    pub fn look_through(&mut self, view: &dyn CameraView) {
+        self.position = view.position();
+        // the third row of a view matrix is the camera's backward axis
+        let front = -view.view_matrix().row(2).truncate().normalize();
+        self.yaw = front.z.atan2(front.x).to_degrees();
+        self.pitch = front
+            .y
+            .clamp(-1.0, 1.0)
+            .asin()
+            .to_degrees()
+            .clamp(-MAX_PITCH, MAX_PITCH);
+        let projection = view.projection(1.0);
+        if projection.w.w == 0.0 {
+            let fov = 2.0 * (1.0 / projection.y.y).atan();
+            self.zoom = fov.to_degrees().clamp(MIN_ZOOM, MAX_ZOOM);
+        }
+        self.update_camera_vectors();
+    }
+
+    /// Calculates the front vector from the Camera's (updated) Euler Angles
+    fn update_camera_vectors(&mut self) {
+        let (yaw, pitch) = (self.yaw.to_radians(), self.pitch.to_radians());
+        self.front = vec3(
+            yaw.cos() * pitch.cos(),
+            pitch.sin(),
+            yaw.sin() * pitch.cos(),
+        )
+        .normalize();
+        // normalize the vectors, because their length gets closer to 0 the more you look up or
+        // down which results in slower movement.
+        self.right = self.front.cross(self.world_up).normalize();
+        self.up = self.right.cross(self.front).normalize();
+    }
+}
+
+impl CameraView for Camera {
+    fn position(&self) -> Point3<f32> {
+        self.position
+    }
+
+    /// Returns the view matrix calculated using Euler Angles and the LookAt Matrix
+    fn view_matrix(&self) -> Matrix4<f32> {
+        Matrix4::look_at_rh(self.position, self.position + self.front, self.up)
+    }
+
+    fn projection(&self, aspect: f32) -> Matrix4<f32> {
+        perspective(Deg(self.zoom), aspect, self.near, self.far)
+    }
+}
+
+/// Captures the cursor for mouse look: while captured the cursor is hidden and locked to the
+/// window so its movement can be turned into camera rotation.
+#[derive(Debug, Default)]
+pub struct MouseLook {
+    captured: bool,
+}
+
+impl MouseLook {
+    pub fn is_captured(&self) -> bool {
+        self.captured
+    }
+
+    /// Hide and lock the cursor to the window (or release it)
+    /// ------------------------------------------------------------------------
+    pub fn set_captured(&mut self, window: &mut Window, captured: bool) {
+        self.captured = captured;
+        window.set_cursor_mode(if captured {
+            CursorMode::Disabled
+        } else {
+            CursorMode::Normal
+        });
+    }
+
+    pub fn toggle(&mut self, window: &mut Window) {
+        self.set_captured(window, !self.captured);
+    }
+}
@@ -0,0 +1,328 @@
+#[cfg(test)]
+use std::cell::Cell;
+use std::thread;
+use std::time::Duration;
+
+/// Where the current time (in seconds) comes from.
+/// The render loop uses glfw's timer, tests use `ManualTime` to step frames deterministically.
+pub trait TimeSource {
+    fn now(&self) -> f64;
+
+    /// wait for `seconds`; only used by the frame limiter
+    fn sleep(&self, seconds: f64) {
+        if seconds > 0.0 {
+            thread::sleep(Duration::from_secs_f64(seconds));
+        }
+    }
+}
+
+impl TimeSource for glfw::Glfw {
+    fn now(&self) -> f64 {
+        self.get_time()
+    }
+}
+
+/// Time source that only moves when told to.
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub struct ManualTime {
+    time: Cell<f64>,
+}
+
+#[cfg(test)]
+impl ManualTime {
+    pub fn new(start: f64) -> ManualTime {
+        ManualTime {
+            time: Cell::new(start),
+        }
+    }
+
+    pub fn advance(&self, seconds: f64) {
+        self.time.set(self.time.get() + seconds);
+    }
+}
+
+#[cfg(test)]
+impl TimeSource for ManualTime {
+    fn now(&self) -> f64 {
+        self.time.get()
+    }
+
+    // sleeping just moves the clock forward
+    fn sleep(&self, seconds: f64) {
+        if seconds > 0.0 {
+            self.advance(seconds);
+        }
+    }
+}
+
+impl<T: TimeSource> TimeSource for &T {
+    fn now(&self) -> f64 {
+        (**self).now()
+    }
+
+    fn sleep(&self, seconds: f64) {
+        (**self).sleep(seconds)
+    }
+}
+
+/// Pausable, scalable game time built from raw time deltas.
+#[derive(Debug, Clone)]
+pub struct Clock {
+    time: f64,
+    scale: f64,
+    paused: bool,
+}
+
+impl Default for Clock {
+    fn default() -> Clock {
+        Clock {
+            time: 0.0,
+            scale: 1.0,
+            paused: false,
+        }
+    }
+}
+
+impl Clock {
+    /// advance by a raw delta, returns the scaled delta (0 while paused)
+    /// ------------------------------------------------------------------------
+    pub fn advance(&mut self, raw_delta: f64) -> f64 {
+        let delta = if self.paused {
+            0.0
+        } else {
+            raw_delta * self.scale
+        };
+        self.time += delta;
+        delta
+    }
+
+    /// accumulated game time in seconds
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    pub fn scale(&self) -> f64 {
+        self.scale
+    }
+
+    pub fn set_scale(&mut self, scale: f64) {
+        self.scale = scale.max(0.0);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+}
+
+/// Smoothed frame statistics, measured in real (unscaled) time.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FrameStats {
+    /// exponentially smoothed frame time in seconds
+    pub frame_time: f64,
+    /// frames per second derived from `frame_time`
+    pub fps: f64,
+    pub frame_count: u64,
+}
+
+impl FrameStats {
+    /// weight of the newest sample in the moving average
+    const SMOOTHING: f64 = 0.1;
+
+    fn push(&mut self, raw_delta: f64) {
+        self.frame_time = if self.frame_count == 0 {
+            raw_delta
+        } else {
+            self.frame_time + (raw_delta - self.frame_time) * Self::SMOOTHING
+        };
+        self.fps = if self.frame_time > 0.0 {
+            1.0 / self.frame_time
+        } else {
+            0.0
+        };
+        self.frame_count += 1;
+    }
+}
+
+/// Frame timing for the render loop: variable delta time, a fixed-timestep accumulator for
+/// simulation updates and an optional frame rate cap.
+///
+/// ```ignore
+/// while !window.should_close() {
+///     let dt = timer.begin_frame();
+///     while timer.step() {
+///         simulate(timer.fixed_step());
+///     }
+///     render(dt, timer.alpha());
+///     window.swap_buffers();
+///     timer.end_frame();
+/// }
+/// ```
+pub struct FrameTimer<T: TimeSource> {
+    source: T,
+    pub clock: Clock,
+    fixed_step: f64,
+    /// upper bound for one frame's delta, keeps the accumulator from spiralling after a stall
+    max_delta: f64,
+    accumulator: f64,
+    target_frame_time: Option<f64>,
+    frame_start: f64,
+    raw_delta: f64,
+    stats: FrameStats,
+}
+
+impl<T: TimeSource> FrameTimer<T> {
+    pub fn new(source: T, fixed_step: f64) -> FrameTimer<T> {
+        let frame_start = source.now();
+        FrameTimer {
+            source,
+            clock: Clock::default(),
+            fixed_step,
+            max_delta: 0.25,
+            accumulator: 0.0,
+            target_frame_time: None,
+            frame_start,
+            raw_delta: 0.0,
+            stats: FrameStats::default(),
+        }
+    }
+
+    /// cap the frame rate; `None` or 0 disables the limiter
+    /// ------------------------------------------------------------------------
+    pub fn set_fps_cap(&mut self, fps: Option<f64>) {
+        self.target_frame_time = fps.filter(|fps| *fps > 0.0).map(|fps| 1.0 / fps);
+    }
+
+    /// Start a new frame. Returns the scaled delta time since the previous frame.
+    /// ------------------------------------------------------------------------
+    pub fn begin_frame(&mut self) -> f64 {
+        let now = self.source.now();
+        let raw_delta = (now - self.frame_start).max(0.0);
+        self.frame_start = now;
+        self.advance_frame(raw_delta)
+    }
+
+    /// Start a new frame with a given raw delta instead of reading the time source, e.g. when
+    /// replaying recorded frame times.
+    /// ------------------------------------------------------------------------
+    pub fn advance_frame(&mut self, raw_delta: f64) -> f64 {
+        self.raw_delta = raw_delta;
+        self.stats.push(raw_delta);
+
+        let delta = self.clock.advance(raw_delta.min(self.max_delta));
+        self.accumulator += delta;
+        delta
+    }
+
+    /// Consume one fixed step from the accumulator; call in a loop until it returns false.
+    /// ------------------------------------------------------------------------
+    pub fn step(&mut self) -> bool {
+        if self.fixed_step > 0.0 && self.accumulator >= self.fixed_step {
+            self.accumulator -= self.fixed_step;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Interpolation factor between the last two simulation states, in [0, 1).
+    /// ------------------------------------------------------------------------
+    pub fn alpha(&self) -> f32 {
+        if self.fixed_step > 0.0 {
+            (self.accumulator / self.fixed_step) as f32
+        } else {
+            0.0
+        }
+    }
+
+    /// Finish the frame, sleeping if a frame rate cap is set.
+    /// ------------------------------------------------------------------------
+    pub fn end_frame(&mut self) {
+        if let Some(target) = self.target_frame_time {
+            let elapsed = self.source.now() - self.frame_start;
+            self.source.sleep(target - elapsed);
+        }
+    }
+
+    /// game time each `step` simulates
+    pub fn fixed_step(&self) -> f64 {
+        self.fixed_step
+    }
+
+    /// real time between the start of the previous and the current frame
+    pub fn raw_delta(&self) -> f64 {
+        self.raw_delta
+    }
+
+    /// scaled game time
+    pub fn time(&self) -> f64 {
+        self.clock.time()
+    }
+
+    pub fn stats(&self) -> FrameStats {
+        self.stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixed_steps_and_alpha() {
+        let time = ManualTime::new(0.0);
+        let mut timer = FrameTimer::new(&time, 0.01);
+
+        time.advance(0.025);
+        assert!((timer.begin_frame() - 0.025).abs() < 1e-9);
+        let mut steps = 0;
+        while timer.step() {
+            steps += 1;
+        }
+        assert_eq!(steps, 2);
+        assert!((timer.alpha() - 0.5).abs() < 1e-4);
+    }
+
+    #[test]
+    fn pause_and_scale() {
+        let time = ManualTime::new(0.0);
+        let mut timer = FrameTimer::new(&time, 0.01);
+
+        timer.clock.set_scale(0.5);
+        time.advance(0.1);
+        assert!((timer.begin_frame() - 0.05).abs() < 1e-9);
+
+        timer.clock.toggle_pause();
+        time.advance(0.1);
+        assert_eq!(timer.begin_frame(), 0.0);
+        assert!((timer.time() - 0.05).abs() < 1e-9);
+        // stats keep measuring real time
+        assert!((timer.stats().frame_time - 0.1).abs() < 1e-9);
+    }
+
+    #[test]
+    fn limiter_waits_for_the_rest_of_the_frame() {
+        let time = ManualTime::new(0.0);
+        let mut timer = FrameTimer::new(&time, 0.01);
+        timer.set_fps_cap(Some(50.0));
+
+        timer.begin_frame();
+        time.advance(0.005);
+        timer.end_frame();
+        assert!((time.now() - 0.02).abs() < 1e-9);
+    }
+
+    #[test]
+    fn long_stall_is_clamped() {
+        let time = ManualTime::new(0.0);
+        let mut timer = FrameTimer::new(&time, 0.01);
+
+        time.advance(10.0);
+        assert!((timer.begin_frame() - 0.25).abs() < 1e-9);
+        assert_eq!(timer.stats().frame_count, 1);
+    }
+}
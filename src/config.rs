@@ -0,0 +1,309 @@
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+/// How the window is placed on screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DisplayMode {
+    Windowed,
+    /// exclusive fullscreen, switches the monitor to the configured resolution
+    Fullscreen,
+    /// undecorated window covering the whole monitor at its current video mode
+    Borderless,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GlProfile {
+    Core,
+    Compat,
+    Any,
+}
+
+/// Everything needed to create the window and its GL context.
+///
+/// Loaded from a `.toml` or `.ron` file; every field is optional in the file and falls back to
+/// the defaults below (800x600, GL 3.3 core, vsync on).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowConfig {
+    pub title: String,
+    pub width: u32,
+    pub height: u32,
+    pub display_mode: DisplayMode,
+    /// index into the connected monitors, 0 is the primary monitor
+    pub monitor: usize,
+    pub vsync: bool,
+    /// MSAA sample count, 0 disables multisampling
+    pub samples: u32,
+    pub gl_version: (u32, u32),
+    pub gl_profile: GlProfile,
+    pub debug_context: bool,
+    pub resizable: bool,
+    /// false for headless runs such as input replays
+    pub visible: bool,
+    pub clear_color: [f32; 4],
+}
+
+impl Default for WindowConfig {
+    fn default() -> WindowConfig {
+        WindowConfig {
+            title: "LearnOpenGL".to_string(),
+            width: 800,
+            height: 600,
+            display_mode: DisplayMode::Windowed,
+            monitor: 0,
+            vsync: true,
+            samples: 0,
+            gl_version: (3, 3),
+            gl_profile: GlProfile::Core,
+            debug_context: false,
+            resizable: true,
+            visible: true,
+            clear_color: [0.2, 0.3, 0.3, 1.0],
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(PathBuf, std::io::Error),
+    Parse(PathBuf, String),
+    UnknownFormat(PathBuf),
+    Arg(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Io(path, err) => write!(f, "failed to read {}: {}", path.display(), err),
+            ConfigError::Parse(path, err) => write!(f, "{}: {}", path.display(), err),
+            ConfigError::UnknownFormat(path) => write!(
+                f,
+                "{}: unknown config format, expected .toml or .ron",
+                path.display()
+            ),
+            ConfigError::Arg(msg) => write!(f, "invalid argument: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+pub const USAGE: &str = "\
+options:
+    --config <file>          load settings from a .toml or .ron file
+    --title <text>           window title
+    --size <W>x<H>           window size (also --width <W> / --height <H>)
+    --windowed               windowed mode
+    --fullscreen             exclusive fullscreen
+    --borderless             borderless window covering the monitor
+    --monitor <index>        monitor used for fullscreen / borderless
+    --vsync, --no-vsync      toggle vertical sync
+    --msaa <samples>         multisample count, 0 disables MSAA
+    --gl <major>.<minor>     OpenGL context version
+    --profile <core|compat|any>
+    --debug-gl               request a debug context
+    --resizable, --no-resizable
+    --inspector              open a second window sharing the GL resources
+    --model <file>           draw an OBJ, glTF, PLY or STL model instead of the quad
+    --export <file>          write the --model mesh as binary .ply or .stl and exit
+    --export-ascii <file>    the same as text
+    --scene <file>           draw a .ron or .json scene instead of the quad, F5 saves it
+    --record <file>          record input events and frame times
+    --replay <file>          replay a recording headless and check the frame hashes";
+
+impl WindowConfig {
+    /// Load a config file, picking the format from the extension.
+    /// ------------------------------------------------------------------------
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<WindowConfig, ConfigError> {
+        let path = path.as_ref();
+        let text =
+            fs::read_to_string(path).map_err(|err| ConfigError::Io(path.to_path_buf(), err))?;
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => toml::from_str(&text)
+                .map_err(|err| ConfigError::Parse(path.to_path_buf(), err.to_string())),
+            Some("ron") => ron::from_str(&text)
+                .map_err(|err| ConfigError::Parse(path.to_path_buf(), err.to_string())),
+            _ => Err(ConfigError::UnknownFormat(path.to_path_buf())),
+        }
+    }
+
+    /// Apply one window flag, taking its value from `args`. Unknown flags are an error so a
+    /// typo doesn't go unnoticed.
+    /// ------------------------------------------------------------------------
+    fn apply_flag<I: Iterator<Item = String>>(
+        &mut self,
+        flag: &str,
+        args: &mut I,
+    ) -> Result<(), ConfigError> {
+        match flag {
+            "--title" => self.title = value(flag, args.next())?,
+            "--width" => self.width = parse(flag, args.next())?,
+            "--height" => self.height = parse(flag, args.next())?,
+            "--size" => {
+                let size = value(flag, args.next())?;
+                let (width, height) = split_pair(&size, 'x')
+                    .ok_or_else(|| ConfigError::Arg(format!("--size {}", size)))?;
+                self.width = width;
+                self.height = height;
+            }
+            "--windowed" => self.display_mode = DisplayMode::Windowed,
+            "--fullscreen" => self.display_mode = DisplayMode::Fullscreen,
+            "--borderless" => self.display_mode = DisplayMode::Borderless,
+            "--monitor" => self.monitor = parse(flag, args.next())?,
+            "--vsync" => self.vsync = true,
+            "--no-vsync" => self.vsync = false,
+            "--msaa" => self.samples = parse(flag, args.next())?,
+            "--gl" => {
+                let version = value(flag, args.next())?;
+                self.gl_version = split_pair(&version, '.')
+                    .ok_or_else(|| ConfigError::Arg(format!("--gl {}", version)))?;
+            }
+            "--profile" => {
+                self.gl_profile = match value(flag, args.next())?.as_str() {
+                    "core" => GlProfile::Core,
+                    "compat" => GlProfile::Compat,
+                    "any" => GlProfile::Any,
+                    other => return Err(ConfigError::Arg(format!("--profile {}", other))),
+                }
+            }
+            "--debug-gl" => self.debug_context = true,
+            "--resizable" => self.resizable = true,
+            "--no-resizable" => self.resizable = false,
+            other => return Err(ConfigError::Arg(format!("unknown flag {}", other))),
+        }
+        Ok(())
+    }
+}
+
+/// Everything the command line asks for: the window config and what to do with the window.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RunArgs {
+    pub window: WindowConfig,
+    /// open a second window sharing the GL resources
+    pub inspector: bool,
+    /// OBJ, glTF, PLY or STL model drawn instead of the quad
+    pub model: Option<String>,
+    /// scene file drawn instead of the quad
+    pub scene: Option<String>,
+    /// file the model mesh is written to without opening a window, and whether as text
+    pub export: Option<(String, bool)>,
+    /// file recording the input events and frame times
+    pub record: Option<String>,
+    /// recording to play back headless
+    pub replay: Option<String>,
+}
+
+impl RunArgs {
+    /// Parse the command line arguments (without the program name). `--config <file>` is read
+    /// first, every other window flag overrides the file; for the rest the last flag wins.
+    /// ------------------------------------------------------------------------
+    pub fn from_args<I: IntoIterator<Item = String>>(args: I) -> Result<RunArgs, ConfigError> {
+        let args: Vec<String> = args.into_iter().collect();
+        let window = match args.iter().position(|arg| arg == "--config") {
+            Some(i) => {
+                let path = args
+                    .get(i + 1)
+                    .ok_or_else(|| ConfigError::Arg("--config needs a file".to_string()))?;
+                WindowConfig::load(path)?
+            }
+            None => WindowConfig::default(),
+        };
+        let mut run = RunArgs {
+            window,
+            ..RunArgs::default()
+        };
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--config" => {
+                    // already loaded
+                    args.next();
+                }
+                "--inspector" => run.inspector = true,
+                "--model" => run.model = Some(value(&arg, args.next())?),
+                "--scene" => run.scene = Some(value(&arg, args.next())?),
+                "--export" => run.export = Some((value(&arg, args.next())?, false)),
+                "--export-ascii" => run.export = Some((value(&arg, args.next())?, true)),
+                "--record" => run.record = Some(value(&arg, args.next())?),
+                "--replay" => run.replay = Some(value(&arg, args.next())?),
+                flag => run.window.apply_flag(flag, &mut args)?,
+            }
+        }
+        Ok(run)
+    }
+}
+
+fn value(flag: &str, value: Option<String>) -> Result<String, ConfigError> {
+    value.ok_or_else(|| ConfigError::Arg(format!("{} needs a value", flag)))
+}
+
+fn parse<T: std::str::FromStr>(flag: &str, arg: Option<String>) -> Result<T, ConfigError> {
+    let arg = value(flag, arg)?;
+    arg.parse()
+        .map_err(|_| ConfigError::Arg(format!("{} {}", flag, arg)))
+}
+
+fn split_pair(text: &str, separator: char) -> Option<(u32, u32)> {
+    let (a, b) = text.split_once(separator)?;
+    Some((a.trim().parse().ok()?, b.trim().parse().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn flags_override_the_defaults() {
+        let config = RunArgs::from_args(args(
+            "--size 1024x768 --borderless --no-vsync --msaa 4 --gl 4.1",
+        ))
+        .unwrap()
+        .window;
+        assert_eq!((config.width, config.height), (1024, 768));
+        assert_eq!(config.display_mode, DisplayMode::Borderless);
+        assert!(!config.vsync);
+        assert_eq!(config.samples, 4);
+        assert_eq!(config.gl_version, (4, 1));
+        // the last flag wins
+        let config = RunArgs::from_args(args("--width 10 --width 20 --title demo"))
+            .unwrap()
+            .window;
+        assert_eq!((config.width, config.title.as_str()), (20, "demo"));
+    }
+
+    #[test]
+    fn run_flags_mix_with_window_flags() {
+        let run = RunArgs::from_args(args(
+            "--model scan.ply --export scan.stl --inspector --replay run.rec --msaa 2",
+        ))
+        .unwrap();
+        assert_eq!(run.window.samples, 2);
+        assert_eq!(run.model.as_deref(), Some("scan.ply"));
+        assert_eq!(run.export, Some(("scan.stl".to_string(), false)));
+        assert!(run.inspector);
+        assert_eq!(run.replay.as_deref(), Some("run.rec"));
+        assert_eq!((run.scene, run.record), (None, None));
+    }
+
+    #[test]
+    fn bad_flags_are_errors() {
+        let error = |line| RunArgs::from_args(args(line)).unwrap_err().to_string();
+        assert_eq!(
+            error("--fulscreen"),
+            "invalid argument: unknown flag --fulscreen"
+        );
+        assert_eq!(error("--size 800"), "invalid argument: --size 800");
+        assert_eq!(error("--msaa"), "invalid argument: --msaa needs a value");
+        assert_eq!(error("--profile es"), "invalid argument: --profile es");
+        assert_eq!(error("--model"), "invalid argument: --model needs a value");
+    }
+}
@@ -0,0 +1,534 @@
+use gl::types::*;
+
+use crate::surface::{ResizeListener, Surface};
+
+/// Offscreen multisampled render target that is resolved into the default framebuffer.
+///
+/// Changing the sample count at runtime only recreates these renderbuffers, the window and its
+/// context stay untouched. With `samples == 0` everything renders straight to the window.
+///
+/// An offscreen target resolves into a single-sample framebuffer of its own instead (and
+/// renders into it without MSAA), then copies that to the window. Its pixels can be read back
+/// even when the window is hidden or covered, where the default framebuffer's are undefined.
+pub struct MsaaTarget {
+    samples: u32,
+    max_samples: u32,
+    size: (i32, i32),
+    fbo: GLuint,
+    color: GLuint,
+    depth_stencil: GLuint,
+    offscreen: bool,
+    resolve_fbo: GLuint,
+    resolve_color: GLuint,
+    resolve_depth_stencil: GLuint,
+}
+
+impl MsaaTarget {
+    pub fn new(samples: u32, width: i32, height: i32) -> MsaaTarget {
+        let mut max_samples = 0;
+        unsafe { gl::GetIntegerv(gl::MAX_SAMPLES, &mut max_samples) };
+        let mut target = MsaaTarget {
+            samples: 0,
+            max_samples: max_samples.max(0) as u32,
+            size: (width, height),
+            fbo: 0,
+            color: 0,
+            depth_stencil: 0,
+            offscreen: false,
+            resolve_fbo: 0,
+            resolve_color: 0,
+            resolve_depth_stencil: 0,
+        };
+        target.set_samples(samples);
+        target
+    }
+
+    /// Like `new`, resolving into a framebuffer that `read_framebuffer` can be read from.
+    /// ------------------------------------------------------------------------
+    pub fn offscreen(samples: u32, width: i32, height: i32) -> MsaaTarget {
+        let mut target = MsaaTarget::new(0, width, height);
+        target.offscreen = true;
+        target.set_samples(samples);
+        target
+    }
+
+    pub fn samples(&self) -> u32 {
+        self.samples
+    }
+
+    /// `GL_MAX_SAMPLES`, read once when the target is created
+    pub fn max_samples(&self) -> u32 {
+        self.max_samples
+    }
+
+    /// Change the sample count, clamped to `max_samples`.
+    /// ------------------------------------------------------------------------
+    pub fn set_samples(&mut self, samples: u32) {
+        self.samples = samples.min(self.max_samples);
+        self.recreate();
+    }
+
+    /// Bind for rendering the scene.
+    /// ------------------------------------------------------------------------
+    pub fn bind(&self) {
+        let fbo = if self.fbo != 0 {
+            self.fbo
+        } else {
+            self.resolve_fbo
+        };
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, fbo);
+            gl::Viewport(0, 0, self.size.0, self.size.1);
+        }
+    }
+
+    /// Resolve the samples into the default framebuffer, through the offscreen one when there
+    /// is one, and leave it bound.
+    /// ------------------------------------------------------------------------
+    pub fn resolve(&self) {
+        unsafe {
+            if self.fbo != 0 {
+                blit(self.fbo, self.resolve_fbo, self.size);
+            }
+            if self.resolve_fbo != 0 {
+                blit(self.resolve_fbo, 0, self.size);
+            }
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+    }
+
+    /// the single-sample framebuffer holding the resolved frame, 0 for the window's
+    pub fn read_framebuffer(&self) -> GLuint {
+        self.resolve_fbo
+    }
+
+    fn recreate(&mut self) {
+        self.release();
+        let (width, height) = self.size;
+        if width <= 0 || height <= 0 {
+            return;
+        }
+        unsafe {
+            if self.samples > 0 {
+                let samples = self.samples as GLsizei;
+                (self.fbo, self.color, self.depth_stencil) = create_target(samples, width, height);
+            }
+            if self.offscreen {
+                (
+                    self.resolve_fbo,
+                    self.resolve_color,
+                    self.resolve_depth_stencil,
+                ) = create_target(0, width, height);
+            }
+        }
+    }
+
+    fn release(&mut self) {
+        unsafe {
+            for (fbo, color, depth_stencil) in [
+                (self.fbo, self.color, self.depth_stencil),
+                (
+                    self.resolve_fbo,
+                    self.resolve_color,
+                    self.resolve_depth_stencil,
+                ),
+            ] {
+                if fbo != 0 {
+                    gl::DeleteFramebuffers(1, &fbo);
+                    gl::DeleteRenderbuffers(1, &color);
+                    gl::DeleteRenderbuffers(1, &depth_stencil);
+                }
+            }
+        }
+        self.fbo = 0;
+        self.color = 0;
+        self.depth_stencil = 0;
+        self.resolve_fbo = 0;
+        self.resolve_color = 0;
+        self.resolve_depth_stencil = 0;
+    }
+}
+
+/// Framebuffer with RGBA8 colour and depth/stencil renderbuffers, multisampled unless
+/// `samples` is 0. Returns the framebuffer and the two renderbuffers.
+unsafe fn create_target(samples: GLsizei, width: i32, height: i32) -> (GLuint, GLuint, GLuint) {
+    let (mut fbo, mut color, mut depth_stencil) = (0, 0, 0);
+    gl::GenFramebuffers(1, &mut fbo);
+    gl::BindFramebuffer(gl::FRAMEBUFFER, fbo);
+
+    gl::GenRenderbuffers(1, &mut color);
+    gl::BindRenderbuffer(gl::RENDERBUFFER, color);
+    gl::RenderbufferStorageMultisample(gl::RENDERBUFFER, samples, gl::RGBA8, width, height);
+    gl::FramebufferRenderbuffer(
+        gl::FRAMEBUFFER,
+        gl::COLOR_ATTACHMENT0,
+        gl::RENDERBUFFER,
+        color,
+    );
+
+    gl::GenRenderbuffers(1, &mut depth_stencil);
+    gl::BindRenderbuffer(gl::RENDERBUFFER, depth_stencil);
+    gl::RenderbufferStorageMultisample(
+        gl::RENDERBUFFER,
+        samples,
+        gl::DEPTH24_STENCIL8,
+        width,
+        height,
+    );
+    gl::FramebufferRenderbuffer(
+        gl::FRAMEBUFFER,
+        gl::DEPTH_STENCIL_ATTACHMENT,
+        gl::RENDERBUFFER,
+        depth_stencil,
+    );
+
+    if gl::CheckFramebufferStatus(gl::FRAMEBUFFER) != gl::FRAMEBUFFER_COMPLETE {
+        println!("ERROR::FRAMEBUFFER:: render target is not complete!");
+    }
+    gl::BindRenderbuffer(gl::RENDERBUFFER, 0);
+    gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+    (fbo, color, depth_stencil)
+}
+
+/// copy the colour of one framebuffer to another of the same size
+unsafe fn blit(from: GLuint, to: GLuint, (width, height): (i32, i32)) {
+    gl::BindFramebuffer(gl::READ_FRAMEBUFFER, from);
+    gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, to);
+    gl::BlitFramebuffer(
+        0,
+        0,
+        width,
+        height,
+        0,
+        0,
+        width,
+        height,
+        gl::COLOR_BUFFER_BIT,
+        gl::NEAREST,
+    );
+}
+
+impl ResizeListener for MsaaTarget {
+    fn on_resize(&mut self, surface: &Surface) {
+        if self.size != surface.framebuffer_size {
+            self.size = surface.framebuffer_size;
+            self.recreate();
+        }
+    }
+}
+
+impl Drop for MsaaTarget {
+    fn drop(&mut self) {
+        self.release();
+    }
+}
+
+/// Offscreen target for object IDs: an `R32UI` colour attachment with its own depth buffer,
+/// single sampled so every pixel holds exactly one ID. 0 is cleared in as "nothing".
+pub struct IdTarget {
+    size: (i32, i32),
+    fbo: GLuint,
+    ids: GLuint,
+    depth: GLuint,
+}
+
+impl IdTarget {
+    pub fn new(width: i32, height: i32) -> IdTarget {
+        let mut target = IdTarget {
+            size: (width, height),
+            fbo: 0,
+            ids: 0,
+            depth: 0,
+        };
+        target.recreate();
+        target
+    }
+
+    /// Bind for rendering and clear the IDs to 0 and the depth to 1.
+    /// ------------------------------------------------------------------------
+    pub fn bind(&self) {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.fbo);
+            gl::Viewport(0, 0, self.size.0, self.size.1);
+            let nothing: [GLuint; 4] = [0; 4];
+            gl::ClearBufferuiv(gl::COLOR, 0, nothing.as_ptr());
+            gl::ClearBufferfv(gl::DEPTH, 0, &1.0);
+        }
+    }
+
+    /// ID at a framebuffer pixel (origin bottom left), 0 outside the target. Waits for the
+    /// GPU to finish drawing, so read once per pick rather than every frame.
+    /// ------------------------------------------------------------------------
+    pub fn read(&self, x: i32, y: i32) -> u32 {
+        let (width, height) = self.size;
+        if self.fbo == 0 || x < 0 || y < 0 || x >= width || y >= height {
+            return 0;
+        }
+        let mut id: GLuint = 0;
+        unsafe {
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.fbo);
+            gl::ReadBuffer(gl::COLOR_ATTACHMENT0);
+            gl::ReadPixels(
+                x,
+                y,
+                1,
+                1,
+                gl::RED_INTEGER,
+                gl::UNSIGNED_INT,
+                &mut id as *mut GLuint as *mut _,
+            );
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, 0);
+        }
+        id
+    }
+
+    fn recreate(&mut self) {
+        self.release();
+        let (width, height) = self.size;
+        if width <= 0 || height <= 0 {
+            return;
+        }
+        unsafe {
+            gl::GenFramebuffers(1, &mut self.fbo);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.fbo);
+
+            gl::GenRenderbuffers(1, &mut self.ids);
+            gl::BindRenderbuffer(gl::RENDERBUFFER, self.ids);
+            gl::RenderbufferStorage(gl::RENDERBUFFER, gl::R32UI, width, height);
+            gl::FramebufferRenderbuffer(
+                gl::FRAMEBUFFER,
+                gl::COLOR_ATTACHMENT0,
+                gl::RENDERBUFFER,
+                self.ids,
+            );
+
+            gl::GenRenderbuffers(1, &mut self.depth);
+            gl::BindRenderbuffer(gl::RENDERBUFFER, self.depth);
+            gl::RenderbufferStorage(gl::RENDERBUFFER, gl::DEPTH_COMPONENT24, width, height);
+            gl::FramebufferRenderbuffer(
+                gl::FRAMEBUFFER,
+                gl::DEPTH_ATTACHMENT,
+                gl::RENDERBUFFER,
+                self.depth,
+            );
+
+            if gl::CheckFramebufferStatus(gl::FRAMEBUFFER) != gl::FRAMEBUFFER_COMPLETE {
+                println!("ERROR::FRAMEBUFFER:: ID framebuffer is not complete!");
+            }
+            gl::BindRenderbuffer(gl::RENDERBUFFER, 0);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+    }
+
+    fn release(&mut self) {
+        unsafe {
+            if self.fbo != 0 {
+                gl::DeleteFramebuffers(1, &self.fbo);
+                gl::DeleteRenderbuffers(1, &self.ids);
+                gl::DeleteRenderbuffers(1, &self.depth);
+            }
+        }
+        self.fbo = 0;
+        self.ids = 0;
+        self.depth = 0;
+    }
+}
+
+impl ResizeListener for IdTarget {
+    fn on_resize(&mut self, surface: &Surface) {
+        if self.size != surface.framebuffer_size {
+            self.size = surface.framebuffer_size;
+            self.recreate();
+        }
+    }
+}
+
+impl Drop for IdTarget {
+    fn drop(&mut self) {
+        self.release();
+    }
+}
+
+/// Depth-only target for shadow maps: a square `DEPTH_COMPONENT24` texture array with one
+/// layer per map, rendered a layer at a time. Depth outside the maps reads as 1, the far
+/// plane, so nothing beyond them is shadowed.
+pub struct DepthArrayTarget {
+    resolution: i32,
+    layers: i32,
+    fbo: GLuint,
+    depth: GLuint,
+}
+
+impl DepthArrayTarget {
+    pub fn new(resolution: i32, layers: i32) -> DepthArrayTarget {
+        let mut target = DepthArrayTarget {
+            resolution: resolution.max(1),
+            layers: layers.max(1),
+            fbo: 0,
+            depth: 0,
+        };
+        unsafe {
+            gl::GenTextures(1, &mut target.depth);
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, target.depth);
+            gl::TexImage3D(
+                gl::TEXTURE_2D_ARRAY,
+                0,
+                gl::DEPTH_COMPONENT24 as GLint,
+                target.resolution,
+                target.resolution,
+                target.layers,
+                0,
+                gl::DEPTH_COMPONENT,
+                gl::FLOAT,
+                std::ptr::null(),
+            );
+            // filtering happens in the shader, which compares each texel itself
+            let nearest = gl::NEAREST as GLint;
+            gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_MIN_FILTER, nearest);
+            gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_MAG_FILTER, nearest);
+            let border = gl::CLAMP_TO_BORDER as GLint;
+            gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_WRAP_S, border);
+            gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_WRAP_T, border);
+            let far = [1.0f32; 4];
+            gl::TexParameterfv(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_BORDER_COLOR, far.as_ptr());
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, 0);
+
+            gl::GenFramebuffers(1, &mut target.fbo);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, target.fbo);
+            gl::FramebufferTextureLayer(gl::FRAMEBUFFER, gl::DEPTH_ATTACHMENT, target.depth, 0, 0);
+            gl::DrawBuffer(gl::NONE);
+            gl::ReadBuffer(gl::NONE);
+            if gl::CheckFramebufferStatus(gl::FRAMEBUFFER) != gl::FRAMEBUFFER_COMPLETE {
+                println!("ERROR::FRAMEBUFFER:: depth framebuffer is not complete!");
+            }
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+        target
+    }
+
+    /// Bind for rendering into `layer` and clear its depth to 1.
+    /// ------------------------------------------------------------------------
+    pub fn bind_layer(&self, layer: i32) {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.fbo);
+            gl::FramebufferTextureLayer(
+                gl::FRAMEBUFFER,
+                gl::DEPTH_ATTACHMENT,
+                self.depth,
+                0,
+                layer,
+            );
+            gl::Viewport(0, 0, self.resolution, self.resolution);
+            gl::ClearBufferfv(gl::DEPTH, 0, &1.0);
+        }
+    }
+
+    /// bind the depth texture array to texture unit `unit`
+    pub unsafe fn bind_texture(&self, unit: u32) {
+        gl::ActiveTexture(gl::TEXTURE0 + unit);
+        gl::BindTexture(gl::TEXTURE_2D_ARRAY, self.depth);
+        gl::ActiveTexture(gl::TEXTURE0);
+    }
+}
+
+impl Drop for DepthArrayTarget {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteFramebuffers(1, &self.fbo);
+            gl::DeleteTextures(1, &self.depth);
+        }
+    }
+}
+
+/// Depth-only cubemap target for point light shadows, rendered a face at a time.
+pub struct DepthCubeTarget {
+    resolution: i32,
+    fbo: GLuint,
+    cube: GLuint,
+}
+
+impl DepthCubeTarget {
+    pub fn new(resolution: i32) -> DepthCubeTarget {
+        let mut target = DepthCubeTarget {
+            resolution: resolution.max(1),
+            fbo: 0,
+            cube: 0,
+        };
+        unsafe {
+            gl::GenTextures(1, &mut target.cube);
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, target.cube);
+            for face in 0..6 {
+                gl::TexImage2D(
+                    gl::TEXTURE_CUBE_MAP_POSITIVE_X + face,
+                    0,
+                    gl::DEPTH_COMPONENT24 as GLint,
+                    target.resolution,
+                    target.resolution,
+                    0,
+                    gl::DEPTH_COMPONENT,
+                    gl::FLOAT,
+                    std::ptr::null(),
+                );
+            }
+            let nearest = gl::NEAREST as GLint;
+            gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_MIN_FILTER, nearest);
+            gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_MAG_FILTER, nearest);
+            let edge = gl::CLAMP_TO_EDGE as GLint;
+            gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_WRAP_S, edge);
+            gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_WRAP_T, edge);
+            gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_WRAP_R, edge);
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, 0);
+
+            gl::GenFramebuffers(1, &mut target.fbo);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, target.fbo);
+            gl::FramebufferTexture2D(
+                gl::FRAMEBUFFER,
+                gl::DEPTH_ATTACHMENT,
+                gl::TEXTURE_CUBE_MAP_POSITIVE_X,
+                target.cube,
+                0,
+            );
+            gl::DrawBuffer(gl::NONE);
+            gl::ReadBuffer(gl::NONE);
+            if gl::CheckFramebufferStatus(gl::FRAMEBUFFER) != gl::FRAMEBUFFER_COMPLETE {
+                println!("ERROR::FRAMEBUFFER:: depth cubemap framebuffer is not complete!");
+            }
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+        target
+    }
+
+    /// Bind for rendering into `face` (0 to 5 for +X, -X, +Y, -Y, +Z, -Z) and clear its
+    /// depth to 1.
+    /// ------------------------------------------------------------------------
+    pub fn bind_face(&self, face: u32) {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.fbo);
+            gl::FramebufferTexture2D(
+                gl::FRAMEBUFFER,
+                gl::DEPTH_ATTACHMENT,
+                gl::TEXTURE_CUBE_MAP_POSITIVE_X + face,
+                self.cube,
+                0,
+            );
+            gl::Viewport(0, 0, self.resolution, self.resolution);
+            gl::ClearBufferfv(gl::DEPTH, 0, &1.0);
+        }
+    }
+
+    /// bind the cubemap to texture unit `unit`
+    pub unsafe fn bind_texture(&self, unit: u32) {
+        gl::ActiveTexture(gl::TEXTURE0 + unit);
+        gl::BindTexture(gl::TEXTURE_CUBE_MAP, self.cube);
+        gl::ActiveTexture(gl::TEXTURE0);
+    }
+}
+
+impl Drop for DepthCubeTarget {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteFramebuffers(1, &self.fbo);
+            gl::DeleteTextures(1, &self.cube);
+        }
+    }
+}
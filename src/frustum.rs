@@ -0,0 +1,200 @@
+use cgmath::prelude::*;
+use cgmath::{Matrix4, Point3, Vector3, Vector4};
+
+use crate::bounds::{Aabb, BoundingSphere};
+
+/// Plane `dot(normal, p) + d = 0`, with positive distances on the side `normal` points to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Plane {
+    pub normal: Vector3<f32>,
+    pub d: f32,
+}
+
+impl Plane {
+    /// Plane from the coefficients `(a, b, c, d)` of `ax + by + cz + d = 0`, scaled so the
+    /// normal has unit length. A zero normal (the far plane of an infinite projection) is
+    /// kept as is, which puts every point at distance `d`.
+    /// ------------------------------------------------------------------------
+    pub fn from_coefficients(coefficients: Vector4<f32>) -> Plane {
+        let normal = coefficients.truncate();
+        let length = normal.magnitude();
+        if length > f32::EPSILON {
+            Plane {
+                normal: normal / length,
+                d: coefficients.w / length,
+            }
+        } else {
+            Plane {
+                normal,
+                d: coefficients.w,
+            }
+        }
+    }
+
+    /// signed distance, positive in front of the plane
+    pub fn distance(&self, point: Point3<f32>) -> f32 {
+        self.normal.dot(point.to_vec()) + self.d
+    }
+}
+
+/// The six planes of a view volume, normals pointing inwards, in the order left, right,
+/// bottom, top, near, far.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frustum {
+    pub planes: [Plane; 6],
+}
+
+impl Frustum {
+    /// Extract the planes from a view-projection matrix (Gribb and Hartmann): a point is
+    /// inside when `-w <= x, y, z <= w` in clip space, so each plane is row 3 plus or minus
+    /// one of the other rows. Planes come out in the space the matrix maps from, world space
+    /// for `projection * view`.
+    /// ------------------------------------------------------------------------
+    pub fn from_matrix(view_projection: &Matrix4<f32>) -> Frustum {
+        let m = view_projection;
+        let w = m.row(3);
+        let plane = |row: usize, sign: f32| Plane::from_coefficients(w + m.row(row) * sign);
+        Frustum {
+            planes: [
+                plane(0, 1.0),
+                plane(0, -1.0),
+                plane(1, 1.0),
+                plane(1, -1.0),
+                plane(2, 1.0),
+                plane(2, -1.0),
+            ],
+        }
+    }
+
+    #[cfg(test)]
+    pub fn contains(&self, point: Point3<f32>) -> bool {
+        self.planes.iter().all(|plane| plane.distance(point) >= 0.0)
+    }
+
+    /// False only when the sphere is completely behind one of the planes.
+    /// ------------------------------------------------------------------------
+    pub fn intersects_sphere(&self, sphere: &BoundingSphere) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.distance(sphere.center) >= -sphere.radius)
+    }
+
+    /// False only when the box is completely behind one of the planes: for each plane the
+    /// corner furthest along its normal is tested. Boxes near the frustum's corners can pass
+    /// without touching it, which only costs a draw.
+    /// ------------------------------------------------------------------------
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        self.planes.iter().all(|plane| {
+            let corner = Point3::new(
+                if plane.normal.x >= 0.0 {
+                    aabb.max.x
+                } else {
+                    aabb.min.x
+                },
+                if plane.normal.y >= 0.0 {
+                    aabb.max.y
+                } else {
+                    aabb.min.y
+                },
+                if plane.normal.z >= 0.0 {
+                    aabb.max.z
+                } else {
+                    aabb.min.z
+                },
+            );
+            plane.distance(corner) >= 0.0
+        })
+    }
+}
+
+/// What a culled draw did: objects drawn and objects skipped because they were outside the
+/// frustum.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CullStats {
+    pub visible: usize,
+    pub culled: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::{ortho, perspective, vec3, vec4, Deg};
+
+    fn camera() -> Matrix4<f32> {
+        // at (0, 0, 5) looking down -Z
+        let view = Matrix4::look_at_rh(
+            Point3::new(0.0, 0.0, 5.0),
+            Point3::origin(),
+            Vector3::unit_y(),
+        );
+        perspective(Deg(90.0), 2.0, 1.0, 100.0) * view
+    }
+
+    #[test]
+    fn planes_come_from_the_matrix() {
+        let frustum = Frustum::from_matrix(&camera());
+        let [left, right, bottom, top, near, far] = frustum.planes;
+        let close = |a: Vector3<f32>, b: Vector3<f32>| (a - b).magnitude() < 1e-5;
+        // 90 degrees vertically: top and bottom at 45 degrees
+        let s = 0.5f32.sqrt();
+        assert!(close(bottom.normal, vec3(0.0, s, -s)));
+        assert!(close(top.normal, vec3(0.0, -s, -s)));
+        // horizontally tan(x) = 2, the side planes lean further out
+        assert!(close(left.normal, vec3(1.0, 0.0, -2.0).normalize()));
+        assert!(close(right.normal, vec3(-1.0, 0.0, -2.0).normalize()));
+        assert!(close(near.normal, -Vector3::unit_z()));
+        assert!(close(far.normal, Vector3::unit_z()));
+        // near plane 1 in front of the camera, far plane 100
+        assert!((near.distance(Point3::new(3.0, 7.0, 4.0))).abs() < 1e-4);
+        assert!((far.distance(Point3::new(0.0, 0.0, -95.0))).abs() < 1e-3);
+        // the camera itself is on all four side planes
+        for plane in &frustum.planes[..4] {
+            assert!(plane.distance(Point3::new(0.0, 0.0, 5.0)).abs() < 1e-5);
+        }
+
+        // orthographic: parallel sides
+        let box_view = Frustum::from_matrix(&ortho(-2.0, 2.0, -1.0, 1.0, 0.5, 10.0));
+        assert!(close(box_view.planes[0].normal, Vector3::unit_x()));
+        assert!((box_view.planes[0].d - 2.0).abs() < 1e-6);
+        assert!((box_view.planes[3].d - 1.0).abs() < 1e-6);
+
+        // an infinite far plane accepts everything
+        let infinite = Plane::from_coefficients(vec4(0.0, 0.0, 0.0, 0.2));
+        assert!(infinite.distance(Point3::new(0.0, 0.0, -1e9)) > 0.0);
+    }
+
+    #[test]
+    fn bounding_volumes_against_the_frustum() {
+        let frustum = Frustum::from_matrix(&camera());
+        assert!(frustum.contains(Point3::origin()));
+        assert!(!frustum.contains(Point3::new(0.0, 0.0, 6.0)));
+        assert!(!frustum.contains(Point3::new(0.0, 6.0, 0.0)));
+
+        let at = |x: f32, y: f32, z: f32, half: f32| {
+            Aabb::new(
+                Point3::new(x - half, y - half, z - half),
+                Point3::new(x + half, y + half, z + half),
+            )
+        };
+        let sphere = |aabb: Aabb| BoundingSphere::from(aabb);
+        let cases = [
+            // inside, straddling the top plane, above it, behind the camera, past the far plane
+            (at(0.0, 0.0, 0.0, 1.0), true),
+            (at(0.0, 5.5, 0.0, 1.0), true),
+            (at(0.0, 8.0, 0.0, 1.0), false),
+            (at(0.0, 0.0, 8.0, 1.0), false),
+            (at(0.0, 0.0, -110.0, 1.0), false),
+            // wider than tall: x = 9 is still inside at this depth
+            (at(9.0, 0.0, 0.0, 0.5), true),
+        ];
+        for (aabb, visible) in cases.iter() {
+            assert_eq!(frustum.intersects_aabb(aabb), *visible, "{:?}", aabb);
+            assert_eq!(
+                frustum.intersects_sphere(&sphere(*aabb)),
+                *visible,
+                "{:?}",
+                aabb
+            );
+        }
+    }
+}
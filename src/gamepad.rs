@@ -0,0 +1,258 @@
+use std::fs;
+use std::path::Path;
+
+use glfw::{Action, GamepadAxis, GamepadButton, Glfw, JoystickId};
+
+use crate::config::ConfigError;
+use crate::input::Input;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GamepadEvent {
+    Connected(JoystickId, String),
+    Disconnected(JoystickId),
+}
+
+/// What the active gamepad feeds into `Input` in one frame, after the dead zones: a bit per
+/// `GamepadButton` and the `GamepadAxis` values. All released and centred without a gamepad.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct GamepadInput {
+    pub buttons: u32,
+    pub axes: [f32; GAMEPAD_AXES],
+}
+
+pub const GAMEPAD_AXES: usize = (glfw::ffi::GAMEPAD_AXIS_LAST + 1) as usize;
+
+impl GamepadInput {
+    pub fn apply(&self, input: &mut Input) {
+        for button in (0..=glfw::ffi::GAMEPAD_BUTTON_LAST).filter_map(GamepadButton::from_i32) {
+            input.set_gamepad_button(button, self.buttons & (1 << button as u32) != 0);
+        }
+        for axis in (0..=glfw::ffi::GAMEPAD_AXIS_LAST).filter_map(GamepadAxis::from_i32) {
+            input.set_gamepad_axis(axis, self.axes[axis as usize]);
+        }
+    }
+}
+
+/// Polls glfw joysticks that have a gamepad mapping; the state of the first connected one is
+/// fed into `Input` as `gamepad:*` bindings through `GamepadInput`.
+///
+/// glfw ships with a built-in set of mappings; more can be loaded from an SDL
+/// `gamecontrollerdb.txt` (https://github.com/gabomdq/SDL_GameControllerDB).
+pub struct Gamepads {
+    /// stick values closer to the centre than this are treated as 0
+    pub stick_dead_zone: f32,
+    /// trigger values below this are treated as released
+    pub trigger_dead_zone: f32,
+    connected: Vec<JoystickId>,
+    active: Option<JoystickId>,
+    state: GamepadInput,
+}
+
+impl Default for Gamepads {
+    fn default() -> Gamepads {
+        Gamepads {
+            stick_dead_zone: 0.15,
+            trigger_dead_zone: 0.05,
+            connected: Vec::new(),
+            active: None,
+            state: GamepadInput::default(),
+        }
+    }
+}
+
+const JOYSTICKS: [JoystickId; 16] = [
+    JoystickId::Joystick1,
+    JoystickId::Joystick2,
+    JoystickId::Joystick3,
+    JoystickId::Joystick4,
+    JoystickId::Joystick5,
+    JoystickId::Joystick6,
+    JoystickId::Joystick7,
+    JoystickId::Joystick8,
+    JoystickId::Joystick9,
+    JoystickId::Joystick10,
+    JoystickId::Joystick11,
+    JoystickId::Joystick12,
+    JoystickId::Joystick13,
+    JoystickId::Joystick14,
+    JoystickId::Joystick15,
+    JoystickId::Joystick16,
+];
+
+impl Gamepads {
+    /// Load SDL game controller mappings. Returns the number of mappings in the file.
+    /// ------------------------------------------------------------------------
+    pub fn load_mappings<P: AsRef<Path>>(glfw: &Glfw, path: P) -> Result<usize, ConfigError> {
+        let path = path.as_ref();
+        let text =
+            fs::read_to_string(path).map_err(|err| ConfigError::Io(path.to_path_buf(), err))?;
+        if !glfw.update_gamepad_mappings(&text) {
+            return Err(ConfigError::Parse(
+                path.to_path_buf(),
+                "invalid gamepad mapping".to_string(),
+            ));
+        }
+        Ok(text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .count())
+    }
+
+    /// the gamepad currently driving input
+    pub fn active(&self) -> Option<JoystickId> {
+        self.active
+    }
+
+    /// what the active gamepad held at the last `poll`
+    pub fn state(&self) -> GamepadInput {
+        self.state
+    }
+
+    /// Check for connected / disconnected gamepads and read the active one's state. Call once
+    /// per frame, then apply `state()` before `input.update()`.
+    /// ------------------------------------------------------------------------
+    pub fn poll(&mut self, glfw: &Glfw) -> Vec<GamepadEvent> {
+        let present: Vec<(JoystickId, String)> = JOYSTICKS
+            .iter()
+            .map(|id| glfw.get_joystick(*id))
+            .filter(|joystick| joystick.is_present() && joystick.is_gamepad())
+            .map(|joystick| (joystick.id, joystick.get_gamepad_name().unwrap_or_default()))
+            .collect();
+        let events = self.update_connected(&present);
+
+        // everything is let go of when the active pad goes away
+        self.state = GamepadInput::default();
+        let state = match self
+            .active
+            .and_then(|id| glfw.get_joystick(id).get_gamepad_state())
+        {
+            Some(state) => state,
+            None => return events,
+        };
+        for button in (0..=glfw::ffi::GAMEPAD_BUTTON_LAST).filter_map(GamepadButton::from_i32) {
+            if state.get_button_state(button) == Action::Press {
+                self.state.buttons |= 1 << button as u32;
+            }
+        }
+        let sticks = [
+            (GamepadAxis::AxisLeftX, GamepadAxis::AxisLeftY),
+            (GamepadAxis::AxisRightX, GamepadAxis::AxisRightY),
+        ];
+        for (x_axis, y_axis) in sticks.iter().copied() {
+            let (x, y) = radial_dead_zone(
+                state.get_axis(x_axis),
+                state.get_axis(y_axis),
+                self.stick_dead_zone,
+            );
+            self.state.axes[x_axis as usize] = x;
+            self.state.axes[y_axis as usize] = y;
+        }
+        for trigger in [GamepadAxis::AxisLeftTrigger, GamepadAxis::AxisRightTrigger].iter() {
+            // triggers rest at -1, remap to 0..1
+            let value = (state.get_axis(*trigger) + 1.0) / 2.0;
+            self.state.axes[*trigger as usize] = dead_zone(value, self.trigger_dead_zone);
+        }
+        events
+    }
+
+    /// Diff the gamepads present now against the known ones, oldest first, and keep the
+    /// active one while it stays connected, falling back to the oldest.
+    /// ------------------------------------------------------------------------
+    fn update_connected(&mut self, present: &[(JoystickId, String)]) -> Vec<GamepadEvent> {
+        let mut events = Vec::new();
+        self.connected.retain(|id| {
+            let still = present.iter().any(|(present, _)| present == id);
+            if !still {
+                events.push(GamepadEvent::Disconnected(*id));
+            }
+            still
+        });
+        for (id, name) in present {
+            if !self.connected.contains(id) {
+                self.connected.push(*id);
+                events.push(GamepadEvent::Connected(*id, name.clone()));
+            }
+        }
+        if self.active.is_none_or(|id| !self.connected.contains(&id)) {
+            self.active = self.connected.first().copied();
+        }
+        events
+    }
+}
+
+/// Dead zone on the stick's distance from the centre, rescaled so values still start at 0 just
+/// outside the dead zone and reach 1 at the rim. Unlike per-axis dead zones this does not snap
+/// diagonal movement to the axes.
+pub fn radial_dead_zone(x: f32, y: f32, dead_zone: f32) -> (f32, f32) {
+    let length = (x * x + y * y).sqrt();
+    if length <= dead_zone {
+        return (0.0, 0.0);
+    }
+    let scaled = ((length - dead_zone) / (1.0 - dead_zone)).min(1.0);
+    (x / length * scaled, y / length * scaled)
+}
+
+/// one-dimensional dead zone for values in 0..1
+pub fn dead_zone(value: f32, dead_zone: f32) -> f32 {
+    if value <= dead_zone {
+        0.0
+    } else {
+        ((value - dead_zone) / (1.0 - dead_zone)).min(1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dead_zones_rescale_what_is_left() {
+        assert_eq!(radial_dead_zone(0.1, -0.1, 0.15), (0.0, 0.0));
+        let (x, y) = radial_dead_zone(0.0, 1.0, 0.15);
+        assert_eq!((x, y), (0.0, 1.0));
+        // just outside the dead zone starts near 0, diagonals keep their direction
+        let (x, y) = radial_dead_zone(0.12, 0.12, 0.15);
+        assert!(x > 0.0 && x < 0.02 && (x - y).abs() < 1e-6, "{} {}", x, y);
+        // square gates report corners past the rim, they are cut back to it
+        let (x, y) = radial_dead_zone(1.0, 1.0, 0.15);
+        assert!(((x * x + y * y).sqrt() - 1.0).abs() < 1e-6);
+
+        assert_eq!(dead_zone(0.05, 0.05), 0.0);
+        assert!((dead_zone(0.525, 0.05) - 0.5).abs() < 1e-6);
+        assert_eq!(dead_zone(1.0, 0.05), 1.0);
+    }
+
+    #[test]
+    fn connections_are_diffed_and_the_active_pad_kept() {
+        let pad = |id: JoystickId| (id, format!("{:?}", id));
+        let mut gamepads = Gamepads::default();
+        assert_eq!(gamepads.update_connected(&[]), vec![]);
+        assert_eq!(gamepads.active(), None);
+
+        let events = gamepads.update_connected(&[pad(JoystickId::Joystick2)]);
+        assert_eq!(
+            events,
+            vec![GamepadEvent::Connected(
+                JoystickId::Joystick2,
+                "Joystick2".to_string()
+            )]
+        );
+        assert_eq!(gamepads.active(), Some(JoystickId::Joystick2));
+
+        // a pad in a lower slot doesn't take over
+        let both = [pad(JoystickId::Joystick1), pad(JoystickId::Joystick2)];
+        assert_eq!(gamepads.update_connected(&both).len(), 1);
+        assert_eq!(gamepads.update_connected(&both), vec![]);
+        assert_eq!(gamepads.active(), Some(JoystickId::Joystick2));
+
+        let events = gamepads.update_connected(&[pad(JoystickId::Joystick1)]);
+        assert_eq!(
+            events,
+            vec![GamepadEvent::Disconnected(JoystickId::Joystick2)]
+        );
+        assert_eq!(gamepads.active(), Some(JoystickId::Joystick1));
+        gamepads.update_connected(&[]);
+        assert_eq!(gamepads.active(), None);
+    }
+}
@@ -0,0 +1,1027 @@
+use std::ffi::CStr;
+use std::path::Path;
+
+use cgmath::prelude::*;
+use cgmath::{
+    perspective, vec2, vec4, Matrix3, Matrix4, Point3, Quaternion, Rad, Vector3, Vector4,
+};
+
+use gltf::camera::Projection;
+use gltf::image::Format;
+use gltf::khr_lights_punctual::Kind;
+use gltf::mesh::Mode;
+use gltf::texture::{MagFilter, MinFilter, WrappingMode};
+
+use crate::bounds::Aabb;
+use crate::camera::CameraView;
+use crate::config::ConfigError;
+use crate::mesh::{Mesh, MeshData, Vertex};
+use crate::ray::{Ray, RayHit};
+use crate::scene::{Light, NodeId, Scene, Transform};
+use crate::shader::Shader;
+use crate::texture::Texture;
+
+/// A node of the glTF hierarchy with its local transform split into translation, rotation and
+/// scale.
+#[derive(Debug, Clone)]
+pub struct GltfNode {
+    pub name: String,
+    pub parent: Option<usize>,
+    pub children: Vec<usize>,
+    pub translation: Vector3<f32>,
+    pub rotation: Quaternion<f32>,
+    pub scale: Vector3<f32>,
+    /// index into `GltfScene::meshes`
+    pub mesh: Option<usize>,
+    /// index into `GltfScene::cameras`
+    pub camera: Option<usize>,
+    /// index into `GltfScene::lights`
+    pub light: Option<usize>,
+}
+
+impl GltfNode {
+    pub fn local_matrix(&self) -> Matrix4<f32> {
+        Matrix4::from_translation(self.translation)
+            * Matrix4::from(self.rotation)
+            * Matrix4::from_nonuniform_scale(self.scale.x, self.scale.y, self.scale.z)
+    }
+}
+
+/// One draw call worth of a glTF mesh.
+#[derive(Debug, Clone)]
+pub struct GltfPrimitive {
+    pub data: MeshData,
+    /// index into `GltfScene::materials`
+    pub material: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct GltfMesh {
+    pub primitives: Vec<GltfPrimitive>,
+}
+
+/// Texture reference of a material: which texture and which UV set it samples.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextureSlot {
+    /// index into `GltfScene::textures`
+    pub texture: usize,
+    pub tex_coord: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlphaMode {
+    Opaque,
+    Mask,
+    Blend,
+}
+
+/// Metallic-roughness material. Texture values are multiplied with their factors.
+#[derive(Debug, Clone)]
+pub struct PbrMaterial {
+    pub base_color: Vector4<f32>,
+    pub base_color_texture: Option<TextureSlot>,
+    pub metallic: f32,
+    pub roughness: f32,
+    /// roughness in the green channel, metalness in the blue one
+    pub metallic_roughness_texture: Option<TextureSlot>,
+    pub normal_texture: Option<TextureSlot>,
+    pub normal_scale: f32,
+    pub occlusion_texture: Option<TextureSlot>,
+    pub occlusion_strength: f32,
+    pub emissive: Vector3<f32>,
+    pub emissive_texture: Option<TextureSlot>,
+    pub alpha_mode: AlphaMode,
+    pub alpha_cutoff: f32,
+    pub double_sided: bool,
+}
+
+impl Default for PbrMaterial {
+    /// the material the glTF spec uses for primitives without one
+    fn default() -> PbrMaterial {
+        PbrMaterial {
+            base_color: vec4(1.0, 1.0, 1.0, 1.0),
+            base_color_texture: None,
+            metallic: 1.0,
+            roughness: 1.0,
+            metallic_roughness_texture: None,
+            normal_texture: None,
+            normal_scale: 1.0,
+            occlusion_texture: None,
+            occlusion_strength: 1.0,
+            emissive: Vector3::zero(),
+            emissive_texture: None,
+            alpha_mode: AlphaMode::Opaque,
+            alpha_cutoff: 0.5,
+            double_sided: false,
+        }
+    }
+}
+
+/// Decoded texture image as tightly packed RGBA8 rows, first row at the top, with the sampler
+/// settings as GL enums.
+#[derive(Debug, Clone)]
+pub struct GltfTexture {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+    pub wrap_s: u32,
+    pub wrap_t: u32,
+    pub min_filter: u32,
+    pub mag_filter: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GltfProjection {
+    /// `aspect` is `None` when the viewport's aspect ratio should be used, `zfar` is `None`
+    /// for an infinite projection
+    Perspective {
+        yfov: f32,
+        aspect: Option<f32>,
+        znear: f32,
+        zfar: Option<f32>,
+    },
+    Orthographic {
+        xmag: f32,
+        ymag: f32,
+        znear: f32,
+        zfar: f32,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct GltfCamera {
+    pub projection: GltfProjection,
+}
+
+impl GltfCamera {
+    pub fn projection_matrix(&self, aspect: f32) -> Matrix4<f32> {
+        match self.projection {
+            GltfProjection::Perspective {
+                yfov,
+                aspect: fixed,
+                znear,
+                zfar: Some(zfar),
+            } => perspective(Rad(yfov), fixed.unwrap_or(aspect), znear, zfar),
+            GltfProjection::Perspective {
+                yfov,
+                aspect: fixed,
+                znear,
+                zfar: None,
+            } => {
+                let f = 1.0 / (yfov / 2.0).tan();
+                Matrix4::from_cols(
+                    vec4(f / fixed.unwrap_or(aspect), 0.0, 0.0, 0.0),
+                    vec4(0.0, f, 0.0, 0.0),
+                    vec4(0.0, 0.0, -1.0, -1.0),
+                    vec4(0.0, 0.0, -2.0 * znear, 0.0),
+                )
+            }
+            GltfProjection::Orthographic {
+                xmag,
+                ymag,
+                znear,
+                zfar,
+            } => cgmath::ortho(-xmag, xmag, -ymag, ymag, znear, zfar),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LightKind {
+    Directional,
+    Point,
+    /// cone angles in radians from the light's -Z axis
+    Spot {
+        inner_cone: f32,
+        outer_cone: f32,
+    },
+}
+
+/// A `KHR_lights_punctual` light. Lights shine down their node's -Z axis.
+#[derive(Debug, Clone)]
+pub struct GltfLight {
+    pub kind: LightKind,
+    pub color: Vector3<f32>,
+    pub intensity: f32,
+    /// `None` means no cutoff, the light falls off with the inverse square law only
+    pub range: Option<f32>,
+}
+
+/// Everything read from a glTF 2.0 file (`.gltf` with external or embedded buffers, or
+/// `.glb`), converted to the crate's own types. Needs no GL context.
+#[derive(Debug, Clone, Default)]
+pub struct GltfScene {
+    pub nodes: Vec<GltfNode>,
+    /// nodes of the default scene (or the first one) without a parent
+    pub roots: Vec<usize>,
+    pub meshes: Vec<GltfMesh>,
+    pub materials: Vec<PbrMaterial>,
+    pub textures: Vec<GltfTexture>,
+    pub cameras: Vec<GltfCamera>,
+    pub lights: Vec<GltfLight>,
+}
+
+impl GltfScene {
+    /// Read a `.gltf` or `.glb` file together with the buffers and images it references.
+    /// Primitives are reordered for the vertex cache, see `MeshData::optimize`.
+    /// ------------------------------------------------------------------------
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<GltfScene, ConfigError> {
+        let path = path.as_ref();
+        let (document, buffers, images) = gltf::import(path)
+            .map_err(|err| ConfigError::Parse(path.to_path_buf(), err.to_string()))?;
+        let mut scene = GltfScene::from_document(&document, &buffers, &images);
+        for mesh in &mut scene.meshes {
+            for primitive in &mut mesh.primitives {
+                primitive.data.optimize();
+            }
+        }
+        Ok(scene)
+    }
+
+    fn from_document(
+        document: &gltf::Document,
+        buffers: &[gltf::buffer::Data],
+        images: &[gltf::image::Data],
+    ) -> GltfScene {
+        let mut scene = GltfScene {
+            nodes: document.nodes().map(read_node).collect(),
+            meshes: document
+                .meshes()
+                .map(|mesh| read_mesh(&mesh, buffers))
+                .collect(),
+            materials: document.materials().map(|m| read_material(&m)).collect(),
+            textures: document
+                .textures()
+                .map(|texture| read_texture(&texture, images))
+                .collect(),
+            cameras: document.cameras().map(|c| read_camera(&c)).collect(),
+            lights: document
+                .lights()
+                .map(|lights| lights.map(|light| read_light(&light)).collect())
+                .unwrap_or_default(),
+            roots: Vec::new(),
+        };
+        for index in 0..scene.nodes.len() {
+            for child in scene.nodes[index].children.clone() {
+                scene.nodes[child].parent = Some(index);
+            }
+        }
+        scene.roots = match document
+            .default_scene()
+            .or_else(|| document.scenes().next())
+        {
+            Some(default) => default.nodes().map(|node| node.index()).collect(),
+            // no scenes at all: show every node that isn't a child
+            None => (0..scene.nodes.len())
+                .filter(|index| scene.nodes[*index].parent.is_none())
+                .collect(),
+        };
+        scene
+    }
+
+    /// World transform of every node, indexed like `nodes`. Nodes that aren't reachable from
+    /// `roots` keep their local transform.
+    /// ------------------------------------------------------------------------
+    pub fn world_matrices(&self) -> Vec<Matrix4<f32>> {
+        let mut world: Vec<Matrix4<f32>> = self.nodes.iter().map(GltfNode::local_matrix).collect();
+        let mut stack: Vec<usize> = self.roots.clone();
+        while let Some(index) = stack.pop() {
+            for &child in &self.nodes[index].children {
+                world[child] = world[index] * world[child];
+                stack.push(child);
+            }
+        }
+        world
+    }
+
+    /// Add a scene node below `parent` for every node carrying a camera or a light, named
+    /// like the glTF node and placed at its world position and orientation. Scale is left
+    /// out, it would only stretch the view or the light's cone.
+    /// ------------------------------------------------------------------------
+    pub fn add_cameras_and_lights(&self, scene: &mut Scene, parent: NodeId) -> Vec<NodeId> {
+        let mut added = Vec::new();
+        for (node, world) in self.nodes.iter().zip(self.world_matrices()) {
+            if node.camera.is_none() && node.light.is_none() {
+                continue;
+            }
+            let axes = Matrix3::from_cols(
+                world.x.truncate().normalize(),
+                world.y.truncate().normalize(),
+                world.z.truncate().normalize(),
+            );
+            let transform = Transform {
+                translation: world.w.truncate(),
+                rotation: Quaternion::from(axes),
+                ..Transform::default()
+            };
+            let id = scene.add_node(&node.name, transform, Some(parent));
+            scene.set_camera(id, node.camera.map(|camera| self.cameras[camera].clone()));
+            scene.set_light(id, node.light.map(|light| Light::from(&self.lights[light])));
+            added.push(id);
+        }
+        added
+    }
+
+    /// Axis-aligned bounds of every mesh instance in world space.
+    /// ------------------------------------------------------------------------
+    pub fn bounds(&self) -> Aabb {
+        let mut bounds = Aabb::empty();
+        for (node, world) in self.nodes.iter().zip(self.world_matrices()) {
+            let Some(mesh) = node.mesh else { continue };
+            for primitive in &self.meshes[mesh].primitives {
+                bounds = bounds.union(&primitive.data.bounds().transformed(&world));
+            }
+        }
+        bounds
+    }
+}
+
+impl From<&GltfLight> for Light {
+    fn from(light: &GltfLight) -> Light {
+        Light {
+            kind: light.kind,
+            color: light.color,
+            intensity: light.intensity,
+            range: light.range,
+        }
+    }
+}
+
+/// A glTF camera together with the world transform of its node.
+#[derive(Debug, Clone)]
+pub struct NodeCamera {
+    pub world: Matrix4<f32>,
+    pub camera: GltfCamera,
+}
+
+impl CameraView for NodeCamera {
+    fn position(&self) -> Point3<f32> {
+        Point3::from_vec(self.world.w.truncate())
+    }
+
+    fn view_matrix(&self) -> Matrix4<f32> {
+        self.world.invert().unwrap_or_else(Matrix4::identity)
+    }
+
+    fn projection(&self, aspect: f32) -> Matrix4<f32> {
+        self.camera.projection_matrix(aspect)
+    }
+}
+
+fn read_node(node: gltf::Node) -> GltfNode {
+    let (translation, [x, y, z, w], scale) = node.transform().decomposed();
+    GltfNode {
+        name: node.name().unwrap_or_default().to_string(),
+        parent: None,
+        children: node.children().map(|child| child.index()).collect(),
+        translation: translation.into(),
+        rotation: Quaternion::new(w, x, y, z),
+        scale: scale.into(),
+        mesh: node.mesh().map(|mesh| mesh.index()),
+        camera: node.camera().map(|camera| camera.index()),
+        light: node.light().map(|light| light.index()),
+    }
+}
+
+fn read_mesh(mesh: &gltf::Mesh, buffers: &[gltf::buffer::Data]) -> GltfMesh {
+    let name = mesh.name().unwrap_or_default().to_string();
+    let primitives = mesh
+        .primitives()
+        .filter_map(|primitive| {
+            let data = read_primitive(&primitive, buffers);
+            if data.is_none() {
+                println!(
+                    "WARNING::GLTF::PRIMITIVE_SKIPPED {}[{}] ({:?})",
+                    name,
+                    primitive.index(),
+                    primitive.mode()
+                );
+            }
+            Some(GltfPrimitive {
+                data: data?,
+                material: primitive.material().index(),
+            })
+        })
+        .collect();
+    GltfMesh { primitives }
+}
+
+/// Only triangle primitives are kept; strips and fans are turned into lists. Missing normals
+/// become flat ones, missing tangents are computed from the UVs.
+fn read_primitive(primitive: &gltf::Primitive, buffers: &[gltf::buffer::Data]) -> Option<MeshData> {
+    let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+    let positions: Vec<[f32; 3]> = reader.read_positions()?.collect();
+    let indices: Vec<u32> = match reader.read_indices() {
+        Some(indices) => indices.into_u32().collect(),
+        None => (0..positions.len() as u32).collect(),
+    };
+    let indices = triangle_list(primitive.mode(), &indices)?;
+    if indices
+        .iter()
+        .any(|&index| index as usize >= positions.len())
+    {
+        return None;
+    }
+
+    let normals: Option<Vec<[f32; 3]>> = reader.read_normals().map(Iterator::collect);
+    let tangents: Option<Vec<[f32; 4]>> = reader.read_tangents().map(Iterator::collect);
+    let uvs: Option<Vec<[f32; 2]>> = reader
+        .read_tex_coords(0)
+        .map(|uvs| uvs.into_f32().collect());
+    let colors: Option<Vec<[f32; 4]>> = reader
+        .read_colors(0)
+        .map(|colors| colors.into_rgba_f32().collect());
+
+    let vertices = positions
+        .iter()
+        .enumerate()
+        .map(|(i, &position)| {
+            let mut vertex = Vertex {
+                position: position.into(),
+                ..Vertex::default()
+            };
+            if let Some(normal) = normals.as_ref().and_then(|n| n.get(i)) {
+                vertex.normal = (*normal).into();
+            }
+            if let Some(tangent) = tangents.as_ref().and_then(|t| t.get(i)) {
+                vertex.tangent = (*tangent).into();
+            }
+            if let Some([u, v]) = uvs.as_ref().and_then(|uv| uv.get(i)) {
+                vertex.uv = vec2(*u, *v);
+            }
+            if let Some(color) = colors.as_ref().and_then(|c| c.get(i)) {
+                vertex.color = (*color).into();
+            }
+            vertex
+        })
+        .collect();
+
+    let mut data = MeshData { vertices, indices };
+    if normals.is_none() {
+        data.compute_flat_normals();
+    }
+    if normals.is_none() || tangents.is_none() {
+        data.compute_tangents();
+        // glTF's V runs down the image while normal maps keep +Y up, so the bitangent
+        // has to follow decreasing V
+        for vertex in &mut data.vertices {
+            vertex.tangent.w = -vertex.tangent.w;
+        }
+    }
+    Some(data)
+}
+
+/// Indices of a triangle list for primitives drawn as triangles, strips or fans.
+/// ------------------------------------------------------------------------
+pub fn triangle_list(mode: Mode, indices: &[u32]) -> Option<Vec<u32>> {
+    match mode {
+        Mode::Triangles => Some(indices[..indices.len() / 3 * 3].to_vec()),
+        Mode::TriangleStrip => Some(
+            (0..indices.len().saturating_sub(2))
+                .flat_map(|i| {
+                    // every other triangle of a strip is wound the other way round
+                    if i % 2 == 0 {
+                        [indices[i], indices[i + 1], indices[i + 2]]
+                    } else {
+                        [indices[i + 1], indices[i], indices[i + 2]]
+                    }
+                })
+                .collect(),
+        ),
+        Mode::TriangleFan => Some(
+            (1..indices.len().saturating_sub(1))
+                .flat_map(|i| [indices[0], indices[i], indices[i + 1]])
+                .collect(),
+        ),
+        Mode::Points | Mode::Lines | Mode::LineLoop | Mode::LineStrip => None,
+    }
+}
+
+/// Only `TEXCOORD_0` is read, textures meant for another UV set are reported and sample it
+/// anyway.
+fn read_material(material: &gltf::Material) -> PbrMaterial {
+    let slot = |info: Option<gltf::texture::Info>| {
+        info.map(|info| TextureSlot {
+            texture: info.texture().index(),
+            tex_coord: info.tex_coord(),
+        })
+    };
+    let pbr = material.pbr_metallic_roughness();
+    let normal = material.normal_texture();
+    let occlusion = material.occlusion_texture();
+    let pbr_material = PbrMaterial {
+        base_color: pbr.base_color_factor().into(),
+        base_color_texture: slot(pbr.base_color_texture()),
+        metallic: pbr.metallic_factor(),
+        roughness: pbr.roughness_factor(),
+        metallic_roughness_texture: slot(pbr.metallic_roughness_texture()),
+        normal_scale: normal.as_ref().map_or(1.0, |normal| normal.scale()),
+        normal_texture: normal.map(|normal| TextureSlot {
+            texture: normal.texture().index(),
+            tex_coord: normal.tex_coord(),
+        }),
+        occlusion_strength: occlusion
+            .as_ref()
+            .map_or(1.0, |occlusion| occlusion.strength()),
+        occlusion_texture: occlusion.map(|occlusion| TextureSlot {
+            texture: occlusion.texture().index(),
+            tex_coord: occlusion.tex_coord(),
+        }),
+        emissive: material.emissive_factor().into(),
+        emissive_texture: slot(material.emissive_texture()),
+        alpha_mode: match material.alpha_mode() {
+            gltf::material::AlphaMode::Opaque => AlphaMode::Opaque,
+            gltf::material::AlphaMode::Mask => AlphaMode::Mask,
+            gltf::material::AlphaMode::Blend => AlphaMode::Blend,
+        },
+        alpha_cutoff: material.alpha_cutoff().unwrap_or(0.5),
+        double_sided: material.double_sided(),
+    };
+    let slots = [
+        pbr_material.base_color_texture,
+        pbr_material.metallic_roughness_texture,
+        pbr_material.normal_texture,
+        pbr_material.occlusion_texture,
+        pbr_material.emissive_texture,
+    ];
+    for slot in slots.into_iter().flatten() {
+        if slot.tex_coord != 0 {
+            println!(
+                "WARNING::GLTF::TEXCOORD_UNSUPPORTED {} texture {}: TEXCOORD_{}",
+                material.name().unwrap_or_default(),
+                slot.texture,
+                slot.tex_coord
+            );
+        }
+    }
+    pbr_material
+}
+
+fn read_texture(texture: &gltf::Texture, images: &[gltf::image::Data]) -> GltfTexture {
+    let image = &images[texture.source().index()];
+    let sampler = texture.sampler();
+    let wrap = |mode: WrappingMode| match mode {
+        WrappingMode::ClampToEdge => gl::CLAMP_TO_EDGE,
+        WrappingMode::MirroredRepeat => gl::MIRRORED_REPEAT,
+        WrappingMode::Repeat => gl::REPEAT,
+    };
+    GltfTexture {
+        width: image.width,
+        height: image.height,
+        pixels: to_rgba8(image.format, &image.pixels),
+        wrap_s: wrap(sampler.wrap_s()),
+        wrap_t: wrap(sampler.wrap_t()),
+        min_filter: match sampler.min_filter() {
+            Some(MinFilter::Nearest) => gl::NEAREST,
+            Some(MinFilter::Linear) => gl::LINEAR,
+            Some(MinFilter::NearestMipmapNearest) => gl::NEAREST_MIPMAP_NEAREST,
+            Some(MinFilter::LinearMipmapNearest) => gl::LINEAR_MIPMAP_NEAREST,
+            Some(MinFilter::NearestMipmapLinear) => gl::NEAREST_MIPMAP_LINEAR,
+            Some(MinFilter::LinearMipmapLinear) | None => gl::LINEAR_MIPMAP_LINEAR,
+        },
+        mag_filter: match sampler.mag_filter() {
+            Some(MagFilter::Nearest) => gl::NEAREST,
+            Some(MagFilter::Linear) | None => gl::LINEAR,
+        },
+    }
+}
+
+/// Expand decoded image data to RGBA8. Grey images are replicated to RGB, 16-bit channels keep
+/// their high byte.
+/// ------------------------------------------------------------------------
+pub fn to_rgba8(format: Format, pixels: &[u8]) -> Vec<u8> {
+    let (channels, bytes) = match format {
+        Format::R8 => (1, 1),
+        Format::R8G8 => (2, 1),
+        Format::R8G8B8 | Format::B8G8R8 => (3, 1),
+        Format::R8G8B8A8 | Format::B8G8R8A8 => (4, 1),
+        Format::R16 => (1, 2),
+        Format::R16G16 => (2, 2),
+        Format::R16G16B16 => (3, 2),
+        Format::R16G16B16A16 => (4, 2),
+    };
+    let bgr = matches!(format, Format::B8G8R8 | Format::B8G8R8A8);
+    pixels
+        .chunks_exact(channels * bytes)
+        .flat_map(|pixel| {
+            // 16-bit samples are little endian
+            let c = |i: usize| pixel[i * bytes + bytes - 1];
+            match channels {
+                1 => [c(0), c(0), c(0), 255],
+                2 => [c(0), c(1), 0, 255],
+                3 if bgr => [c(2), c(1), c(0), 255],
+                3 => [c(0), c(1), c(2), 255],
+                _ if bgr => [c(2), c(1), c(0), c(3)],
+                _ => [c(0), c(1), c(2), c(3)],
+            }
+        })
+        .collect()
+}
+
+fn read_camera(camera: &gltf::Camera) -> GltfCamera {
+    GltfCamera {
+        projection: match camera.projection() {
+            Projection::Perspective(p) => GltfProjection::Perspective {
+                yfov: p.yfov(),
+                aspect: p.aspect_ratio(),
+                znear: p.znear(),
+                zfar: p.zfar(),
+            },
+            Projection::Orthographic(o) => GltfProjection::Orthographic {
+                xmag: o.xmag(),
+                ymag: o.ymag(),
+                znear: o.znear(),
+                zfar: o.zfar(),
+            },
+        },
+    }
+}
+
+fn read_light(light: &gltf::khr_lights_punctual::Light) -> GltfLight {
+    GltfLight {
+        kind: match light.kind() {
+            Kind::Directional => LightKind::Directional,
+            Kind::Point => LightKind::Point,
+            Kind::Spot {
+                inner_cone_angle,
+                outer_cone_angle,
+            } => LightKind::Spot {
+                inner_cone: inner_cone_angle,
+                outer_cone: outer_cone_angle,
+            },
+        },
+        color: light.color().into(),
+        intensity: light.intensity(),
+        range: light.range(),
+    }
+}
+
+/// A glTF scene uploaded to the GPU: one `Mesh` per primitive and one `Texture` per glTF
+/// texture.
+pub struct GltfModel {
+    pub scene: GltfScene,
+    /// `scene.bounds()`, computed once
+    pub bounds: Aabb,
+    /// indexed like `scene.meshes`, then by primitive
+    meshes: Vec<Vec<Mesh>>,
+    textures: Vec<Texture>,
+    /// bound in place of missing colour maps
+    white: Texture,
+    /// bound in place of missing normal maps, a normal straight along +Z
+    flat_normal: Texture,
+}
+
+impl GltfModel {
+    /// Load a `.gltf` / `.glb` file and upload it; needs a current GL context.
+    /// ------------------------------------------------------------------------
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<GltfModel, ConfigError> {
+        Ok(GltfModel::new(GltfScene::load(path)?))
+    }
+
+    pub fn new(scene: GltfScene) -> GltfModel {
+        let meshes = scene
+            .meshes
+            .iter()
+            .map(|mesh| {
+                mesh.primitives
+                    .iter()
+                    .map(|primitive| Mesh::with_bvh(&primitive.data))
+                    .collect()
+            })
+            .collect();
+        // base colour and emissive maps hold sRGB colours, the others linear data
+        let colour_maps: Vec<usize> = scene
+            .materials
+            .iter()
+            .flat_map(|material| [material.base_color_texture, material.emissive_texture])
+            .flatten()
+            .map(|slot| slot.texture)
+            .collect();
+        let textures = scene
+            .textures
+            .iter()
+            .enumerate()
+            .map(|(index, source)| {
+                // glTF images start at the top left like their UVs, so no flip
+                let texture = if colour_maps.contains(&index) {
+                    Texture::from_srgba(source.width, source.height, &source.pixels)
+                } else {
+                    Texture::from_rgba(source.width, source.height, &source.pixels)
+                };
+                unsafe {
+                    gl::BindTexture(gl::TEXTURE_2D, texture.id);
+                    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, source.wrap_s as i32);
+                    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, source.wrap_t as i32);
+                    gl::TexParameteri(
+                        gl::TEXTURE_2D,
+                        gl::TEXTURE_MIN_FILTER,
+                        source.min_filter as i32,
+                    );
+                    gl::TexParameteri(
+                        gl::TEXTURE_2D,
+                        gl::TEXTURE_MAG_FILTER,
+                        source.mag_filter as i32,
+                    );
+                }
+                texture
+            })
+            .collect();
+        GltfModel {
+            bounds: scene.bounds(),
+            scene,
+            meshes,
+            textures,
+            white: Texture::solid([255, 255, 255, 255]),
+            flat_normal: Texture::solid([128, 128, 255, 255]),
+        }
+    }
+
+    /// Closest primitive along `ray`, given in the model's space. `TriangleHit::mesh` counts
+    /// primitives across the whole scene in node order, the same order `draw` uses.
+    /// ------------------------------------------------------------------------
+    pub fn raycast(&self, ray: &Ray) -> Option<RayHit> {
+        let mut closest: Option<RayHit> = None;
+        let mut primitive_index = 0;
+        for (node, world) in self.scene.nodes.iter().zip(self.scene.world_matrices()) {
+            let Some(mesh) = node.mesh else { continue };
+            let local = world.invert().map(|to_local| ray.transformed(&to_local));
+            for primitive in &self.meshes[mesh] {
+                let index = primitive_index;
+                primitive_index += 1;
+                let Some(local) = local.as_ref() else {
+                    continue;
+                };
+                if let Some(mut hit) = primitive
+                    .raycast(local)
+                    .filter(|hit| closest.is_none_or(|c| hit.t < c.t))
+                {
+                    if let Some(triangle) = hit.triangle.as_mut() {
+                        triangle.mesh = index;
+                    }
+                    closest = Some(hit);
+                }
+            }
+        }
+        closest
+    }
+
+    /// Draw every mesh instance of the hierarchy with `shader`, which should be in use and
+    /// have `view` / `projection` set. Sets `model` per node and the material per primitive:
+    /// `texture_diffuse1` (base colour, unit 0), `texture_metallic_roughness` (unit 1),
+    /// `texture_normal` (unit 2), `texture_occlusion` (unit 3), `texture_emissive` (unit 4),
+    /// `diffuse_color`, `metallic_factor`, `roughness_factor`, `emissive_factor`,
+    /// `normal_scale`, `occlusion_strength` and `alpha_cutoff` (0 unless the material masks).
+    /// Colour maps decode to linear values when sampled, so `srgb_output` is set while
+    /// drawing for the shader to encode its result again.
+    /// Single sided materials are back face culled and blended ones alpha blended.
+    /// ------------------------------------------------------------------------
+    pub unsafe fn draw(&self, shader: &Shader, transform: &Matrix4<f32>) {
+        let default = PbrMaterial::default();
+        shader.setInt(c_str!("texture_diffuse1"), 0);
+        shader.setInt(c_str!("texture_metallic_roughness"), 1);
+        shader.setInt(c_str!("texture_normal"), 2);
+        shader.setInt(c_str!("texture_occlusion"), 3);
+        shader.setInt(c_str!("texture_emissive"), 4);
+        shader.setBool(c_str!("srgb_output"), true);
+        for (node, world) in self.scene.nodes.iter().zip(self.scene.world_matrices()) {
+            let Some(mesh) = node.mesh else { continue };
+            shader.setMat4(c_str!("model"), &(transform * world));
+            for (primitive, gpu_mesh) in self.scene.meshes[mesh]
+                .primitives
+                .iter()
+                .zip(&self.meshes[mesh])
+            {
+                let material = primitive
+                    .material
+                    .map_or(&default, |index| &self.scene.materials[index]);
+                let bind = |slot: Option<TextureSlot>, fallback: &Texture, unit: u32| {
+                    slot.map_or(fallback, |slot| &self.textures[slot.texture])
+                        .bind(unit)
+                };
+                bind(material.base_color_texture, &self.white, 0);
+                bind(material.metallic_roughness_texture, &self.white, 1);
+                bind(material.normal_texture, &self.flat_normal, 2);
+                bind(material.occlusion_texture, &self.white, 3);
+                bind(material.emissive_texture, &self.white, 4);
+                shader.setVector3(c_str!("diffuse_color"), &material.base_color.truncate());
+                shader.setFloat(c_str!("metallic_factor"), material.metallic);
+                shader.setFloat(c_str!("roughness_factor"), material.roughness);
+                shader.setVector3(c_str!("emissive_factor"), &material.emissive);
+                shader.setFloat(c_str!("normal_scale"), material.normal_scale);
+                shader.setFloat(c_str!("occlusion_strength"), material.occlusion_strength);
+                let cutoff = match material.alpha_mode {
+                    AlphaMode::Mask => material.alpha_cutoff,
+                    AlphaMode::Opaque | AlphaMode::Blend => 0.0,
+                };
+                shader.setFloat(c_str!("alpha_cutoff"), cutoff);
+                if material.double_sided {
+                    gl::Disable(gl::CULL_FACE);
+                } else {
+                    gl::Enable(gl::CULL_FACE);
+                }
+                if material.alpha_mode == AlphaMode::Blend {
+                    gl::Enable(gl::BLEND);
+                    gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+                } else {
+                    gl::Disable(gl::BLEND);
+                }
+                gpu_mesh.draw();
+            }
+        }
+        // back to what the other drawables expect
+        gl::Disable(gl::CULL_FACE);
+        gl::Disable(gl::BLEND);
+        shader.setFloat(c_str!("alpha_cutoff"), 0.0);
+        shader.setBool(c_str!("srgb_output"), false);
+        gl::ActiveTexture(gl::TEXTURE0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::Camera;
+    // `Transform` itself is the scene's
+    use cgmath::{vec3, Transform as _};
+
+    /// pack a glTF JSON document and its binary buffer into a .glb container
+    fn glb(json: &str, bin: &[u8]) -> Vec<u8> {
+        let pad = |mut bytes: Vec<u8>, fill: u8| {
+            while !bytes.len().is_multiple_of(4) {
+                bytes.push(fill);
+            }
+            bytes
+        };
+        let json = pad(json.as_bytes().to_vec(), b' ');
+        let bin = pad(bin.to_vec(), 0);
+        let mut out = Vec::new();
+        out.extend_from_slice(b"glTF");
+        out.extend_from_slice(&2u32.to_le_bytes());
+        out.extend_from_slice(&((12 + 8 + json.len() + 8 + bin.len()) as u32).to_le_bytes());
+        out.extend_from_slice(&(json.len() as u32).to_le_bytes());
+        out.extend_from_slice(b"JSON");
+        out.extend_from_slice(&json);
+        out.extend_from_slice(&(bin.len() as u32).to_le_bytes());
+        out.extend_from_slice(b"BIN\0");
+        out.extend_from_slice(&bin);
+        out
+    }
+
+    /// read a `.glb` file, or a `.gltf` one with embedded buffers, from memory
+    fn from_slice(bytes: &[u8]) -> GltfScene {
+        let (document, buffers, images) = gltf::import_slice(bytes).unwrap();
+        GltfScene::from_document(&document, &buffers, &images)
+    }
+
+    /// a quad with u16 indices and normalized u16 UVs, instanced by a child node, plus a camera
+    /// and a spot light
+    fn quad_scene() -> GltfScene {
+        let mut bin = Vec::new();
+        for p in [
+            [0.0f32, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [1.0, 1.0, 0.0],
+            [0.0, 1.0, 0.0],
+        ] {
+            for c in p {
+                bin.extend_from_slice(&c.to_le_bytes());
+            }
+        }
+        for uv in [[0u16, 65535], [65535, 65535], [65535, 0], [0, 0]] {
+            for c in uv {
+                bin.extend_from_slice(&c.to_le_bytes());
+            }
+        }
+        for i in [0u16, 1, 2, 0, 2, 3] {
+            bin.extend_from_slice(&i.to_le_bytes());
+        }
+        let json = format!(
+            r#"{{
+  "asset": {{"version": "2.0"}},
+  "extensionsUsed": ["KHR_lights_punctual"],
+  "extensions": {{"KHR_lights_punctual": {{"lights": [
+    {{"type": "spot", "color": [1, 0.5, 0], "intensity": 3, "range": 10,
+      "spot": {{"innerConeAngle": 0.2, "outerConeAngle": 0.5}}}}
+  ]}}}},
+  "scene": 0,
+  "scenes": [{{"nodes": [0, 2, 3]}}],
+  "nodes": [
+    {{"name": "parent", "translation": [0, 2, 0], "children": [1]}},
+    {{"name": "quad", "translation": [1, 0, 0], "scale": [2, 2, 2], "mesh": 0}},
+    {{"name": "eye", "camera": 0, "translation": [0, 0, 5]}},
+    {{"name": "lamp", "rotation": [-0.7071068, 0, 0, 0.7071068],
+      "extensions": {{"KHR_lights_punctual": {{"light": 0}}}}}}
+  ],
+  "cameras": [{{"type": "perspective", "perspective": {{"yfov": 0.5, "znear": 0.1}}}}],
+  "materials": [{{"name": "red", "pbrMetallicRoughness": {{"baseColorFactor": [1, 0, 0, 1],
+    "metallicFactor": 0.25}}, "doubleSided": true}}],
+  "meshes": [{{"name": "quad", "primitives": [{{"attributes": {{"POSITION": 0, "TEXCOORD_0": 1}},
+    "indices": 2, "material": 0}}]}}],
+  "buffers": [{{"byteLength": {len}}}],
+  "bufferViews": [
+    {{"buffer": 0, "byteOffset": 0, "byteLength": 48}},
+    {{"buffer": 0, "byteOffset": 48, "byteLength": 16}},
+    {{"buffer": 0, "byteOffset": 64, "byteLength": 12}}
+  ],
+  "accessors": [
+    {{"bufferView": 0, "componentType": 5126, "count": 4, "type": "VEC3",
+      "min": [0, 0, 0], "max": [1, 1, 0]}},
+    {{"bufferView": 1, "componentType": 5123, "normalized": true, "count": 4, "type": "VEC2"}},
+    {{"bufferView": 2, "componentType": 5123, "count": 6, "type": "SCALAR"}}
+  ]
+}}"#,
+            len = bin.len()
+        );
+        from_slice(&glb(&json, &bin))
+    }
+
+    #[test]
+    fn glb_meshes_and_materials() {
+        let scene = quad_scene();
+        assert_eq!(scene.meshes.len(), 1);
+        let primitive = &scene.meshes[0].primitives[0];
+        assert_eq!(primitive.material, Some(0));
+        // no normals in the file: flat ones, one vertex per corner
+        assert_eq!(primitive.data.triangle_count(), 2);
+        assert_eq!(primitive.data.vertex_count(), 6);
+        for vertex in &primitive.data.vertices {
+            assert!((vertex.normal - Vector3::unit_z()).magnitude() < 1e-6);
+            assert!((vertex.tangent - vec4(1.0, 0.0, 0.0, 1.0)).magnitude() < 1e-5);
+        }
+        assert_eq!(primitive.data.vertices[2].uv, vec2(1.0, 0.0));
+
+        let material = &scene.materials[0];
+        assert_eq!(material.base_color, vec4(1.0, 0.0, 0.0, 1.0));
+        assert_eq!(material.metallic, 0.25);
+        assert_eq!(material.roughness, 1.0);
+        assert!(material.double_sided);
+        assert_eq!(material.alpha_mode, AlphaMode::Opaque);
+    }
+
+    #[test]
+    fn hierarchy_cameras_and_lights() {
+        let scene = quad_scene();
+        assert_eq!(scene.roots, vec![0, 2, 3]);
+        assert_eq!(scene.nodes[1].parent, Some(0));
+        let world = scene.world_matrices();
+        let corner = world[1].transform_point(Point3::new(1.0, 1.0, 0.0));
+        assert!((corner - Point3::new(3.0, 4.0, 0.0)).magnitude() < 1e-6);
+
+        let bounds = scene.bounds();
+        assert!((bounds.min - Point3::new(1.0, 2.0, 0.0)).magnitude() < 1e-6);
+        assert!((bounds.max - Point3::new(3.0, 4.0, 0.0)).magnitude() < 1e-6);
+
+        let mut graph = Scene::new();
+        let root = graph.add_node("quad.glb", Transform::default(), None);
+        let added = scene.add_cameras_and_lights(&mut graph, root);
+        assert_eq!(added.len(), 2);
+        let eye = graph.camera(graph.find("eye").unwrap()).unwrap();
+        assert_eq!(eye.position(), Point3::new(0.0, 0.0, 5.0));
+        let projection = eye.projection(2.0);
+        assert!((projection.y.y / projection.x.x - 2.0).abs() < 1e-5);
+        // infinite far plane: points far away still end up in front of the far clip plane
+        let far = projection * vec4(0.0, 0.0, -1e6, 1.0);
+        assert!(far.z / far.w < 1.0);
+
+        // the fly camera takes over the file's view
+        let mut fly = Camera::default();
+        fly.look_through(&eye);
+        assert_eq!(fly.position, Point3::new(0.0, 0.0, 5.0));
+        assert!((fly.front - vec3(0.0, 0.0, -1.0)).magnitude() < 1e-5);
+        assert!((fly.zoom - 0.5f32.to_degrees()).abs() < 1e-3);
+
+        let lights = graph.lights();
+        assert_eq!(lights.len(), 1);
+        let lamp = lights[0];
+        assert_eq!(graph.node(lamp.node).name, "lamp");
+        // rotated -90 degrees about X, the light points straight down
+        assert!((lamp.direction - vec3(0.0, -1.0, 0.0)).magnitude() < 1e-5);
+        assert_eq!(
+            lamp.light.kind,
+            LightKind::Spot {
+                inner_cone: 0.2,
+                outer_cone: 0.5
+            }
+        );
+        assert_eq!(lamp.light.range, Some(10.0));
+    }
+
+    #[test]
+    fn strips_and_fans_become_lists() {
+        let strip = triangle_list(Mode::TriangleStrip, &[0, 1, 2, 3]).unwrap();
+        assert_eq!(strip, vec![0, 1, 2, 2, 1, 3]);
+        let fan = triangle_list(Mode::TriangleFan, &[0, 1, 2, 3]).unwrap();
+        assert_eq!(fan, vec![0, 1, 2, 0, 2, 3]);
+        assert!(triangle_list(Mode::Lines, &[0, 1]).is_none());
+    }
+
+    #[test]
+    fn image_formats_expand_to_rgba() {
+        assert_eq!(to_rgba8(Format::R8, &[7]), vec![7, 7, 7, 255]);
+        assert_eq!(to_rgba8(Format::B8G8R8A8, &[1, 2, 3, 4]), vec![3, 2, 1, 4]);
+        assert_eq!(
+            to_rgba8(Format::R16G16B16, &[0, 1, 0, 2, 0, 3]),
+            vec![1, 2, 3, 255]
+        );
+    }
+}
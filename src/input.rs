@@ -0,0 +1,630 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+use glfw::{Action, GamepadAxis, GamepadButton, Key, MouseButton, WindowEvent};
+use serde::Deserialize;
+
+use crate::config::ConfigError;
+
+/// A digital input that can trigger an action.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Binding {
+    Key(Key),
+    Mouse(MouseButton),
+    Gamepad(GamepadButton),
+}
+
+/// An analog input feeding an axis.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AnalogSource {
+    /// cursor movement since the last frame, in screen coordinates (y up)
+    MouseX,
+    MouseY,
+    ScrollX,
+    ScrollY,
+    Gamepad(GamepadAxis),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AxisBinding {
+    pub positive: Vec<Binding>,
+    pub negative: Vec<Binding>,
+    /// analog sources with their scale (negative to invert)
+    pub analog: Vec<(AnalogSource, f32)>,
+}
+
+/// Named actions and axes and the inputs bound to them.
+///
+/// ```toml
+/// [actions]
+/// quit = ["Escape"]
+/// jump = ["Space", "gamepad:A"]
+///
+/// [axes.move_x]
+/// positive = ["D"]
+/// negative = ["A"]
+/// analog = ["gamepad:LeftX"]
+///
+/// [axes.look_y]
+/// analog = ["mouse:y", "-gamepad:RightY*10"]
+/// ```
+///
+/// Keys use glfw's names (`W`, `LeftShift`, `F11`, `Num1`, ...), mouse buttons are
+/// `mouse:left|right|middle|4..8`, gamepad buttons `gamepad:A|B|X|Y|LeftBumper|DpadUp|...`.
+/// Analog sources are `mouse:x|y`, `scroll:x|y` and `gamepad:LeftX|LeftY|RightX|RightY|
+/// LeftTrigger|RightTrigger`, optionally negated with `-` and scaled with `*<factor>`.
+#[derive(Debug, Clone, Default)]
+pub struct InputMap {
+    pub actions: HashMap<String, Vec<Binding>>,
+    pub axes: HashMap<String, AxisBinding>,
+}
+
+#[derive(Deserialize)]
+struct InputMapFile {
+    #[serde(default)]
+    actions: HashMap<String, Vec<String>>,
+    #[serde(default)]
+    axes: HashMap<String, AxisFile>,
+}
+
+#[derive(Deserialize)]
+struct AxisFile {
+    #[serde(default)]
+    positive: Vec<String>,
+    #[serde(default)]
+    negative: Vec<String>,
+    #[serde(default)]
+    analog: Vec<String>,
+}
+
+impl InputMap {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<InputMap, ConfigError> {
+        let path = path.as_ref();
+        let text =
+            fs::read_to_string(path).map_err(|err| ConfigError::Io(path.to_path_buf(), err))?;
+        InputMap::from_toml(&text).map_err(|err| ConfigError::Parse(path.to_path_buf(), err))
+    }
+
+    pub fn from_toml(text: &str) -> Result<InputMap, String> {
+        let file: InputMapFile = toml::from_str(text).map_err(|err| err.to_string())?;
+        let mut map = InputMap::default();
+
+        for (action, names) in file.actions {
+            let bindings = names
+                .iter()
+                .map(|name| parse_binding(name).ok_or_else(|| unknown(&action, name)))
+                .collect::<Result<_, _>>()?;
+            map.actions.insert(action, bindings);
+        }
+        for (axis, entry) in file.axes {
+            let buttons = |names: &[String]| {
+                names
+                    .iter()
+                    .map(|name| parse_binding(name).ok_or_else(|| unknown(&axis, name)))
+                    .collect::<Result<Vec<_>, _>>()
+            };
+            let binding = AxisBinding {
+                positive: buttons(&entry.positive)?,
+                negative: buttons(&entry.negative)?,
+                analog: entry
+                    .analog
+                    .iter()
+                    .map(|name| parse_analog(name).ok_or_else(|| unknown(&axis, name)))
+                    .collect::<Result<_, _>>()?,
+            };
+            map.axes.insert(axis, binding);
+        }
+        Ok(map)
+    }
+}
+
+fn unknown(entry: &str, binding: &str) -> String {
+    format!("`{}`: unknown binding `{}`", entry, binding)
+}
+
+/// Parse a key, mouse button or gamepad button name
+pub fn parse_binding(name: &str) -> Option<Binding> {
+    let name = name.trim();
+    if let Some(button) = name.strip_prefix("mouse:") {
+        let button = match button.to_ascii_lowercase().as_str() {
+            "left" => MouseButton::Button1,
+            "right" => MouseButton::Button2,
+            "middle" => MouseButton::Button3,
+            n => MouseButton::from_i32(n.parse::<i32>().ok()? - 1)?,
+        };
+        return Some(Binding::Mouse(button));
+    }
+    if let Some(button) = name.strip_prefix("gamepad:") {
+        return (0..=glfw::ffi::GAMEPAD_BUTTON_LAST)
+            .filter_map(GamepadButton::from_i32)
+            .find(|b| format!("{:?}", b)[6..].eq_ignore_ascii_case(button))
+            .map(Binding::Gamepad);
+    }
+    KEYS.iter()
+        .find(|(key_name, _)| key_name.eq_ignore_ascii_case(name))
+        .map(|(_, key)| Binding::Key(*key))
+}
+
+/// Parse an analog source like `mouse:x`, `-gamepad:LeftY` or `gamepad:RightX*4`
+pub fn parse_analog(name: &str) -> Option<(AnalogSource, f32)> {
+    let mut name = name.trim();
+    let mut scale = 1.0;
+    if let Some((source, factor)) = name.split_once('*') {
+        scale = factor.trim().parse().ok()?;
+        name = source.trim();
+    }
+    if let Some(source) = name.strip_prefix('-') {
+        scale = -scale;
+        name = source;
+    }
+    let source = match name.to_ascii_lowercase().as_str() {
+        "mouse:x" => AnalogSource::MouseX,
+        "mouse:y" => AnalogSource::MouseY,
+        "scroll:x" => AnalogSource::ScrollX,
+        "scroll:y" => AnalogSource::ScrollY,
+        _ => {
+            let axis = name.strip_prefix("gamepad:")?;
+            let axis = (0..=glfw::ffi::GAMEPAD_AXIS_LAST)
+                .filter_map(GamepadAxis::from_i32)
+                .find(|a| format!("{:?}", a)[4..].eq_ignore_ascii_case(axis))?;
+            AnalogSource::Gamepad(axis)
+        }
+    };
+    Some((source, scale))
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct ActionState {
+    held: bool,
+    pressed: bool,
+    released: bool,
+}
+
+/// Per-frame input state, queried by action and axis name instead of glfw keys.
+///
+/// Feed every window event to `handle_event`, then call `update` once per frame before
+/// querying; pressed / released stay true for exactly one frame. Both edges are taken from the
+/// events, so a tap that goes down and up within one frame is pressed and released.
+pub struct Input {
+    pub map: InputMap,
+    down: HashSet<Binding>,
+    /// bindings that went down during this frame, so a tap shorter than a frame still counts
+    tapped: HashSet<Binding>,
+    /// bindings that went up during this frame
+    lifted: HashSet<Binding>,
+    actions: HashMap<String, ActionState>,
+    axes: HashMap<String, f32>,
+    cursor: Option<(f64, f64)>,
+    mouse_delta: (f32, f32),
+    scroll: (f32, f32),
+    gamepad_axes: [f32; (glfw::ffi::GAMEPAD_AXIS_LAST + 1) as usize],
+}
+
+impl Input {
+    pub fn new(map: InputMap) -> Input {
+        Input {
+            map,
+            down: HashSet::new(),
+            tapped: HashSet::new(),
+            lifted: HashSet::new(),
+            actions: HashMap::new(),
+            axes: HashMap::new(),
+            cursor: None,
+            mouse_delta: (0.0, 0.0),
+            scroll: (0.0, 0.0),
+            gamepad_axes: [0.0; (glfw::ffi::GAMEPAD_AXIS_LAST + 1) as usize],
+        }
+    }
+
+    /// track keys, mouse buttons, cursor movement and scrolling
+    /// ------------------------------------------------------------------------
+    pub fn handle_event(&mut self, event: &WindowEvent) {
+        match *event {
+            WindowEvent::Key(key, _, action, _) => self.set_binding(Binding::Key(key), action),
+            WindowEvent::MouseButton(button, action, _) => {
+                self.set_binding(Binding::Mouse(button), action)
+            }
+            WindowEvent::CursorPos(x, y) => {
+                if let Some((last_x, last_y)) = self.cursor {
+                    self.mouse_delta.0 += (x - last_x) as f32;
+                    // window coordinates go from top to bottom
+                    self.mouse_delta.1 += (last_y - y) as f32;
+                }
+                self.cursor = Some((x, y));
+            }
+            WindowEvent::Scroll(x, y) => {
+                self.scroll.0 += x as f32;
+                self.scroll.1 += y as f32;
+            }
+            WindowEvent::Focus(false) => {
+                // nothing is let go of while the window is unfocused, so count it now
+                self.lifted.extend(self.down.drain());
+            }
+            _ => {}
+        }
+    }
+
+    /// Forget the last cursor position, e.g. after capturing the cursor, so the next movement
+    /// doesn't produce a jump.
+    /// ------------------------------------------------------------------------
+    pub fn reset_cursor(&mut self) {
+        self.cursor = None;
+    }
+
+    /// last cursor position in window coordinates (origin top left), once it has moved
+    pub fn cursor(&self) -> Option<(f64, f64)> {
+        self.cursor
+    }
+
+    pub fn set_gamepad_button(&mut self, button: GamepadButton, pressed: bool) {
+        let action = if pressed {
+            Action::Press
+        } else {
+            Action::Release
+        };
+        if pressed != self.down.contains(&Binding::Gamepad(button)) {
+            self.set_binding(Binding::Gamepad(button), action);
+        }
+    }
+
+    pub fn set_gamepad_axis(&mut self, axis: GamepadAxis, value: f32) {
+        self.gamepad_axes[axis as usize] = value;
+    }
+
+    fn set_binding(&mut self, binding: Binding, action: Action) {
+        match action {
+            Action::Press => {
+                self.down.insert(binding);
+                self.tapped.insert(binding);
+            }
+            Action::Release => {
+                if self.down.remove(&binding) {
+                    self.lifted.insert(binding);
+                }
+            }
+            Action::Repeat => {}
+        }
+    }
+
+    /// Resolve the events of this frame into action and axis values.
+    /// ------------------------------------------------------------------------
+    pub fn update(&mut self) {
+        for (name, bindings) in self.map.actions.iter() {
+            let held = bindings.iter().any(|b| self.down.contains(b));
+            let tapped = bindings.iter().any(|b| self.tapped.contains(b));
+            let lifted = bindings.iter().any(|b| self.lifted.contains(b));
+            let state = self.actions.entry(name.clone()).or_default();
+            let was_held = state.held;
+            *state = ActionState {
+                held,
+                pressed: tapped || (held && !was_held),
+                released: !held && (was_held || lifted),
+            };
+        }
+
+        for (name, binding) in self.map.axes.iter() {
+            let digital = |bindings: &[Binding]| {
+                if bindings.iter().any(|b| self.down.contains(b)) {
+                    1.0
+                } else {
+                    0.0
+                }
+            };
+            let analog: f32 = binding
+                .analog
+                .iter()
+                .map(|(source, scale)| {
+                    scale
+                        * match *source {
+                            AnalogSource::MouseX => self.mouse_delta.0,
+                            AnalogSource::MouseY => self.mouse_delta.1,
+                            AnalogSource::ScrollX => self.scroll.0,
+                            AnalogSource::ScrollY => self.scroll.1,
+                            AnalogSource::Gamepad(axis) => self.gamepad_axes[axis as usize],
+                        }
+                })
+                .sum();
+            let value = digital(&binding.positive) - digital(&binding.negative) + analog;
+            self.axes.insert(name.clone(), value);
+        }
+
+        self.tapped.clear();
+        self.lifted.clear();
+        self.mouse_delta = (0.0, 0.0);
+        self.scroll = (0.0, 0.0);
+    }
+
+    /// true on the frame the action went down
+    pub fn pressed(&self, action: &str) -> bool {
+        self.actions.get(action).is_some_and(|state| state.pressed)
+    }
+
+    /// true while any binding of the action is down
+    pub fn held(&self, action: &str) -> bool {
+        self.actions.get(action).is_some_and(|state| state.held)
+    }
+
+    /// true on the frame the action was let go
+    pub fn released(&self, action: &str) -> bool {
+        self.actions.get(action).is_some_and(|state| state.released)
+    }
+
+    /// value of an axis this frame; 0 for unknown axes
+    pub fn axis(&self, axis: &str) -> f32 {
+        self.axes.get(axis).copied().unwrap_or(0.0)
+    }
+
+    /// two axes combined, e.g. `axis2("look_x", "look_y")`
+    pub fn axis2(&self, x: &str, y: &str) -> (f32, f32) {
+        (self.axis(x), self.axis(y))
+    }
+}
+
+/// Key from its glfw key code, `Key::Unknown` for codes glfw doesn't name
+pub fn key_from_code(code: i32) -> Key {
+    KEYS.iter()
+        .map(|(_, key)| *key)
+        .find(|key| *key as i32 == code)
+        .unwrap_or(Key::Unknown)
+}
+
+macro_rules! key_table {
+    ($($key:ident),* $(,)?) => {
+        &[$((stringify!($key), Key::$key)),*]
+    };
+}
+
+/// glfw key names accepted in input maps
+const KEYS: &[(&str, Key)] = key_table!(
+    Space,
+    Apostrophe,
+    Comma,
+    Minus,
+    Period,
+    Slash,
+    Num0,
+    Num1,
+    Num2,
+    Num3,
+    Num4,
+    Num5,
+    Num6,
+    Num7,
+    Num8,
+    Num9,
+    Semicolon,
+    Equal,
+    A,
+    B,
+    C,
+    D,
+    E,
+    F,
+    G,
+    H,
+    I,
+    J,
+    K,
+    L,
+    M,
+    N,
+    O,
+    P,
+    Q,
+    R,
+    S,
+    T,
+    U,
+    V,
+    W,
+    X,
+    Y,
+    Z,
+    LeftBracket,
+    Backslash,
+    RightBracket,
+    GraveAccent,
+    World1,
+    World2,
+    Escape,
+    Enter,
+    Tab,
+    Backspace,
+    Insert,
+    Delete,
+    Right,
+    Left,
+    Down,
+    Up,
+    PageUp,
+    PageDown,
+    Home,
+    End,
+    CapsLock,
+    ScrollLock,
+    NumLock,
+    PrintScreen,
+    Pause,
+    F1,
+    F2,
+    F3,
+    F4,
+    F5,
+    F6,
+    F7,
+    F8,
+    F9,
+    F10,
+    F11,
+    F12,
+    F13,
+    F14,
+    F15,
+    F16,
+    F17,
+    F18,
+    F19,
+    F20,
+    F21,
+    F22,
+    F23,
+    F24,
+    F25,
+    Kp0,
+    Kp1,
+    Kp2,
+    Kp3,
+    Kp4,
+    Kp5,
+    Kp6,
+    Kp7,
+    Kp8,
+    Kp9,
+    KpDecimal,
+    KpDivide,
+    KpMultiply,
+    KpSubtract,
+    KpAdd,
+    KpEnter,
+    KpEqual,
+    LeftShift,
+    LeftControl,
+    LeftAlt,
+    LeftSuper,
+    RightShift,
+    RightControl,
+    RightAlt,
+    RightSuper,
+    Menu,
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glfw::Modifiers;
+
+    const MAP: &str = r#"
+        [actions]
+        jump = ["Space", "gamepad:A"]
+        fire = ["mouse:left"]
+
+        [axes.move_x]
+        positive = ["D"]
+        negative = ["a"]
+        analog = ["-gamepad:LeftX*2"]
+
+        [axes.look_y]
+        analog = ["mouse:y"]
+    "#;
+
+    fn key(key: Key, action: Action) -> WindowEvent {
+        WindowEvent::Key(key, 0, action, Modifiers::empty())
+    }
+
+    fn input() -> Input {
+        Input::new(InputMap::from_toml(MAP).unwrap())
+    }
+
+    #[test]
+    fn bindings_parse_by_name() {
+        assert_eq!(
+            parse_binding("LeftShift"),
+            Some(Binding::Key(Key::LeftShift))
+        );
+        assert_eq!(parse_binding(" f11 "), Some(Binding::Key(Key::F11)));
+        assert_eq!(
+            parse_binding("mouse:middle"),
+            Some(Binding::Mouse(MouseButton::Button3))
+        );
+        assert_eq!(
+            parse_binding("mouse:4"),
+            Some(Binding::Mouse(MouseButton::Button4))
+        );
+        assert_eq!(
+            parse_binding("gamepad:dpadup"),
+            Some(Binding::Gamepad(GamepadButton::ButtonDpadUp))
+        );
+        assert_eq!(parse_binding("Shift"), None);
+        assert_eq!(parse_binding("mouse:9"), None);
+
+        assert_eq!(parse_analog("scroll:y"), Some((AnalogSource::ScrollY, 1.0)));
+        assert_eq!(
+            parse_analog("-gamepad:RightY*10"),
+            Some((AnalogSource::Gamepad(GamepadAxis::AxisRightY), -10.0))
+        );
+        assert_eq!(parse_analog("gamepad:LeftX*fast"), None);
+
+        let error = InputMap::from_toml("[actions]\njump = [\"Spcae\"]").unwrap_err();
+        assert_eq!(error, "`jump`: unknown binding `Spcae`");
+    }
+
+    #[test]
+    fn actions_go_down_and_up_for_one_frame() {
+        let mut input = input();
+        input.handle_event(&key(Key::Space, Action::Press));
+        input.update();
+        assert!(input.pressed("jump") && input.held("jump") && !input.released("jump"));
+        // key repeat doesn't press again
+        input.handle_event(&key(Key::Space, Action::Repeat));
+        input.update();
+        assert!(!input.pressed("jump") && input.held("jump"));
+        input.handle_event(&key(Key::Space, Action::Release));
+        input.update();
+        assert!(!input.held("jump") && input.released("jump"));
+        input.update();
+        assert!(!input.released("jump"));
+        assert!(!input.pressed("unknown") && !input.held("unknown"));
+    }
+
+    #[test]
+    fn taps_within_a_frame_are_kept() {
+        let mut input = input();
+        input.handle_event(&key(Key::Space, Action::Press));
+        input.handle_event(&key(Key::Space, Action::Release));
+        input.update();
+        assert!(input.pressed("jump") && !input.held("jump") && input.released("jump"));
+
+        // letting go of one of two bindings keeps the action held
+        input.handle_event(&key(Key::Space, Action::Press));
+        input.set_gamepad_button(GamepadButton::ButtonA, true);
+        input.update();
+        input.handle_event(&key(Key::Space, Action::Release));
+        input.update();
+        assert!(input.held("jump") && !input.released("jump"));
+        input.set_gamepad_button(GamepadButton::ButtonA, false);
+        input.update();
+        assert!(input.released("jump"));
+
+        // losing focus lets go of everything
+        input.handle_event(&WindowEvent::MouseButton(
+            MouseButton::Button1,
+            Action::Press,
+            Modifiers::empty(),
+        ));
+        input.update();
+        input.handle_event(&WindowEvent::Focus(false));
+        input.update();
+        assert!(!input.held("fire") && input.released("fire"));
+    }
+
+    #[test]
+    fn axes_add_up_their_inputs() {
+        let mut input = input();
+        input.handle_event(&key(Key::D, Action::Press));
+        input.set_gamepad_axis(GamepadAxis::AxisLeftX, 0.25);
+        input.update();
+        assert_eq!(input.axis("move_x"), 0.5);
+        input.handle_event(&key(Key::A, Action::Press));
+        input.update();
+        assert_eq!(input.axis("move_x"), -0.5);
+
+        // cursor movement is a per-frame delta, y up
+        input.handle_event(&WindowEvent::CursorPos(10.0, 10.0));
+        input.handle_event(&WindowEvent::CursorPos(12.0, 4.0));
+        input.update();
+        assert_eq!(input.axis("look_y"), 6.0);
+        input.update();
+        assert_eq!(input.axis2("look_y", "unknown"), (0.0, 0.0));
+    }
+}
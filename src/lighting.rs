@@ -0,0 +1,458 @@
+use std::ffi::CStr;
+use std::os::raw::c_void;
+
+use cgmath::prelude::*;
+use cgmath::{vec3, Point3, Vector3};
+use gl::types::*;
+
+use crate::gltf_scene::LightKind;
+use crate::scene::PlacedLight;
+use crate::shader::Shader;
+use crate::shadow::{
+    LightShadow, PointShadow, ShadowSettings, MAX_CASCADES, MAX_POINT_SHADOWS, MAX_SHADOW_MAPS,
+};
+
+/// Lights of each type the `Lights` block has room for; keep in step with
+/// `src/shaders/lit.fs`.
+pub const MAX_DIRECTIONAL_LIGHTS: usize = 4;
+pub const MAX_POINT_LIGHTS: usize = 16;
+pub const MAX_SPOT_LIGHTS: usize = 8;
+
+/// uniform buffer binding point of the `Lights` block
+pub const LIGHTS_BINDING: GLuint = 0;
+
+/// range assumed for point and spot lights without one, where attenuation has made them
+/// faint
+pub const DEFAULT_RANGE: f32 = 50.0;
+
+/// size of the `Lights` block in vec4s: 3 header rows, then 2 per directional light, 3 per
+/// point light and 5 per spot light, then the shadow parameters, the point shadow ranges, the
+/// view direction, the cascade splits and a mat4 per shadow map
+const BLOCK_ROWS: usize = 3
+    + 2 * MAX_DIRECTIONAL_LIGHTS
+    + 3 * MAX_POINT_LIGHTS
+    + 5 * MAX_SPOT_LIGHTS
+    + 4
+    + 4 * MAX_SHADOW_MAPS;
+
+/// Distance falloff `1 / (constant + linear * d + quadratic * d^2)`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Attenuation {
+    pub constant: f32,
+    pub linear: f32,
+    pub quadratic: f32,
+}
+
+impl Attenuation {
+    /// Coefficients `linear = 4.5 / range` and `quadratic = 75 / range^2`, which leave a light
+    /// at 1 / 80.5, about 1.2%, of its intensity at `range` whatever the range. At range 50
+    /// that is close to the usual table's 0.09 and 0.032.
+    /// ------------------------------------------------------------------------
+    pub fn from_range(range: f32) -> Attenuation {
+        let range = range.max(f32::EPSILON);
+        Attenuation {
+            constant: 1.0,
+            linear: 4.5 / range,
+            quadratic: 75.0 / (range * range),
+        }
+    }
+
+    #[cfg(test)]
+    pub fn at(&self, distance: f32) -> f32 {
+        1.0 / (self.constant + self.linear * distance + self.quadratic * distance * distance)
+    }
+}
+
+/// Everything the `Lights` uniform block holds for one frame: the camera position for
+/// specular highlights and its direction for picking shadow cascades, the ambient term, the
+/// Phong / Blinn-Phong switch, the scene's lights and the shadow maps rendered for them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LightSet {
+    pub view_position: Point3<f32>,
+    pub view_direction: Vector3<f32>,
+    pub ambient: Vector3<f32>,
+    /// Blinn-Phong half vector highlights instead of Phong reflection
+    pub blinn: bool,
+    pub lights: Vec<PlacedLight>,
+    /// from `ShadowMaps::render`; a light's shadow map layer is its index here
+    pub shadows: Vec<LightShadow>,
+    /// from `ShadowMaps::render_points`; a point light's cubemap is its index here
+    pub point_shadows: Vec<PointShadow>,
+    pub shadow_settings: ShadowSettings,
+    /// tint each shadow cascade of the first directional light a different colour
+    pub show_cascades: bool,
+}
+
+impl Default for LightSet {
+    fn default() -> LightSet {
+        LightSet {
+            view_position: Point3::origin(),
+            view_direction: -Vector3::unit_z(),
+            ambient: vec3(0.05, 0.05, 0.05),
+            blinn: true,
+            lights: Vec::new(),
+            shadows: Vec::new(),
+            point_shadows: Vec::new(),
+            shadow_settings: ShadowSettings::default(),
+            show_cascades: false,
+        }
+    }
+}
+
+impl LightSet {
+    /// Number of (directional, point, spot) lights, before they are cut to the block's size.
+    /// ------------------------------------------------------------------------
+    pub fn counts(&self) -> (usize, usize, usize) {
+        self.lights
+            .iter()
+            .fold((0, 0, 0), |(d, p, s), light| match light.light.kind {
+                LightKind::Directional => (d + 1, p, s),
+                LightKind::Point => (d, p + 1, s),
+                LightKind::Spot { .. } => (d, p, s + 1),
+            })
+    }
+
+    /// true when some lights don't fit in the block and are left out
+    pub fn over_budget(&self) -> bool {
+        let (directional, point, spot) = self.counts();
+        directional > MAX_DIRECTIONAL_LIGHTS || point > MAX_POINT_LIGHTS || spot > MAX_SPOT_LIGHTS
+    }
+
+    /// The block's contents in std140 layout, every member a vec4. Lights past the limits
+    /// are dropped in scene order; colours are premultiplied by intensity. The direction of
+    /// a light with a shadow map holds its layer + 1 in w, 0 meaning no shadow, and the
+    /// attenuation of a point light its cubemap + 1.
+    /// ------------------------------------------------------------------------
+    pub fn std140(&self) -> Vec<[f32; 4]> {
+        let mut directional = Vec::new();
+        let mut point = Vec::new();
+        let mut spot = Vec::new();
+        for placed in &self.lights {
+            let light = &placed.light;
+            let color = (light.color * light.intensity).extend(1.0).into();
+            let position = placed
+                .position
+                .to_vec()
+                .extend(light.range.unwrap_or(0.0))
+                .into();
+            let layer = self
+                .shadows
+                .iter()
+                .take(MAX_SHADOW_MAPS)
+                .position(|shadow| shadow.node == placed.node)
+                .map_or(0.0, |layer| layer as f32 + 1.0);
+            let direction = placed.direction.extend(layer).into();
+            let cube = self
+                .point_shadows
+                .iter()
+                .take(MAX_POINT_SHADOWS)
+                .position(|shadow| shadow.node == placed.node)
+                .map_or(0.0, |cube| cube as f32 + 1.0);
+            let attenuation = Attenuation::from_range(light.range.unwrap_or(DEFAULT_RANGE));
+            let attenuation = [
+                attenuation.constant,
+                attenuation.linear,
+                attenuation.quadratic,
+                cube,
+            ];
+            match light.kind {
+                LightKind::Directional if directional.len() < 2 * MAX_DIRECTIONAL_LIGHTS => {
+                    directional.extend([direction, color])
+                }
+                LightKind::Point if point.len() < 3 * MAX_POINT_LIGHTS => {
+                    point.extend([position, color, attenuation])
+                }
+                LightKind::Spot {
+                    inner_cone,
+                    outer_cone,
+                } if spot.len() < 5 * MAX_SPOT_LIGHTS => spot.extend([
+                    position,
+                    direction,
+                    color,
+                    attenuation,
+                    [inner_cone.cos(), outer_cone.cos(), 0.0, 0.0],
+                ]),
+                _ => {}
+            }
+        }
+        let counts = [
+            (directional.len() / 2) as f32,
+            (point.len() / 3) as f32,
+            (spot.len() / 5) as f32,
+            0.0,
+        ];
+        let mut rows = Vec::with_capacity(BLOCK_ROWS);
+        rows.push(
+            self.view_position
+                .to_vec()
+                .extend(if self.blinn { 1.0 } else { 0.0 })
+                .into(),
+        );
+        rows.push(self.ambient.extend(0.0).into());
+        rows.push(counts);
+        for (lights, capacity) in [
+            (directional, 2 * MAX_DIRECTIONAL_LIGHTS),
+            (point, 3 * MAX_POINT_LIGHTS),
+            (spot, 5 * MAX_SPOT_LIGHTS),
+        ] {
+            let unused = capacity - lights.len();
+            rows.extend(lights);
+            rows.extend(std::iter::repeat_n([0.0; 4], unused));
+        }
+        let settings = &self.shadow_settings;
+        rows.push([
+            settings.bias,
+            settings.slope_bias,
+            settings.pcf_radius as f32,
+            if self.show_cascades { 1.0 } else { 0.0 },
+        ]);
+        let mut far = [0.0; 4];
+        for (far, shadow) in far.iter_mut().zip(&self.point_shadows) {
+            *far = shadow.far;
+        }
+        rows.push(far);
+        // every directional light is split at the same depths, the first one's will do
+        let directional = self.shadows.iter().find(|shadow| shadow.split > 0.0);
+        let cascades: Vec<f32> = self
+            .shadows
+            .iter()
+            .filter(|shadow| Some(shadow.node) == directional.map(|first| first.node))
+            .map(|shadow| shadow.split)
+            .take(MAX_CASCADES)
+            .collect();
+        rows.push(self.view_direction.extend(cascades.len() as f32).into());
+        let mut splits = [0.0; 4];
+        splits[..cascades.len()].copy_from_slice(&cascades);
+        rows.push(splits);
+        for layer in 0..MAX_SHADOW_MAPS {
+            let light_space: [[f32; 4]; 4] = match self.shadows.get(layer) {
+                Some(shadow) => shadow.light_space.into(),
+                None => [[0.0; 4]; 4],
+            };
+            rows.extend(light_space);
+        }
+        rows
+    }
+}
+
+/// The uniform buffer behind the `Lights` block, bound to `LIGHTS_BINDING` so every shader
+/// attached to it reads the same lights.
+pub struct LightBuffer {
+    ubo: GLuint,
+    warned: bool,
+}
+
+impl LightBuffer {
+    /// Create the buffer and bind it; needs a current GL context.
+    /// ------------------------------------------------------------------------
+    pub fn new() -> LightBuffer {
+        let mut ubo = 0;
+        unsafe {
+            gl::GenBuffers(1, &mut ubo);
+            gl::BindBuffer(gl::UNIFORM_BUFFER, ubo);
+            gl::BufferData(
+                gl::UNIFORM_BUFFER,
+                (BLOCK_ROWS * 16) as GLsizeiptr,
+                std::ptr::null(),
+                gl::DYNAMIC_DRAW,
+            );
+            gl::BindBuffer(gl::UNIFORM_BUFFER, 0);
+            gl::BindBufferBase(gl::UNIFORM_BUFFER, LIGHTS_BINDING, ubo);
+        }
+        LightBuffer { ubo, warned: false }
+    }
+
+    /// Upload this frame's lights. Warns once when there are more than the block holds.
+    /// ------------------------------------------------------------------------
+    pub fn update(&mut self, lights: &LightSet) {
+        if lights.over_budget() && !self.warned {
+            let (directional, point, spot) = lights.counts();
+            println!(
+                "WARNING::LIGHTS::TOO_MANY {} directional, {} point and {} spot lights; \
+                 only {}, {} and {} are used",
+                directional, point, spot, MAX_DIRECTIONAL_LIGHTS, MAX_POINT_LIGHTS, MAX_SPOT_LIGHTS
+            );
+            self.warned = true;
+        }
+        let rows = lights.std140();
+        unsafe {
+            gl::BindBuffer(gl::UNIFORM_BUFFER, self.ubo);
+            gl::BufferSubData(
+                gl::UNIFORM_BUFFER,
+                0,
+                (rows.len() * 16) as GLsizeiptr,
+                rows.as_ptr() as *const c_void,
+            );
+            gl::BindBuffer(gl::UNIFORM_BUFFER, 0);
+        }
+    }
+
+    /// Point `shader`'s `Lights` block at the buffer. Returns false for shaders without one,
+    /// which are left alone.
+    /// ------------------------------------------------------------------------
+    pub fn attach(&self, shader: &Shader) -> bool {
+        unsafe {
+            let index = gl::GetUniformBlockIndex(shader.ID, c_str!("Lights").as_ptr());
+            if index == gl::INVALID_INDEX {
+                return false;
+            }
+            gl::UniformBlockBinding(shader.ID, index, LIGHTS_BINDING);
+        }
+        true
+    }
+}
+
+impl Drop for LightBuffer {
+    fn drop(&mut self) {
+        unsafe { gl::DeleteBuffers(1, &self.ubo) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene::{Light, Scene, Transform};
+    use cgmath::{Deg, Matrix4, Quaternion};
+
+    fn light(kind: LightKind, range: Option<f32>) -> Light {
+        Light {
+            kind,
+            color: vec3(1.0, 0.5, 0.25),
+            intensity: 2.0,
+            range,
+        }
+    }
+
+    #[test]
+    fn attenuation_fades_towards_the_range() {
+        let near = Attenuation::from_range(7.0);
+        assert!((near.linear - 0.643).abs() < 1e-3 && (near.quadratic - 1.531).abs() < 1e-3);
+        let far = Attenuation::from_range(50.0);
+        assert!((far.linear - 0.09).abs() < 1e-6 && (far.quadratic - 0.03).abs() < 1e-6);
+        assert_eq!(far.at(0.0), 1.0);
+        for range in [1.0, 7.0, 50.0, 3250.0] {
+            let at_range = Attenuation::from_range(range).at(range);
+            assert!(at_range > 0.01 && at_range < 0.02, "{}", at_range);
+        }
+    }
+
+    #[test]
+    fn lights_pack_into_the_block() {
+        let mut scene = Scene::new();
+        let sun = scene.add_node("sun", Transform::default(), None);
+        scene.set_rotation(sun, Quaternion::from_angle_x(Deg(-90.0)));
+        scene.set_light(sun, Some(light(LightKind::Directional, None)));
+        let lamp = scene.add_node(
+            "lamp",
+            Transform::from_translation(vec3(1.0, 2.0, 3.0)),
+            None,
+        );
+        scene.set_light(lamp, Some(light(LightKind::Point, Some(7.0))));
+        let spot = LightKind::Spot {
+            inner_cone: 0.0,
+            outer_cone: std::f32::consts::FRAC_PI_3,
+        };
+        let torch = scene.add_node("torch", Transform::default(), Some(lamp));
+        scene.set_light(torch, Some(light(spot, None)));
+
+        let set = LightSet {
+            view_position: Point3::new(0.0, 1.0, 5.0),
+            lights: scene.lights(),
+            ..LightSet::default()
+        };
+        assert_eq!(set.counts(), (1, 1, 1));
+        let rows = set.std140();
+        assert_eq!(rows.len(), BLOCK_ROWS);
+        assert_eq!(rows[0], [0.0, 1.0, 5.0, 1.0]);
+        assert_eq!(rows[2], [1.0, 1.0, 1.0, 0.0]);
+        let close = |a: [f32; 4], b: [f32; 4]| a.iter().zip(&b).all(|(a, b)| (a - b).abs() < 1e-6);
+        // the sun points straight down, colours carry the intensity
+        assert!(close(rows[3], [0.0, -1.0, 0.0, 0.0]));
+        assert_eq!(rows[4], [2.0, 1.0, 0.5, 1.0]);
+        let point = 3 + 2 * MAX_DIRECTIONAL_LIGHTS;
+        assert_eq!(rows[point], [1.0, 2.0, 3.0, 7.0]);
+        assert!(close(rows[point + 2], [1.0, 4.5 / 7.0, 75.0 / 49.0, 0.0]));
+        // the spot inherits its parent's position, with no range and the default falloff
+        let spot = point + 3 * MAX_POINT_LIGHTS;
+        assert_eq!(rows[spot], [1.0, 2.0, 3.0, 0.0]);
+        assert!(close(rows[spot + 1], [0.0, 0.0, -1.0, 0.0]));
+        assert!(close(rows[spot + 3], [1.0, 0.09, 0.03, 0.0]));
+        assert!(close(rows[spot + 4], [1.0, 0.5, 0.0, 0.0]));
+        assert!(!set.over_budget());
+    }
+
+    #[test]
+    fn shadows_are_found_by_layer() {
+        let mut scene = Scene::new();
+        let sun = scene.add_node("sun", Transform::default(), None);
+        scene.set_light(sun, Some(light(LightKind::Directional, None)));
+        let spot = LightKind::Spot {
+            inner_cone: 0.1,
+            outer_cone: 0.2,
+        };
+        let torch = scene.add_node("torch", Transform::default(), None);
+        scene.set_light(torch, Some(light(spot, None)));
+        let lamp = scene.add_node("lamp", Transform::default(), None);
+        scene.set_light(lamp, Some(light(LightKind::Point, Some(7.0))));
+        let light_space = Matrix4::from_translation(vec3(1.0, 2.0, 3.0));
+        let cascade = |split| LightShadow {
+            node: sun,
+            light_space: Matrix4::identity(),
+            split,
+        };
+        let set = LightSet {
+            lights: scene.lights(),
+            shadows: vec![
+                cascade(5.0),
+                cascade(12.0),
+                LightShadow {
+                    node: torch,
+                    light_space,
+                    split: 0.0,
+                },
+            ],
+            point_shadows: vec![PointShadow {
+                node: lamp,
+                far: 7.0,
+            }],
+            ..LightSet::default()
+        };
+        let rows = set.std140();
+        // the sun's cascades come first, the torch gets the layer after them
+        assert_eq!(rows[3][3], 1.0);
+        let point = 3 + 2 * MAX_DIRECTIONAL_LIGHTS;
+        assert_eq!(rows[point + 2][3], 1.0);
+        let spot = point + 3 * MAX_POINT_LIGHTS;
+        assert_eq!(rows[spot + 1][3], 3.0);
+        let params = spot + 5 * MAX_SPOT_LIGHTS;
+        assert_eq!(rows[params], [0.0005, 0.002, 1.0, 0.0]);
+        assert_eq!(rows[params + 1], [7.0, 0.0, 0.0, 0.0]);
+        assert_eq!(rows[params + 2], [0.0, 0.0, -1.0, 2.0]);
+        assert_eq!(rows[params + 3], [5.0, 12.0, 0.0, 0.0]);
+        // matrices are stored a column per row, like GLSL's mat4
+        let torch_matrix = params + 4 + 2 * 4;
+        assert_eq!(rows[torch_matrix + 3], [1.0, 2.0, 3.0, 1.0]);
+        assert_eq!(rows[torch_matrix + 4], [0.0; 4]);
+    }
+
+    #[test]
+    fn extra_lights_are_dropped() {
+        let mut scene = Scene::new();
+        for i in 0..MAX_DIRECTIONAL_LIGHTS + 2 {
+            let sun = scene.add_node(&format!("sun{}", i), Transform::default(), None);
+            scene.set_light(sun, Some(light(LightKind::Directional, None)));
+        }
+        let set = LightSet {
+            lights: scene.lights(),
+            blinn: false,
+            ..LightSet::default()
+        };
+        assert!(set.over_budget());
+        let rows = set.std140();
+        assert_eq!(rows.len(), BLOCK_ROWS);
+        assert_eq!(rows[0][3], 0.0);
+        assert_eq!(rows[2][0], MAX_DIRECTIONAL_LIGHTS as f32);
+        // the point lights start right after the last directional light slot
+        assert_eq!(rows[3 + 2 * MAX_DIRECTIONAL_LIGHTS], [0.0; 4]);
+    }
+}
@@ -9,9 +9,9 @@ macro_rules! c_str {
 }
 
 /// Get offset to struct member, similar to `offset_of` in C/C++
-/// From https://stackoverflow.com/questions/40310483/how-to-get-pointer-offset-in-bytes/40310851#40310851
+/// (the old null pointer trick is rejected by current compilers, so this forwards to std)
 macro_rules! offset_of {
     ($ty:ty, $field:ident) => {
-        &(*(ptr::null() as *const $ty)).$field as *const _ as usize
+        std::mem::offset_of!($ty, $field)
     };
 }
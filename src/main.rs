@@ -1,61 +1,166 @@
 #![allow(non_upper_case_globals)]
 
+// declared first so its macros are in scope for every module below
 mod macros;
+
+mod bounds;
+mod bvh;
+mod camera;
+mod clock;
+mod config;
+mod framebuffer;
+mod frustum;
+mod gamepad;
+mod gltf_scene;
+mod input;
+mod lighting;
+mod material;
+mod mesh;
+mod mesh_optimize;
+mod model;
+mod obj;
+mod orbit_camera;
+mod picking;
+mod ply;
+mod ray;
+mod replay;
+mod scene;
+mod scene_file;
 mod shader;
+mod shadow;
+mod stl;
+mod surface;
+mod texture;
+mod window;
 
 extern crate glfw;
 
 use self::glfw::{Action, Context, Key};
-use std::detect::__is_feature_detected::sha;
 
 extern crate gl;
 extern crate image;
 
 use self::gl::types::*;
 
+use crate::bounds::Aabb;
+use crate::bvh::SceneBvh;
+use crate::camera::{Camera, CameraMovement, CameraView, MouseLook};
+use crate::clock::FrameTimer;
+use crate::config::{ConfigError, RunArgs};
+use crate::framebuffer::MsaaTarget;
+use crate::frustum::CullStats;
+use crate::gamepad::{GamepadEvent, Gamepads};
+use crate::input::{Input, InputMap};
+use crate::lighting::{LightBuffer, LightSet};
+use crate::material::Material;
+use crate::orbit_camera::OrbitCamera;
+use crate::picking::IdPicker;
+use crate::ray::Ray;
+use crate::replay::{Recorder, Replay};
+use crate::scene::Scene;
 use crate::shader::Shader;
+use crate::shadow::ShadowMaps;
+use crate::surface::Surface;
+use crate::texture::Texture;
+use crate::window::{DisplayState, Windows};
 use std::ffi::{CStr, CString};
 use std::mem;
 use std::os::raw::c_void;
 use std::path::Path;
 use std::ptr;
-use std::str;
-use std::sync::mpsc::Receiver;
-
-use image::{DynamicImage, GenericImage, GenericImageView};
+use std::rc::Rc;
 
 use cgmath::prelude::*;
-use cgmath::{perspective, vec3, Deg, Matrix4};
-
-const SCR_WIDTH: u32 = 800;
-const SCR_HEIGHT: u32 = 600;
+use cgmath::{Deg, Matrix4, Point3, Rad};
 
 #[allow(non_snake_case)]
 pub fn main() {
+    let run = RunArgs::from_args(std::env::args().skip(1)).unwrap_or_else(|err| {
+        eprintln!("{}\n{}", err, config::USAGE);
+        std::process::exit(2)
+    });
+    let mut config = run.window;
+    // --record <file> writes every frame's events and delta time, --replay <file> plays them
+    // back in a hidden window and compares the rendered frames with the recording
+    let record_path = run.record;
+    let model_path = run.model;
+    let scene_path = run.scene;
+    // --export <file> converts the --model mesh to PLY or STL without opening a window
+    if let Some((path, ascii)) = run.export {
+        let exported = model_path
+            .as_deref()
+            .ok_or_else(|| ConfigError::Arg("--export needs a --model".to_string()))
+            .and_then(scene_file::load_mesh_data)
+            .and_then(|mesh| {
+                scene_file::save_mesh_data(&path, &mesh, ascii)?;
+                Ok(mesh)
+            });
+        match exported {
+            Ok(mesh) => {
+                println!(
+                    "Exported {} vertices and {} triangles to {}",
+                    mesh.vertex_count(),
+                    mesh.triangle_count(),
+                    path
+                );
+                std::process::exit(0)
+            }
+            Err(err) => {
+                eprintln!("{}", err);
+                std::process::exit(2)
+            }
+        }
+    }
+    let mut replay = run.replay.map(|path| {
+        Replay::load(&path).unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            std::process::exit(2)
+        })
+    });
+    if replay.is_some() {
+        config.visible = false;
+    }
+
     let mut glfw = glfw::init(glfw::FAIL_ON_ERRORS).unwrap();
-    glfw.window_hint(glfw::WindowHint::ContextVersion(3, 3));
-    glfw.window_hint(glfw::WindowHint::OpenGlProfile(
-        glfw::OpenGlProfileHint::Core,
-    ));
-    #[cfg(target_os = "macos")]
-    glfw.window_hint(glfw::WindowHint::OpenGlForwardCompat(true));
-
-    let (mut window, events) = glfw
-        .create_window(
-            SCR_WIDTH,
-            SCR_HEIGHT,
-            "LearnOpenGL",
-            glfw::WindowMode::Windowed,
-        )
-        .expect("Failed to create GFLW Window");
-
-    window.make_current();
+    let (mut window, events) = window::create_window(&mut glfw, &config);
     window.set_key_polling(true);
     window.set_framebuffer_size_polling(true);
+    // losing focus releases every held key and button
+    window.set_focus_polling(true);
+    window.set_size_polling(true);
+    window.set_content_scale_polling(true);
+    window.set_cursor_pos_polling(true);
+    window.set_scroll_polling(true);
+    window.set_mouse_button_polling(true);
+
+    // key bindings live in resources/config/input.toml
+    let input_map = InputMap::load("resources/config/input.toml")
+        .unwrap_or_else(|err| panic!("Failed to load input map: {}", err));
+    let mut input = Input::new(input_map);
+    let mut gamepads = Gamepads::default();
+    // optional SDL mappings on top of the ones built into glfw
+    let mappings = Path::new("resources/config/gamecontrollerdb.txt");
+    if mappings.exists() {
+        match Gamepads::load_mappings(&glfw, mappings) {
+            Ok(count) => println!("Loaded {} gamepad mappings", count),
+            Err(err) => println!("WARNING::GAMEPAD:: {}", err),
+        }
+    }
 
-    gl::load_with(|symbol| window.get_proc_address(symbol) as *const _);
+    // fly camera: WASD + Space/LeftShift to fly, LeftControl to go faster, mouse to look,
+    // scroll to zoom, C releases the cursor and LeftAlt while held. O switches to the orbit
+    // camera: left-drag rotates, right-drag pans, scroll dollies and F frames the scene.
+    let mut cameras = Cameras {
+        fly: Camera::default(),
+        mouse_look: MouseLook::default(),
+        orbit: OrbitCamera::new(Point3::new(0.0, 0.0, 0.0), 2.0),
+        orbiting: false,
+        peeking: false,
+    };
+    cameras.fly.position = Point3::new(0.0, 0.0, 2.0);
+    cameras.mouse_look.set_captured(&mut window, true);
 
-    let (shader, VBO, VAO, EBO, texture, texture2) = unsafe {
+    let (quad, VBO, VAO, EBO) = unsafe {
         // build and compile our shader program
         // ------------------------------------
         // vertex shader
@@ -111,127 +216,643 @@ pub fn main() {
         );
         gl::EnableVertexAttribArray(1);
 
-        // load and create a texture
-        let (mut texture, mut texture2) = (0, 0);
-        gl::GenTextures(1, &mut texture);
-        gl::BindTexture(gl::TEXTURE_2D, texture);
-        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::REPEAT as i32);
-        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::REPEAT as i32);
-        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
-        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
-
-        let img = image::open(&Path::new("resources/textures/container.jpg"))
+        // load and create the textures; the material points texture1 and texture2 at their
+        // units whenever it is applied
+        let container = Texture::load("resources/textures/container.jpg", false)
             .expect("Failed to load texture");
-        let data = img.to_bytes();
-        gl::TexImage2D(
-            gl::TEXTURE_2D,
-            0,
-            gl::RGB as i32,
-            img.width() as i32,
-            img.height() as i32,
-            0,
-            gl::RGB,
-            gl::UNSIGNED_BYTE,
-            &data[0] as *const u8 as *const c_void,
-        );
-        gl::GenerateMipmap(gl::TEXTURE_2D);
-
-        // Texture 2
-        gl::GenTextures(1, &mut texture2);
-        gl::BindTexture(gl::TEXTURE_2D, texture2);
-        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::REPEAT as i32); // set texture wrapping to gl::REPEAT (default wrapping method)
-        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::REPEAT as i32);
-        // set texture filtering parameters
-        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
-        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
-        let img = image::open(&Path::new("resources/textures/awesomeface.png"))
+        let face = Texture::load("resources/textures/awesomeface.png", true)
             .expect("Failed to load texture");
-        let img = img.flipv(); // flip loaded texture on the y-axis.
-        let data = img.to_bytes();
-        gl::TexImage2D(
-            gl::TEXTURE_2D,
-            0,
-            gl::RGB as i32,
-            img.width() as i32,
-            img.height() as i32,
-            0,
-            gl::RGBA,
-            gl::UNSIGNED_BYTE,
-            &data[0] as *const u8 as *const c_void,
-        );
-        gl::GenerateMipmap(gl::TEXTURE_2D);
-        shader.useProgram();
-        shader.setInt(c_str!("texture1"), 0);
-        shader.setInt(c_str!("texture2"), 1);
+        let quad = Material::new(Rc::new(shader))
+            .with_texture("texture1", Rc::new(container))
+            .with_texture("texture2", Rc::new(face));
+        for problem in quad.validate() {
+            println!("WARNING::MATERIAL::INVALID quad: {}", problem);
+        }
         // gl::PolygonMode(gl::FRONT_AND_BACK, gl::LINE);
 
-        (shader, VBO, VAO, EBO, texture, texture2)
+        (quad, VBO, VAO, EBO)
     };
 
-    // render loop
-    while !window.should_close() {
-        process_events(&mut window, &events);
-
-        unsafe {
-            gl::ClearColor(0.2, 0.3, 0.3, 1.0);
-            gl::Clear(gl::COLOR_BUFFER_BIT);
-
-            //bind texture
-            gl::ActiveTexture(gl::TEXTURE0);
-            gl::BindTexture(gl::TEXTURE_2D, texture);
-            gl::ActiveTexture(gl::TEXTURE1);
-            gl::BindTexture(gl::TEXTURE_2D, texture2);
+    // --model <file> draws an OBJ, glTF, PLY or STL model instead of the quad
+    let mut scene = Scene::new();
+    if let Some(path) = model_path {
+        let shader = Rc::new(Shader::new(
+            "src/shaders/model_loading.vs",
+            "src/shaders/model_loading.fs",
+        ));
+        let material = Rc::new(Material::new(shader));
+        scene_file::add_model(&mut scene, &path, material)
+            .unwrap_or_else(|err| panic!("Failed to load model: {}", err));
+        unsafe { gl::Enable(gl::DEPTH_TEST) };
+    }
+    // --scene <file> loads a scene file, F5 writes the current state next to it
+    let scene_assets = scene_path.map(|path| {
+        let assets = scene_file::load_scene(&path, &mut scene)
+            .unwrap_or_else(|err| panic!("Failed to load scene: {}", err));
+        unsafe { gl::Enable(gl::DEPTH_TEST) };
+        (path, assets)
+    });
+    // start out looking through the first camera the model or scene file brought along
+    if let Some(view) = scene.iter().find_map(|id| scene.camera(id)) {
+        cameras.fly.look_through(&view);
+    }
+    // shaders with a `Lights` block (src/shaders/lit.fs) all read the scene's lights and
+    // shadow matrices from one uniform buffer; B switches their highlights between
+    // Blinn-Phong and Phong, H turns shadows off and on
+    let mut light_buffer = LightBuffer::new();
+    let mut lit = false;
+    for id in scene.iter() {
+        if let Some(renderable) = &scene.node(id).renderable {
+            if light_buffer.attach(&renderable.material.shader) {
+                ShadowMaps::attach(&renderable.material.shader);
+                lit = true;
+            }
+        }
+    }
+    // the depth maps only exist when lights cast them and a lit shader reads them
+    let lights = scene.lights();
+    let shadows = if lit && !lights.is_empty() {
+        let settings = scene_assets
+            .as_ref()
+            .map(|(_, assets)| assets.shadows())
+            .unwrap_or_default();
+        Some(ShadowMaps::new(settings, &lights))
+    } else {
+        None
+    };
+    let mut blinn = true;
+    let mut shadows_on = true;
+    let mut show_cascades = false;
+
+    // F11 toggles fullscreen, V toggles vsync, M cycles the MSAA sample count
+    let mut display = DisplayState::new(&window, &config);
+    let mut windows = Windows::new(window, events);
+
+    // --inspector opens a second window sharing buffers, textures and the shader program;
+    // only the VAO has to be created again in its context
+    let mut inspector = if run.inspector {
+        let id = windows.create_shared(&mut glfw, 400, 300, "Inspector");
+        let app = windows.make_current(id);
+        app.window.set_key_polling(true);
+        app.window.set_framebuffer_size_polling(true);
+        app.window.set_focus_polling(true);
+        let vao = unsafe { quad_vao(VBO, EBO) };
+        windows.make_current(Windows::MAIN);
+        Some((id, vao))
+    } else {
+        None
+    };
 
-            // let timeValue = glfw.get_time() as f32;
-            // let visibleOffset = timeValue.sin() / 2.0;
-            // let mut transform: Matrix4<f32> = Matrix4::identity();
-            // let time_cos = (glfw.get_time().cos() / 2.0) as f32;
-            // let time_sin = (glfw.get_time().sin() / 2.0) as f32;
-            // transform =
-            //     transform * Matrix4::<f32>::from_translation(vec3(time_sin, time_cos, time_sin));
-            // transform = transform * Matrix4::<f32>::from_angle_z(Rad(glfw.get_time() as f32));
+    // the framebuffer can differ from the requested window size (HiDPI), so query it
+    let main = windows.make_current(Windows::MAIN);
+    if let Some(replay) = &replay {
+        // frames are drawn offscreen at the recorded size, whatever the window got
+        main.surface.framebuffer_size = replay.framebuffer_size;
+    }
+    let surface = main.surface;
+    unsafe {
+        gl::Viewport(0, 0, surface.framebuffer_size.0, surface.framebuffer_size.1);
+    }
+    let (width, height) = surface.framebuffer_size;
+    // recorded and replayed frames are hashed, which needs pixels of our own to read
+    let mut msaa = if record_path.is_some() || replay.is_some() {
+        MsaaTarget::offscreen(config.samples, width, height)
+    } else {
+        MsaaTarget::new(config.samples, width, height)
+    };
+    let mut picker = IdPicker::new(&surface);
 
-            shader.useProgram();
+    // simulation runs at a fixed 60Hz, rendering as fast as the cap allows
+    let mut timer = FrameTimer::new(glfw.clone(), 1.0 / 60.0);
+    timer.set_fps_cap(Some(144.0));
 
-            let model: Matrix4<f32> = Matrix4::from_angle_x(Deg(-55.));
-            let view: Matrix4<f32> = Matrix4::from_translation(vec3(0., 0., -2.));
-            // create orthographic
-            let projection: Matrix4<f32> =
-                perspective(Deg(45.0), SCR_WIDTH as f32 / SCR_HEIGHT as f32, 0.1, 100.0);
-            let modelLoc = gl::GetUniformLocation(shader.ID, c_str!("model").as_ptr());
-            let viewLoc = gl::GetUniformLocation(shader.ID, c_str!("view").as_ptr());
+    let mut recorder = record_path
+        .map(|path| Recorder::create(&path, &surface).unwrap_or_else(|err| panic!("{}", err)));
+    if replay.is_some() {
+        // recorded frame times drive the timer, no need to wait for real time to pass
+        timer.set_fps_cap(None);
+    }
+    let mut mismatched_frames = 0;
+    // the quad turns in fixed steps of game time, so it stops when paused; frames draw it
+    // between its last two angles
+    let (mut previous_angle, mut quad_angle) = (0.0f32, 0.0f32);
+    // K toggles frustum culling; the counts are printed whenever they change
+    let mut cull_stats = CullStats::default();
 
-            gl::UniformMatrix4fv(modelLoc, 1, gl::FALSE, model.as_ptr());
-            gl::UniformMatrix4fv(viewLoc, 1, gl::FALSE, &view[0][0]);
+    // render loop
+    while !windows.main().window.should_close() {
+        let replayed = match replay.as_mut() {
+            Some(replay) => match replay.next_frame() {
+                Some(frame) => Some(frame.clone()),
+                None => break,
+            },
+            None => None,
+        };
+        let delta_time = match &replayed {
+            Some(frame) => timer.advance_frame(frame.delta),
+            None => timer.begin_frame(),
+        } as f32;
+        while timer.step() {
+            previous_angle = quad_angle;
+            quad_angle += 0.5 * timer.fixed_step() as f32;
+        }
 
-            shader.setMat4(c_str!("projection"), &projection);
+        let main = windows.make_current(Windows::MAIN);
+        let mut events = main.flush_events();
+        if let Some(frame) = &replayed {
+            // the hidden window's own events are dropped, it follows the recorded resizes
+            events = frame.events.clone();
+            for event in &events {
+                if let glfw::WindowEvent::Size(width, height) = *event {
+                    main.window.set_size(width, height);
+                }
+            }
+        }
+        let recorded_events = if recorder.is_some() {
+            events.clone()
+        } else {
+            Vec::new()
+        };
+        if process_events(events, &mut main.surface, &mut input, &mut cameras) {
+            main.surface.notify(&mut [&mut msaa, &mut picker]);
+        }
+        // replays bring their own gamepad state
+        let gamepad_events = if replayed.is_none() {
+            gamepads.poll(&glfw)
+        } else {
+            Vec::new()
+        };
+        let gamepad = match &replayed {
+            Some(frame) => frame.gamepad,
+            None => gamepads.state(),
+        };
+        gamepad.apply(&mut input);
+        for event in gamepad_events {
+            match event {
+                GamepadEvent::Connected(id, name) => {
+                    let role = if gamepads.active() == Some(id) {
+                        "active"
+                    } else {
+                        "standby"
+                    };
+                    println!("Gamepad {:?} connected: {} ({})", id, name, role)
+                }
+                GamepadEvent::Disconnected(id) => println!("Gamepad {:?} disconnected", id),
+            }
+        }
+        input.update();
+        process_actions(
+            &mut glfw,
+            &mut main.window,
+            &mut input,
+            &mut display,
+            &mut msaa,
+            &mut cameras,
+        );
+        if !cameras.orbiting {
+            process_input(&input, &mut cameras, delta_time);
+        }
+        // T pauses game time, [ and ] halve and double its speed
+        if input.pressed("toggle_pause") {
+            timer.clock.toggle_pause();
+            let state = if timer.clock.is_paused() {
+                "paused"
+            } else {
+                "running"
+            };
+            println!("Time: {} at {:.2}s", state, timer.time());
+        }
+        for (action, factor) in [("slow_down", 0.5), ("speed_up", 2.0)] {
+            if input.pressed(action) {
+                timer
+                    .clock
+                    .set_scale((timer.clock.scale() * factor).clamp(1.0 / 16.0, 16.0));
+                println!("Time scale: {}x", timer.clock.scale());
+            }
+        }
+        if input.pressed("frame_selection") && cameras.orbiting {
+            // everything the scene draws, or the box around the rotated quad without one
+            let bounds = match scene.bounds() {
+                bounds if bounds.is_empty() => {
+                    Aabb::new(Point3::new(-0.5, -0.5, -0.5), Point3::new(0.5, 0.5, 0.5))
+                }
+                bounds => bounds,
+            };
+            cameras.orbit.frame(&bounds, main.surface.aspect());
+        }
+        if input.pressed("toggle_culling") {
+            scene.culling = !scene.culling;
+            println!(
+                "Frustum culling: {}",
+                if scene.culling { "on" } else { "off" }
+            );
+        }
+        if input.pressed("toggle_shadows") {
+            shadows_on = !shadows_on;
+            println!("Shadows: {}", if shadows_on { "on" } else { "off" });
+        }
+        if input.pressed("show_cascades") {
+            show_cascades = !show_cascades;
+            println!(
+                "Shadow cascades: {}",
+                if show_cascades { "shown" } else { "hidden" }
+            );
+        }
+        if input.pressed("toggle_blinn") {
+            blinn = !blinn;
+            println!("Specular: {}", if blinn { "Blinn-Phong" } else { "Phong" });
+        }
+        if let (true, Some((path, assets))) = (input.pressed("save_scene"), &scene_assets) {
+            let path = Path::new(path);
+            let saved = path.with_extension(format!(
+                "saved.{}",
+                path.extension()
+                    .and_then(|ext| ext.to_str())
+                    .unwrap_or("ron")
+            ));
+            match assets.to_file(&scene).save(&saved) {
+                Ok(()) => println!("Saved scene to {}", saved.display()),
+                Err(err) => println!("ERROR::SCENE::SAVE_FAILED\n{}", err),
+            }
+        }
+        if input.pressed("pick") && !scene.is_empty() {
+            let camera = cameras.active();
+            let ray = Ray::from_cursor(
+                pick_cursor(&input, &cameras, &main.surface),
+                main.surface.window_size,
+                &camera.view_matrix(),
+                &camera.projection(main.surface.aspect()),
+            );
+            match SceneBvh::build(&scene).pick(&scene, &ray) {
+                Some(hit) => {
+                    let point = hit.point;
+                    print!(
+                        "Picked '{}' at ({:.3}, {:.3}, {:.3})",
+                        scene.path(hit.node),
+                        point.x,
+                        point.y,
+                        point.z
+                    );
+                    match hit.triangle {
+                        Some(triangle) => {
+                            let weights = triangle.barycentric;
+                            println!(
+                                ", mesh {} triangle {} barycentric ({:.3}, {:.3}, {:.3})",
+                                triangle.mesh, triangle.triangle, weights.x, weights.y, weights.z
+                            )
+                        }
+                        None => println!(),
+                    }
+                }
+                None => println!("Picked nothing"),
+            }
+        }
+        if input.pressed("pick_id") && !scene.is_empty() {
+            let camera = cameras.active();
+            let picked = unsafe {
+                picker.pick(
+                    &scene,
+                    &camera.view_matrix(),
+                    &camera.projection(main.surface.aspect()),
+                    &main.surface,
+                    pick_cursor(&input, &cameras, &main.surface),
+                )
+            };
+            match picked {
+                Some(node) => println!("Picked '{}' by ID", scene.path(node)),
+                None => println!("Picked nothing by ID"),
+            }
+        }
 
-            gl::BindVertexArray(VAO);
-            gl::DrawElements(gl::TRIANGLES, 6, gl::UNSIGNED_INT, ptr::null());
+        // shadow maps are drawn first, they bind their own framebuffer
+        let camera = cameras.active();
+        let view = camera.view_matrix();
+        let projection = camera.projection(main.surface.aspect());
+        let lights = scene.lights();
+        let (cast, point_cast) = match &shadows {
+            Some(shadows) if shadows_on => unsafe {
+                (
+                    shadows.render(&scene, &lights, &view, &projection),
+                    shadows.render_points(&scene, &lights, camera.position()),
+                )
+            },
+            _ => (Vec::new(), Vec::new()),
+        };
+
+        msaa.bind();
+        unsafe {
+            let [r, g, b, a] = config.clear_color;
+            gl::ClearColor(r, g, b, a);
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+
+            if !scene.is_empty() {
+                light_buffer.update(&LightSet {
+                    view_position: camera.position(),
+                    // the third row of a view matrix is the camera's backward axis
+                    view_direction: -view.row(2).truncate(),
+                    blinn,
+                    lights,
+                    shadows: cast,
+                    point_shadows: point_cast,
+                    shadow_settings: shadows
+                        .as_ref()
+                        .map(|shadows| shadows.settings)
+                        .unwrap_or_default(),
+                    show_cascades,
+                    ..LightSet::default()
+                });
+                if let Some(shadows) = &shadows {
+                    shadows.bind();
+                }
+                let stats = scene.draw(&view, &projection);
+                if stats != cull_stats {
+                    println!("Drawn {}, culled {}", stats.visible, stats.culled);
+                    cull_stats = stats;
+                }
+            } else {
+                // let timeValue = timer.time() as f32;
+                // let visibleOffset = timeValue.sin() / 2.0;
+                // let mut transform: Matrix4<f32> = Matrix4::identity();
+                // let time_cos = (timer.time().cos() / 2.0) as f32;
+                // let time_sin = (timer.time().sin() / 2.0) as f32;
+                // transform =
+                //     transform * Matrix4::<f32>::from_translation(vec3(time_sin, time_cos, time_sin));
+                // transform = transform * Matrix4::<f32>::from_angle_z(Rad(timer.time() as f32));
+
+                quad.shader.useProgram();
+                quad.apply();
+
+                let angle = previous_angle + (quad_angle - previous_angle) * timer.alpha();
+                let model: Matrix4<f32> =
+                    Matrix4::from_angle_y(Rad(angle)) * Matrix4::from_angle_x(Deg(-55.));
+                let view: Matrix4<f32> = camera.view_matrix();
+                let modelLoc = gl::GetUniformLocation(quad.shader.ID, c_str!("model").as_ptr());
+                let viewLoc = gl::GetUniformLocation(quad.shader.ID, c_str!("view").as_ptr());
+
+                gl::UniformMatrix4fv(modelLoc, 1, gl::FALSE, model.as_ptr());
+                gl::UniformMatrix4fv(viewLoc, 1, gl::FALSE, &view[0][0]);
+
+                quad.shader.setMat4(c_str!("projection"), &projection);
+
+                gl::BindVertexArray(VAO);
+                gl::DrawElements(gl::TRIANGLES, 6, gl::UNSIGNED_INT, ptr::null());
+            }
+        }
+        msaa.resolve();
+        if recorder.is_some() || replayed.is_some() {
+            let (width, height) = main.surface.framebuffer_size;
+            let hash = replay::frame_hash(msaa.read_framebuffer(), width, height);
+            if let Some(recorder) = &mut recorder {
+                let delta = timer.raw_delta();
+                if let Err(err) = recorder.record_frame(delta, &recorded_events, &gamepad, hash) {
+                    println!("ERROR::REPLAY::RECORD_FAILED\n{}", err);
+                }
+            }
+            if let Some(frame) = &replayed {
+                if frame.hash != hash {
+                    mismatched_frames += 1;
+                    println!(
+                        "ERROR::REPLAY::FRAME_MISMATCH frame {}: expected {:016x}, got {:016x}",
+                        timer.stats().frame_count - 1,
+                        frame.hash,
+                        hash
+                    );
+                }
+            }
+        }
+        main.window.swap_buffers();
+
+        if let Some((id, vao)) = inspector {
+            let app = windows.make_current(id);
+            for event in app.flush_events() {
+                app.surface.handle_event(&event);
+                if let glfw::WindowEvent::Key(Key::Escape, _, Action::Press, _) = event {
+                    app.window.set_should_close(true)
+                }
+            }
+            if app.window.should_close() {
+                unsafe { gl::DeleteVertexArrays(1, &vao) };
+                windows.close(id);
+                inspector = None;
+            } else {
+                unsafe { draw_inspector(&quad, vao, &app.surface) };
+                app.window.swap_buffers();
+            }
         }
 
-        window.swap_buffers();
         glfw.poll_events();
+        timer.end_frame();
     }
     unsafe {
         gl::DeleteVertexArrays(1, &VAO);
         gl::DeleteBuffers(1, &VBO);
         gl::DeleteBuffers(1, &EBO);
     }
+
+    if let Some(recorder) = recorder {
+        let frames = recorder.frames();
+        match recorder.finish() {
+            Ok(()) => println!("Recorded {} frames", frames),
+            Err(err) => println!("ERROR::REPLAY::RECORD_FAILED\n{}", err),
+        }
+    }
+    if let Some(replay) = &replay {
+        println!(
+            "Replayed {} of {} frames, {} mismatched",
+            timer.stats().frame_count,
+            replay.len(),
+            mismatched_frames
+        );
+        if mismatched_frames > 0 {
+            std::process::exit(1);
+        }
+    }
+}
+
+/// the fly camera and the orbit camera, only one of them is driven at a time
+struct Cameras {
+    fly: Camera,
+    mouse_look: MouseLook,
+    orbit: OrbitCamera,
+    orbiting: bool,
+    /// the cursor was released by holding `free_cursor` and is captured again when let go
+    peeking: bool,
+}
+
+impl Cameras {
+    fn active(&self) -> &dyn CameraView {
+        if self.orbiting {
+            &self.orbit
+        } else {
+            &self.fly
+        }
+    }
+}
+
+/// Cursor position to pick at; a captured cursor is hidden, so aim with the middle of the
+/// window instead.
+fn pick_cursor(input: &Input, cameras: &Cameras, surface: &Surface) -> (f64, f64) {
+    let (width, height) = surface.window_size;
+    match input.cursor() {
+        Some(cursor) if !cameras.mouse_look.is_captured() => cursor,
+        _ => (width as f64 / 2.0, height as f64 / 2.0),
+    }
 }
 
-fn process_events(window: &mut glfw::Window, events: &Receiver<(f64, glfw::WindowEvent)>) {
-    for (_, event) in glfw::flush_messages(events) {
+/// returns true when the framebuffer was resized
+fn process_events(
+    events: Vec<glfw::WindowEvent>,
+    surface: &mut Surface,
+    input: &mut Input,
+    cameras: &mut Cameras,
+) -> bool {
+    let mut resized = false;
+    for event in events {
+        resized |= surface.handle_event(&event);
+        input.handle_event(&event);
         match event {
             glfw::WindowEvent::FramebufferSize(width, height) => {
                 // make sure the viewport matches the new window dimensions; note that width and
                 // height will be significantly larger than specified on retina displays.
                 unsafe { gl::Viewport(0, 0, width, height) }
             }
-            glfw::WindowEvent::Key(Key::Escape, _, Action::Press, _) => {
-                window.set_should_close(true)
+            // the orbit camera works on raw drags rather than actions
+            glfw::WindowEvent::CursorPos(..)
+            | glfw::WindowEvent::MouseButton(..)
+            | glfw::WindowEvent::Scroll(..)
+                if cameras.orbiting =>
+            {
+                cameras.orbit.handle_event(&event, surface.window_size)
             }
             _ => {}
         }
     }
+    resized
+}
+
+/// one-shot actions, after `input.update()`
+fn process_actions(
+    glfw: &mut glfw::Glfw,
+    window: &mut glfw::Window,
+    input: &mut Input,
+    display: &mut DisplayState,
+    msaa: &mut MsaaTarget,
+    cameras: &mut Cameras,
+) {
+    if input.pressed("quit") {
+        window.set_should_close(true)
+    }
+    if input.pressed("toggle_fullscreen") {
+        display.toggle_fullscreen(glfw, window)
+    }
+    if input.pressed("toggle_vsync") {
+        display.toggle_vsync(glfw)
+    }
+    if input.pressed("cycle_msaa") {
+        let samples = (msaa.samples() * 2).max(2);
+        // past GL_MAX_SAMPLES wraps around to no MSAA
+        msaa.set_samples(if samples > msaa.max_samples() {
+            0
+        } else {
+            samples
+        });
+        println!("MSAA: {}x", msaa.samples());
+    }
+    if input.pressed("toggle_orbit") {
+        cameras.orbiting = !cameras.orbiting;
+        // the orbit camera is driven by dragging, so it needs a visible cursor
+        let capture = !cameras.orbiting;
+        cameras.mouse_look.set_captured(window, capture);
+        input.reset_cursor();
+    }
+    if input.pressed("toggle_cursor") && !cameras.orbiting {
+        cameras.mouse_look.toggle(window);
+        input.reset_cursor();
+    }
+    if input.pressed("free_cursor") && !cameras.orbiting && cameras.mouse_look.is_captured() {
+        cameras.mouse_look.set_captured(window, false);
+        cameras.peeking = true;
+    }
+    if input.released("free_cursor") && cameras.peeking {
+        cameras.peeking = false;
+        if !cameras.orbiting {
+            cameras.mouse_look.set_captured(window, true);
+            input.reset_cursor();
+        }
+    }
+}
+
+/// continuous fly camera movement from the movement and look axes, keyboard/mouse or gamepad
+fn process_input(input: &Input, cameras: &mut Cameras, delta_time: f32) {
+    let camera = &mut cameras.fly;
+    let speed = if input.held("boost") { 4.0 } else { 1.0 };
+    let axes = [
+        ("move_forward", CameraMovement::Forward),
+        ("move_right", CameraMovement::Right),
+        ("move_up", CameraMovement::Up),
+    ];
+    for (axis, movement) in axes.iter() {
+        // a negative axis value moves the other way
+        let amount = input.axis(axis).clamp(-1.0, 1.0);
+        if amount != 0.0 {
+            camera.process_keyboard(*movement, amount * speed * delta_time);
+        }
+    }
+
+    // mouse look gives offsets per frame, stick turning a rate
+    let (mut xoffset, mut yoffset) = input.axis2("turn_x", "turn_y");
+    xoffset *= delta_time;
+    yoffset *= delta_time;
+    if cameras.mouse_look.is_captured() {
+        let (look_x, look_y) = input.axis2("look_x", "look_y");
+        xoffset += look_x;
+        yoffset += look_y;
+    }
+    if xoffset != 0.0 || yoffset != 0.0 {
+        camera.process_mouse_movement(xoffset, yoffset);
+    }
+    let zoom = input.axis("zoom");
+    if zoom != 0.0 {
+        camera.process_mouse_scroll(zoom);
+    }
+}
+
+/// vertex array for the quad in the current context, reusing the shared buffers
+unsafe fn quad_vao(vbo: GLuint, ebo: GLuint) -> GLuint {
+    let mut vao = 0;
+    gl::GenVertexArrays(1, &mut vao);
+    gl::BindVertexArray(vao);
+    gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
+    gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, ebo);
+
+    let stride = 5 * mem::size_of::<GLfloat>() as GLsizei;
+    gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, stride, ptr::null());
+    gl::EnableVertexAttribArray(0);
+    gl::VertexAttribPointer(
+        1,
+        2,
+        gl::FLOAT,
+        gl::FALSE,
+        stride,
+        (3 * mem::size_of::<GLfloat>()) as *const c_void,
+    );
+    gl::EnableVertexAttribArray(1);
+    vao
+}
+
+/// the inspector shows the textured quad flat, without perspective
+unsafe fn draw_inspector(quad: &Material, vao: GLuint, surface: &Surface) {
+    gl::Viewport(0, 0, surface.framebuffer_size.0, surface.framebuffer_size.1);
+    gl::ClearColor(0.1, 0.1, 0.1, 1.0);
+    gl::Clear(gl::COLOR_BUFFER_BIT);
+
+    quad.shader.useProgram();
+    quad.apply();
+    let identity: Matrix4<f32> = Matrix4::identity();
+    quad.shader.setMat4(c_str!("model"), &identity);
+    quad.shader.setMat4(c_str!("view"), &identity);
+    quad.shader.setMat4(c_str!("projection"), &identity);
+
+    gl::BindVertexArray(vao);
+    gl::DrawElements(gl::TRIANGLES, 6, gl::UNSIGNED_INT, ptr::null());
 }
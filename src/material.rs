@@ -0,0 +1,335 @@
+use std::ffi::{CStr, CString};
+use std::rc::Rc;
+
+use gl::types::GLenum;
+use serde::{Deserialize, Serialize};
+
+use crate::shader::{ActiveUniform, Shader};
+use crate::texture::Texture;
+
+/// Value of a plain uniform. Written without a tag in scene files, so `1` is an int and `1.0`
+/// a float.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Uniform {
+    Bool(bool),
+    Int(i32),
+    Float(f32),
+    Vec2([f32; 2]),
+    Vec3([f32; 3]),
+    Vec4([f32; 4]),
+}
+
+impl Uniform {
+    /// set on `shader`, which must be in use
+    pub unsafe fn apply(&self, shader: &Shader, name: &CStr) {
+        match *self {
+            Uniform::Bool(value) => shader.setBool(name, value),
+            Uniform::Int(value) => shader.setInt(name, value),
+            Uniform::Float(value) => shader.setFloat(name, value),
+            Uniform::Vec2([x, y]) => shader.setVec2(name, x, y),
+            Uniform::Vec3([x, y, z]) => shader.setVec3(name, x, y, z),
+            Uniform::Vec4([x, y, z, w]) => shader.setVec4(name, x, y, z, w),
+        }
+    }
+
+    /// GLSL type name, for messages
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Uniform::Bool(_) => "bool",
+            Uniform::Int(_) => "int",
+            Uniform::Float(_) => "float",
+            Uniform::Vec2(_) => "vec2",
+            Uniform::Vec3(_) => "vec3",
+            Uniform::Vec4(_) => "vec4",
+        }
+    }
+
+    /// true when the `glUniform*` call `apply` makes is valid for a uniform of GL type `kind`
+    fn fits(&self, kind: GLenum) -> bool {
+        match self {
+            // glUniform1i sets bools, ints and sampler units alike
+            Uniform::Bool(_) | Uniform::Int(_) => {
+                kind == gl::BOOL || kind == gl::INT || is_sampler(kind)
+            }
+            Uniform::Float(_) => kind == gl::FLOAT,
+            Uniform::Vec2(_) => kind == gl::FLOAT_VEC2,
+            Uniform::Vec3(_) => kind == gl::FLOAT_VEC3,
+            Uniform::Vec4(_) => kind == gl::FLOAT_VEC4,
+        }
+    }
+}
+
+fn is_sampler(kind: GLenum) -> bool {
+    matches!(
+        kind,
+        gl::SAMPLER_2D
+            | gl::SAMPLER_3D
+            | gl::SAMPLER_CUBE
+            | gl::SAMPLER_2D_ARRAY
+            | gl::SAMPLER_2D_SHADOW
+            | gl::SAMPLER_CUBE_SHADOW
+            | gl::SAMPLER_2D_ARRAY_SHADOW
+    )
+}
+
+/// GLSL name of the GL types materials deal with, for messages
+fn glsl_type(kind: GLenum) -> String {
+    match kind {
+        gl::BOOL => "bool".to_string(),
+        gl::INT => "int".to_string(),
+        gl::UNSIGNED_INT => "uint".to_string(),
+        gl::FLOAT => "float".to_string(),
+        gl::FLOAT_VEC2 => "vec2".to_string(),
+        gl::FLOAT_VEC3 => "vec3".to_string(),
+        gl::FLOAT_VEC4 => "vec4".to_string(),
+        gl::FLOAT_MAT3 => "mat3".to_string(),
+        gl::FLOAT_MAT4 => "mat4".to_string(),
+        gl::SAMPLER_2D => "sampler2D".to_string(),
+        gl::SAMPLER_CUBE => "samplerCube".to_string(),
+        kind if is_sampler(kind) => "sampler".to_string(),
+        kind => format!("type 0x{:x}", kind),
+    }
+}
+
+/// How a shader is set up for drawing: the shader, named parameter values and textures for
+/// its samplers. Texture slots get units 0, 1, ... in the order they were added, and the
+/// sampler uniforms are pointed at them when the material is applied, so nothing has to be
+/// set by hand with `setInt`.
+///
+/// Materials are shared between renderables through an `Rc`; the scene only re-applies one
+/// when it changes between draws.
+#[derive(Clone)]
+pub struct Material {
+    pub shader: Rc<Shader>,
+    params: Vec<(CString, Uniform)>,
+    textures: Vec<(CString, Rc<Texture>)>,
+}
+
+impl Material {
+    pub fn new(shader: Rc<Shader>) -> Material {
+        Material {
+            shader,
+            params: Vec::new(),
+            textures: Vec::new(),
+        }
+    }
+
+    /// builder style `set`
+    pub fn with(mut self, name: &str, value: Uniform) -> Material {
+        self.set(name, value);
+        self
+    }
+
+    /// builder style `set_texture`
+    pub fn with_texture(mut self, sampler: &str, texture: Rc<Texture>) -> Material {
+        self.set_texture(sampler, texture);
+        self
+    }
+
+    /// Set parameter `name`, replacing its previous value.
+    /// ------------------------------------------------------------------------
+    pub fn set(&mut self, name: &str, value: Uniform) {
+        match self
+            .params
+            .iter_mut()
+            .find(|(param, _)| param.to_bytes() == name.as_bytes())
+        {
+            Some((_, current)) => *current = value,
+            None => self.params.push((uniform_name(name), value)),
+        }
+    }
+
+    #[cfg(test)]
+    pub fn param(&self, name: &str) -> Option<Uniform> {
+        self.params
+            .iter()
+            .find(|(param, _)| param.to_bytes() == name.as_bytes())
+            .map(|(_, value)| *value)
+    }
+
+    /// parameters in the order they were first set
+    #[cfg(test)]
+    pub fn params(&self) -> impl Iterator<Item = (&str, Uniform)> {
+        self.params
+            .iter()
+            .map(|(name, value)| (name.to_str().unwrap_or_default(), *value))
+    }
+
+    /// Put `texture` in the slot for `sampler` and return its texture unit. Replacing the
+    /// texture of a slot keeps its unit.
+    /// ------------------------------------------------------------------------
+    pub fn set_texture(&mut self, sampler: &str, texture: Rc<Texture>) -> u32 {
+        match self.texture_unit(sampler) {
+            Some(unit) => {
+                self.textures[unit as usize].1 = texture;
+                unit
+            }
+            None => {
+                self.textures.push((uniform_name(sampler), texture));
+                self.textures.len() as u32 - 1
+            }
+        }
+    }
+
+    pub fn texture_unit(&self, sampler: &str) -> Option<u32> {
+        self.textures
+            .iter()
+            .position(|(slot, _)| slot.to_bytes() == sampler.as_bytes())
+            .map(|unit| unit as u32)
+    }
+
+    /// Bind the textures to their units, point the samplers at them and set the parameters.
+    /// `shader` must be in use.
+    /// ------------------------------------------------------------------------
+    pub unsafe fn apply(&self) {
+        for (unit, (sampler, texture)) in self.textures.iter().enumerate() {
+            texture.bind(unit as u32);
+            self.shader.setInt(sampler, unit as i32);
+        }
+        self.apply_params(&self.shader);
+    }
+
+    /// Set just the parameters, on any shader in use, e.g. one drawing the same geometry
+    /// for another pass.
+    /// ------------------------------------------------------------------------
+    pub unsafe fn apply_params(&self, shader: &Shader) {
+        for (name, value) in &self.params {
+            value.apply(shader, name);
+        }
+    }
+
+    /// Problems with the material for its shader: parameters and texture slots the shader
+    /// doesn't have (or optimised away), and values of the wrong type. Empty when it's fine.
+    /// ------------------------------------------------------------------------
+    pub unsafe fn validate(&self) -> Vec<String> {
+        let samplers = self.textures.iter().map(|(sampler, _)| sampler.as_c_str());
+        problems(&self.params, samplers, &self.shader.active_uniforms())
+    }
+}
+
+/// Uniform names can't hold a NUL; cut one off rather than failing, the name won't be found.
+fn uniform_name(name: &str) -> CString {
+    let end = name.find('\0').unwrap_or(name.len());
+    CString::new(&name[..end]).unwrap_or_default()
+}
+
+/// `Material::validate` without the GL query.
+fn problems<'a>(
+    params: &[(CString, Uniform)],
+    samplers: impl Iterator<Item = &'a CStr>,
+    active: &[ActiveUniform],
+) -> Vec<String> {
+    // arrays are reported by their first element, `lights[0]`, and set by either name
+    let find = |name: &CStr| {
+        let name = name.to_string_lossy();
+        active.iter().find(|uniform| {
+            uniform.name == name || uniform.name.strip_suffix("[0]") == Some(name.as_ref())
+        })
+    };
+    let mut problems = Vec::new();
+    for (name, value) in params {
+        match find(name) {
+            None => problems.push(format!(
+                "'{}' is not an active uniform",
+                name.to_string_lossy()
+            )),
+            Some(uniform) if !value.fits(uniform.kind) => problems.push(format!(
+                "'{}': the shader has {} but the material sets {}",
+                uniform.name,
+                glsl_type(uniform.kind),
+                value.type_name()
+            )),
+            Some(_) => {}
+        }
+    }
+    for sampler in samplers {
+        match find(sampler) {
+            None => problems.push(format!(
+                "texture slot '{}' is not an active uniform",
+                sampler.to_string_lossy()
+            )),
+            Some(uniform) if !is_sampler(uniform.kind) => problems.push(format!(
+                "texture slot '{}' is a {} in the shader, not a sampler",
+                uniform.name,
+                glsl_type(uniform.kind)
+            )),
+            Some(_) => {}
+        }
+    }
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn active(name: &str, kind: GLenum) -> ActiveUniform {
+        ActiveUniform {
+            name: name.to_string(),
+            kind,
+            size: 1,
+        }
+    }
+
+    #[test]
+    fn params_replace_and_keep_their_order() {
+        let mut material = Material::new(Rc::new(Shader { ID: 1 }))
+            .with("shininess", Uniform::Float(32.0))
+            .with("diffuse_color", Uniform::Vec3([1.0, 0.5, 0.25]));
+        material.set("shininess", Uniform::Float(8.0));
+        assert_eq!(material.param("shininess"), Some(Uniform::Float(8.0)));
+        assert_eq!(material.param("missing"), None);
+        let names: Vec<&str> = material.params().map(|(name, _)| name).collect();
+        assert_eq!(names, ["shininess", "diffuse_color"]);
+        assert_eq!(material.texture_unit("texture_diffuse1"), None);
+    }
+
+    #[test]
+    fn validation_names_the_uniform() {
+        let shader = [
+            active("diffuse_color", gl::FLOAT_VEC3),
+            active("shininess", gl::FLOAT),
+            active("lit", gl::BOOL),
+            active("lights[0]", gl::FLOAT_VEC4),
+            active("texture_diffuse1", gl::SAMPLER_2D),
+            active("model", gl::FLOAT_MAT4),
+        ];
+        let params = |list: &[(&str, Uniform)]| -> Vec<(CString, Uniform)> {
+            list.iter()
+                .map(|(name, value)| (uniform_name(name), *value))
+                .collect()
+        };
+        let good = params(&[
+            ("diffuse_color", Uniform::Vec3([1.0; 3])),
+            ("shininess", Uniform::Float(32.0)),
+            ("lit", Uniform::Bool(true)),
+            ("lights", Uniform::Vec4([0.0; 4])),
+            ("lights[0]", Uniform::Vec4([0.0; 4])),
+            // a sampler can still be pointed at a unit by hand
+            ("texture_diffuse1", Uniform::Int(3)),
+        ]);
+        let diffuse = uniform_name("texture_diffuse1");
+        assert!(problems(&good, std::iter::once(diffuse.as_c_str()), &shader).is_empty());
+
+        let bad = params(&[
+            ("diffuse_colour", Uniform::Vec3([1.0; 3])),
+            ("shininess", Uniform::Int(32)),
+        ]);
+        let model = uniform_name("model");
+        let specular = uniform_name("texture_specular1");
+        assert_eq!(
+            problems(
+                &bad,
+                [model.as_c_str(), specular.as_c_str()].into_iter(),
+                &shader
+            ),
+            [
+                "'diffuse_colour' is not an active uniform",
+                "'shininess': the shader has float but the material sets int",
+                "texture slot 'model' is a mat4 in the shader, not a sampler",
+                "texture slot 'texture_specular1' is not an active uniform",
+            ]
+        );
+    }
+}
@@ -0,0 +1,1215 @@
+use std::collections::HashMap;
+use std::f32::consts::PI;
+use std::mem;
+use std::os::raw::c_void;
+use std::ptr;
+
+use cgmath::prelude::*;
+use cgmath::{vec2, vec3, vec4, Matrix3, Matrix4, Point3, Vector2, Vector3, Vector4};
+
+use gl::types::*;
+
+use crate::bounds::{Aabb, BoundingSphere};
+use crate::bvh::MeshBvh;
+use crate::ray::{Ray, RayHit};
+
+/// Vertex layout shared by every mesh: position, normal, tangent, texture coordinates and a
+/// linear RGBA colour (white unless the file provides one).
+///
+/// `tangent.w` is the handedness of the tangent frame, the bitangent is
+/// `cross(normal, tangent.xyz) * tangent.w`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Vertex {
+    pub position: Vector3<f32>,
+    pub normal: Vector3<f32>,
+    pub tangent: Vector4<f32>,
+    pub uv: Vector2<f32>,
+    pub color: Vector4<f32>,
+}
+
+impl Default for Vertex {
+    fn default() -> Vertex {
+        Vertex {
+            position: Vector3::zero(),
+            normal: Vector3::unit_y(),
+            tangent: vec4(1.0, 0.0, 0.0, 1.0),
+            uv: Vector2::zero(),
+            color: vec4(1.0, 1.0, 1.0, 1.0),
+        }
+    }
+}
+
+/// Indexed triangle list on the CPU side. Triangles are wound counter-clockwise when seen from
+/// the side the normals point to.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MeshData {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+}
+
+impl MeshData {
+    pub fn vertex_count(&self) -> usize {
+        self.vertices.len()
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    pub fn triangles(&self) -> impl Iterator<Item = [u32; 3]> + '_ {
+        self.indices
+            .chunks_exact(3)
+            .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+    }
+
+    /// Append another mesh, offsetting its indices.
+    /// ------------------------------------------------------------------------
+    pub fn append(&mut self, other: &MeshData) {
+        let base = self.vertices.len() as u32;
+        self.vertices.extend_from_slice(&other.vertices);
+        self.indices
+            .extend(other.indices.iter().map(|index| index + base));
+    }
+
+    /// Move the mesh by an affine `transform`. Normals use the inverse transpose, and a
+    /// mirroring transform reverses the winding and the tangent handedness so triangles keep
+    /// facing outwards and normal maps keep their bumps.
+    /// ------------------------------------------------------------------------
+    pub fn transform(&mut self, transform: &Matrix4<f32>) {
+        let linear = Matrix3::from_cols(
+            transform.x.truncate(),
+            transform.y.truncate(),
+            transform.z.truncate(),
+        );
+        let normal_matrix = linear
+            .invert()
+            .map(|inverse| inverse.transpose())
+            .unwrap_or(linear);
+        // a mirror flips the handedness of the tangent frames along with the winding
+        let mirrored = linear.determinant() < 0.0;
+        let handedness = if mirrored { -1.0 } else { 1.0 };
+        let normalize = |v: Vector3<f32>| {
+            if v.magnitude2() > 0.0 {
+                v.normalize()
+            } else {
+                v
+            }
+        };
+        for vertex in &mut self.vertices {
+            vertex.position = transform
+                .transform_point(Point3::from_vec(vertex.position))
+                .to_vec();
+            vertex.normal = normalize(normal_matrix * vertex.normal);
+            let tangent = normalize(linear * vertex.tangent.truncate());
+            vertex.tangent = tangent.extend(vertex.tangent.w * handedness);
+        }
+        if mirrored {
+            for triangle in self.indices.chunks_exact_mut(3) {
+                triangle.swap(1, 2);
+            }
+        }
+    }
+
+    /// Give every triangle its own three vertices with the face normal, for meshes that come
+    /// without normals. Indices become `0..3 * triangle_count`.
+    /// ------------------------------------------------------------------------
+    pub fn compute_flat_normals(&mut self) {
+        let mut vertices = Vec::with_capacity(self.indices.len());
+        for [a, b, c] in self.triangles() {
+            let mut corners = [
+                self.vertices[a as usize],
+                self.vertices[b as usize],
+                self.vertices[c as usize],
+            ];
+            let normal = (corners[1].position - corners[0].position)
+                .cross(corners[2].position - corners[0].position);
+            let normal = if normal.magnitude2() > 1e-20 {
+                normal.normalize()
+            } else {
+                Vector3::unit_y()
+            };
+            for corner in corners.iter_mut() {
+                corner.normal = normal;
+            }
+            vertices.extend_from_slice(&corners);
+        }
+        self.indices = (0..vertices.len() as u32).collect();
+        self.vertices = vertices;
+    }
+
+    /// Angle weighted vertex normals: every triangle adds its face normal to its corners,
+    /// scaled by the corner angle so the result doesn't depend on how polygons were split into
+    /// triangles. Vertices of degenerate triangles only keep their old normal if nothing else
+    /// contributes.
+    /// ------------------------------------------------------------------------
+    pub fn compute_smooth_normals(&mut self) {
+        let mut normals = vec![Vector3::zero(); self.vertices.len()];
+        for triangle in self.triangles() {
+            let face = self.face_normal(triangle);
+            if face.magnitude2() < 1e-20 {
+                continue;
+            }
+            let face = face.normalize();
+            for corner in 0..3 {
+                let angle = self.corner_angle(triangle, corner);
+                normals[triangle[corner] as usize] += face * angle;
+            }
+        }
+        for (vertex, normal) in self.vertices.iter_mut().zip(normals) {
+            if normal.magnitude2() > 1e-20 {
+                vertex.normal = normal.normalize();
+            }
+        }
+    }
+
+    /// MikkTSpace tangent frames, so normal maps baked by other tools line up: per corner
+    /// tangents from the UV gradient, projected onto the vertex normal's plane and weighted by
+    /// the corner angle in that plane. A vertex shared by triangles with mirrored UVs is split
+    /// into one copy per handedness.
+    /// Vertices without usable UVs get an arbitrary tangent perpendicular to the normal.
+    ///
+    /// `tangent.w` is +1 where V increases along `cross(normal, tangent)`.
+    /// ------------------------------------------------------------------------
+    pub fn compute_tangents(&mut self) {
+        // accumulated tangent per vertex and handedness, [positive, negative]
+        let mut sums = vec![[Vector3::zero(); 2]; self.vertices.len()];
+        let mut used = vec![[false; 2]; self.vertices.len()];
+        let mut orientations = Vec::with_capacity(self.triangle_count());
+        for triangle in self.triangles() {
+            let [v0, v1, v2] = triangle.map(|index| &self.vertices[index as usize]);
+            let (edge1, edge2) = (v1.position - v0.position, v2.position - v0.position);
+            let (duv1, duv2) = (v1.uv - v0.uv, v2.uv - v0.uv);
+            let det = duv1.x * duv2.y - duv2.x * duv1.y;
+            let side = if det < 0.0 { 1 } else { 0 };
+            orientations.push(side);
+            if det.abs() < 1e-12 {
+                continue;
+            }
+            // MikkTSpace normalises the per face tangent before weighting
+            let tangent = (edge1 * duv2.y - edge2 * duv1.y) / det;
+            for corner in 0..3 {
+                let index = triangle[corner] as usize;
+                let normal = self.vertices[index].normal;
+                let projected = tangent - normal * normal.dot(tangent);
+                if projected.magnitude2() < 1e-20 {
+                    continue;
+                }
+                let angle = self.projected_corner_angle(triangle, corner, normal);
+                sums[index][side] += projected.normalize() * angle;
+                used[index][side] = true;
+            }
+        }
+
+        // vertices used with both handednesses get a copy for the mirrored side
+        let mut mirrored = vec![None; self.vertices.len()];
+        for (index, [positive, negative]) in used.iter().enumerate() {
+            if *positive && *negative {
+                mirrored[index] = Some(self.vertices.len() as u32);
+                let copy = self.vertices[index];
+                self.vertices.push(copy);
+                sums.push([Vector3::zero(), sums[index][1]]);
+                sums[index][1] = Vector3::zero();
+            }
+        }
+        for (triangle, side) in self.indices.chunks_exact_mut(3).zip(orientations) {
+            if side == 1 {
+                for index in triangle.iter_mut() {
+                    if let Some(copy) = mirrored[*index as usize] {
+                        *index = copy;
+                    }
+                }
+            }
+        }
+
+        for (vertex, [positive, negative]) in self.vertices.iter_mut().zip(sums) {
+            let normal = vertex.normal;
+            let (tangent, handedness) = if negative.magnitude2() > positive.magnitude2() {
+                (negative, -1.0)
+            } else {
+                (positive, 1.0)
+            };
+            let tangent = tangent - normal * normal.dot(tangent);
+            let tangent = if tangent.magnitude2() > 1e-12 {
+                tangent.normalize()
+            } else {
+                perpendicular(normal)
+            };
+            vertex.tangent = tangent.extend(handedness);
+        }
+    }
+
+    /// Unnormalised normal of a counter-clockwise triangle, its length is twice the area.
+    /// ------------------------------------------------------------------------
+    pub fn face_normal(&self, [a, b, c]: [u32; 3]) -> Vector3<f32> {
+        let (a, b, c) = (
+            self.vertices[a as usize].position,
+            self.vertices[b as usize].position,
+            self.vertices[c as usize].position,
+        );
+        (b - a).cross(c - a)
+    }
+
+    /// interior angle of a triangle at one of its corners (0, 1 or 2), in radians
+    fn corner_angle(&self, triangle: [u32; 3], corner: usize) -> f32 {
+        let at = self.vertices[triangle[corner] as usize].position;
+        let next = self.vertices[triangle[(corner + 1) % 3] as usize].position - at;
+        let previous = self.vertices[triangle[(corner + 2) % 3] as usize].position - at;
+        if next.magnitude2() < 1e-20 || previous.magnitude2() < 1e-20 {
+            return 0.0;
+        }
+        next.normalize()
+            .dot(previous.normalize())
+            .clamp(-1.0, 1.0)
+            .acos()
+    }
+
+    /// Like `corner_angle` with the edges first projected onto the plane of `normal`, the
+    /// weight MikkTSpace gives each triangle's tangent at a vertex.
+    /// ------------------------------------------------------------------------
+    fn projected_corner_angle(
+        &self,
+        triangle: [u32; 3],
+        corner: usize,
+        normal: Vector3<f32>,
+    ) -> f32 {
+        let at = self.vertices[triangle[corner] as usize].position;
+        let flatten = |index: u32| {
+            let edge = self.vertices[index as usize].position - at;
+            let edge = edge - normal * normal.dot(edge);
+            if edge.magnitude2() > 1e-20 {
+                edge.normalize()
+            } else {
+                edge
+            }
+        };
+        let next = flatten(triangle[(corner + 1) % 3]);
+        let previous = flatten(triangle[(corner + 2) % 3]);
+        next.dot(previous).clamp(-1.0, 1.0).acos()
+    }
+
+    pub fn bounds(&self) -> Aabb {
+        Aabb::from_points(
+            self.vertices
+                .iter()
+                .map(|vertex| Point3::from_vec(vertex.position)),
+        )
+    }
+
+    pub fn bounding_sphere(&self) -> BoundingSphere {
+        let points: Vec<Point3<f32>> = self
+            .vertices
+            .iter()
+            .map(|vertex| Point3::from_vec(vertex.position))
+            .collect();
+        BoundingSphere::from_points(&points)
+    }
+
+    /// Flat grid in the XZ plane facing +Y, centred on the origin, with `columns` x `rows`
+    /// quads. UVs run from 0 to 1 across the grid.
+    /// ------------------------------------------------------------------------
+    pub fn grid(width: f32, depth: f32, columns: u32, rows: u32) -> MeshData {
+        let mut mesh = MeshData::default();
+        mesh.push_face(
+            Vector3::zero(),
+            Vector3::unit_x() * width,
+            -Vector3::unit_z() * depth,
+            columns.max(1),
+            rows.max(1),
+        );
+        mesh
+    }
+
+    /// single quad version of `grid`
+    pub fn plane(size: f32) -> MeshData {
+        MeshData::grid(size, size, 1, 1)
+    }
+
+    /// Axis-aligned cube centred on the origin. Every face has its own four vertices so normals
+    /// stay flat, and its own 0..1 UV square.
+    /// ------------------------------------------------------------------------
+    pub fn cube(size: f32) -> MeshData {
+        let half = size / 2.0;
+        // (normal, tangent); the bitangent follows from cross(normal, tangent)
+        let faces = [
+            (Vector3::unit_x(), -Vector3::unit_z()),
+            (-Vector3::unit_x(), Vector3::unit_z()),
+            (Vector3::unit_y(), Vector3::unit_x()),
+            (-Vector3::unit_y(), Vector3::unit_x()),
+            (Vector3::unit_z(), Vector3::unit_x()),
+            (-Vector3::unit_z(), -Vector3::unit_x()),
+        ];
+        let mut mesh = MeshData::default();
+        for (normal, tangent) in faces.iter() {
+            let bitangent = normal.cross(*tangent);
+            mesh.push_face(normal * half, tangent * size, bitangent * size, 1, 1);
+        }
+        mesh
+    }
+
+    /// Latitude/longitude sphere. The seam and the poles get duplicated vertices so UVs wrap
+    /// cleanly; `sectors` slices go around the Y axis, `stacks` from pole to pole.
+    /// ------------------------------------------------------------------------
+    pub fn uv_sphere(radius: f32, sectors: u32, stacks: u32) -> MeshData {
+        let stacks = stacks.max(2);
+        let profile: Vec<ProfilePoint> = (0..=stacks)
+            .map(|stack| {
+                let v = stack as f32 / stacks as f32;
+                let phi = (v - 0.5) * PI;
+                ProfilePoint {
+                    // cos(±PI / 2) comes out slightly negative, clamp so the poles are exactly 0
+                    radius: (radius * phi.cos()).max(0.0),
+                    y: radius * phi.sin(),
+                    normal: (phi.cos(), phi.sin()),
+                    v,
+                }
+            })
+            .collect();
+        let mut mesh = MeshData::default();
+        mesh.push_lathe(&profile, sectors.max(3));
+        mesh
+    }
+
+    /// Sphere made by subdividing an icosahedron, with evenly sized triangles. Each subdivision
+    /// splits every triangle into four. Vertices on the UV seam are duplicated.
+    /// ------------------------------------------------------------------------
+    pub fn icosphere(radius: f32, subdivisions: u32) -> MeshData {
+        let t = (1.0 + 5f32.sqrt()) / 2.0;
+        let mut points: Vec<Vector3<f32>> = [
+            (-1.0, t, 0.0),
+            (1.0, t, 0.0),
+            (-1.0, -t, 0.0),
+            (1.0, -t, 0.0),
+            (0.0, -1.0, t),
+            (0.0, 1.0, t),
+            (0.0, -1.0, -t),
+            (0.0, 1.0, -t),
+            (t, 0.0, -1.0),
+            (t, 0.0, 1.0),
+            (-t, 0.0, -1.0),
+            (-t, 0.0, 1.0),
+        ]
+        .iter()
+        .map(|&(x, y, z)| vec3(x, y, z).normalize())
+        .collect();
+        let mut triangles: Vec<[u32; 3]> = vec![
+            [0, 11, 5],
+            [0, 5, 1],
+            [0, 1, 7],
+            [0, 7, 10],
+            [0, 10, 11],
+            [1, 5, 9],
+            [5, 11, 4],
+            [11, 10, 2],
+            [10, 7, 6],
+            [7, 1, 8],
+            [3, 9, 4],
+            [3, 4, 2],
+            [3, 2, 6],
+            [3, 6, 8],
+            [3, 8, 9],
+            [4, 9, 5],
+            [2, 4, 11],
+            [6, 2, 10],
+            [8, 6, 7],
+            [9, 8, 1],
+        ];
+
+        for _ in 0..subdivisions {
+            // edges are shared by two triangles, so remember each midpoint
+            let mut midpoints: HashMap<(u32, u32), u32> = HashMap::new();
+            let mut midpoint = |a: u32, b: u32| {
+                *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                    let point = (points[a as usize] + points[b as usize]).normalize();
+                    points.push(point);
+                    points.len() as u32 - 1
+                })
+            };
+            triangles = triangles
+                .iter()
+                .flat_map(|&[a, b, c]| {
+                    let (ab, bc, ca) = (midpoint(a, b), midpoint(b, c), midpoint(c, a));
+                    vec![[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+                })
+                .collect();
+        }
+
+        let mut mesh = MeshData {
+            vertices: points
+                .iter()
+                .map(|&normal| sphere_vertex(normal, radius))
+                .collect(),
+            indices: Vec::with_capacity(triangles.len() * 3),
+        };
+        // triangles straddling the seam get copies of their low-u vertices moved past u = 1
+        let is_pole = |vertex: &Vertex| vertex.normal.y.abs() > 1.0 - 1e-6;
+        let mut wrapped: HashMap<u32, u32> = HashMap::new();
+        let mut poles_used = Vec::new();
+        for triangle in triangles.iter_mut() {
+            let (min, max) = triangle
+                .iter()
+                .map(|&index| mesh.vertices[index as usize])
+                .filter(|vertex| !is_pole(vertex))
+                .fold((1.0f32, 0.0f32), |(min, max), vertex| {
+                    (min.min(vertex.uv.x), max.max(vertex.uv.x))
+                });
+            if max - min > 0.5 {
+                for index in triangle.iter_mut() {
+                    let vertex = mesh.vertices[*index as usize];
+                    if vertex.uv.x < 0.5 && !is_pole(&vertex) {
+                        *index = *wrapped.entry(*index).or_insert_with(|| {
+                            mesh.vertices.push(Vertex {
+                                uv: vec2(vertex.uv.x + 1.0, vertex.uv.y),
+                                ..vertex
+                            });
+                            mesh.vertices.len() as u32 - 1
+                        });
+                    }
+                }
+            }
+            // a pole has no longitude of its own; every triangle gets a pole vertex halfway
+            // between its other two
+            for corner in 0..3 {
+                let pole = triangle[corner];
+                if !is_pole(&mesh.vertices[pole as usize]) {
+                    continue;
+                }
+                let u = (mesh.vertices[triangle[(corner + 1) % 3] as usize].uv.x
+                    + mesh.vertices[triangle[(corner + 2) % 3] as usize].uv.x)
+                    / 2.0;
+                if poles_used.contains(&pole) {
+                    let mut vertex = mesh.vertices[pole as usize];
+                    vertex.uv.x = u;
+                    mesh.vertices.push(vertex);
+                    triangle[corner] = mesh.vertices.len() as u32 - 1;
+                } else {
+                    mesh.vertices[pole as usize].uv.x = u;
+                    poles_used.push(pole);
+                }
+            }
+            mesh.indices.extend_from_slice(triangle);
+        }
+        mesh
+    }
+
+    /// Capped cylinder along the Y axis, centred on the origin.
+    /// ------------------------------------------------------------------------
+    pub fn cylinder(radius: f32, height: f32, sectors: u32) -> MeshData {
+        let sectors = sectors.max(3);
+        let half = height / 2.0;
+        let profile = [
+            ProfilePoint {
+                radius,
+                y: -half,
+                normal: (1.0, 0.0),
+                v: 0.0,
+            },
+            ProfilePoint {
+                radius,
+                y: half,
+                normal: (1.0, 0.0),
+                v: 1.0,
+            },
+        ];
+        let mut mesh = MeshData::default();
+        mesh.push_lathe(&profile, sectors);
+        mesh.push_disk(-half, radius, sectors, false);
+        mesh.push_disk(half, radius, sectors, true);
+        mesh
+    }
+
+    /// Cone along the Y axis with its base at `-height / 2` and the tip at `height / 2`.
+    /// ------------------------------------------------------------------------
+    pub fn cone(radius: f32, height: f32, sectors: u32) -> MeshData {
+        let sectors = sectors.max(3);
+        let half = height / 2.0;
+        // the side normal leans up by the slope of the cone
+        let slant = (radius * radius + height * height).sqrt();
+        let normal = (height / slant, radius / slant);
+        let profile = [
+            ProfilePoint {
+                radius,
+                y: -half,
+                normal,
+                v: 0.0,
+            },
+            ProfilePoint {
+                radius: 0.0,
+                y: half,
+                normal,
+                v: 1.0,
+            },
+        ];
+        let mut mesh = MeshData::default();
+        mesh.push_lathe(&profile, sectors);
+        mesh.push_disk(-half, radius, sectors, false);
+        mesh
+    }
+
+    /// Torus around the Y axis. `major_radius` is the distance from the centre to the middle
+    /// of the tube, `minor_radius` the radius of the tube.
+    /// ------------------------------------------------------------------------
+    pub fn torus(
+        major_radius: f32,
+        minor_radius: f32,
+        major_segments: u32,
+        minor_segments: u32,
+    ) -> MeshData {
+        let minor_segments = minor_segments.max(3);
+        let profile: Vec<ProfilePoint> = (0..=minor_segments)
+            .map(|segment| {
+                let v = segment as f32 / minor_segments as f32;
+                let phi = v * 2.0 * PI;
+                ProfilePoint {
+                    radius: major_radius + minor_radius * phi.cos(),
+                    y: minor_radius * phi.sin(),
+                    normal: (phi.cos(), phi.sin()),
+                    v,
+                }
+            })
+            .collect();
+        let mut mesh = MeshData::default();
+        mesh.push_lathe(&profile, major_segments.max(3));
+        mesh
+    }
+
+    /// Cylinder with hemispherical ends along the Y axis. `height` is the length of the
+    /// cylindrical part, the whole capsule is `height + 2 * radius` tall. `rings` is the number
+    /// of stacks in each hemisphere.
+    /// ------------------------------------------------------------------------
+    pub fn capsule(radius: f32, height: f32, sectors: u32, rings: u32) -> MeshData {
+        let rings = rings.max(1);
+        let half = height / 2.0;
+        // v follows the arc length of the outline so the texture isn't stretched on the body
+        let length = PI * radius + height;
+        let mut profile = Vec::with_capacity(2 * rings as usize + 2);
+        for (offset, start) in [(-half, -PI / 2.0), (half, 0.0)].iter() {
+            for ring in 0..=rings {
+                let phi = start + ring as f32 / rings as f32 * PI / 2.0;
+                let y = offset + radius * phi.sin();
+                let arc = radius * (phi + PI / 2.0) + if *offset > 0.0 { height } else { 0.0 };
+                profile.push(ProfilePoint {
+                    radius: (radius * phi.cos()).max(0.0),
+                    y,
+                    normal: (phi.cos(), phi.sin()),
+                    v: if length > 0.0 { arc / length } else { 0.0 },
+                });
+            }
+        }
+        let mut mesh = MeshData::default();
+        mesh.push_lathe(&profile, sectors.max(3));
+        mesh
+    }
+
+    /// Rectangle of `columns` x `rows` quads centred on `center`, spanned by `u_axis` and
+    /// `v_axis` (full lengths). The normal is `cross(u_axis, v_axis)`.
+    fn push_face(
+        &mut self,
+        center: Vector3<f32>,
+        u_axis: Vector3<f32>,
+        v_axis: Vector3<f32>,
+        columns: u32,
+        rows: u32,
+    ) {
+        let base = self.vertices.len() as u32;
+        let normal = u_axis.cross(v_axis).normalize();
+        let tangent = u_axis.normalize().extend(1.0);
+        for row in 0..=rows {
+            let v = row as f32 / rows as f32;
+            for column in 0..=columns {
+                let u = column as f32 / columns as f32;
+                self.vertices.push(Vertex {
+                    position: center + u_axis * (u - 0.5) + v_axis * (v - 0.5),
+                    normal,
+                    tangent,
+                    uv: vec2(u, v),
+                    ..Vertex::default()
+                });
+            }
+        }
+        self.push_quads(base, rows, columns);
+    }
+
+    /// Surface of revolution around the Y axis: every profile point becomes a ring of
+    /// `sectors + 1` vertices (the seam is duplicated). The profile has to run so the surface
+    /// is on its left, i.e. bottom to top on the outside.
+    fn push_lathe(&mut self, profile: &[ProfilePoint], sectors: u32) {
+        let base = self.vertices.len() as u32;
+        for point in profile {
+            for sector in 0..=sectors {
+                let u = sector as f32 / sectors as f32;
+                let (sin, cos) = (u * 2.0 * PI).sin_cos();
+                self.vertices.push(Vertex {
+                    position: vec3(point.radius * sin, point.y, point.radius * cos),
+                    normal: vec3(point.normal.0 * sin, point.normal.1, point.normal.0 * cos)
+                        .normalize(),
+                    tangent: vec4(cos, 0.0, -sin, 1.0),
+                    uv: vec2(u, point.v),
+                    ..Vertex::default()
+                });
+            }
+        }
+
+        let stride = sectors + 1;
+        for (ring, pair) in profile.windows(2).enumerate() {
+            for sector in 0..sectors {
+                let lower = base + ring as u32 * stride + sector;
+                let upper = lower + stride;
+                // rings that shrink to a point (poles, tips) only need one triangle per quad
+                if pair[0].radius > 0.0 {
+                    self.indices
+                        .extend_from_slice(&[lower, lower + 1, upper + 1]);
+                }
+                if pair[1].radius > 0.0 {
+                    self.indices.extend_from_slice(&[lower, upper + 1, upper]);
+                }
+            }
+        }
+    }
+
+    /// Flat cap at height `y` facing up or down, with planar UVs.
+    fn push_disk(&mut self, y: f32, radius: f32, sectors: u32, up: bool) {
+        let base = self.vertices.len() as u32;
+        let (normal, flip) = if up {
+            (Vector3::unit_y(), -1.0)
+        } else {
+            (-Vector3::unit_y(), 1.0)
+        };
+        let tangent = vec4(1.0, 0.0, 0.0, 1.0);
+        self.vertices.push(Vertex {
+            position: vec3(0.0, y, 0.0),
+            normal,
+            tangent,
+            uv: vec2(0.5, 0.5),
+            ..Vertex::default()
+        });
+        for sector in 0..sectors {
+            let (sin, cos) = (sector as f32 / sectors as f32 * 2.0 * PI).sin_cos();
+            self.vertices.push(Vertex {
+                position: vec3(radius * sin, y, radius * cos),
+                normal,
+                tangent,
+                uv: vec2(0.5 + sin / 2.0, 0.5 + flip * cos / 2.0),
+                ..Vertex::default()
+            });
+        }
+        for sector in 0..sectors {
+            let current = base + 1 + sector;
+            let next = base + 1 + (sector + 1) % sectors;
+            if up {
+                self.indices.extend_from_slice(&[base, current, next]);
+            } else {
+                self.indices.extend_from_slice(&[base, next, current]);
+            }
+        }
+    }
+
+    /// two triangles per quad of a `(rows + 1) x (columns + 1)` vertex grid starting at `base`
+    fn push_quads(&mut self, base: u32, rows: u32, columns: u32) {
+        let stride = columns + 1;
+        for row in 0..rows {
+            for column in 0..columns {
+                let lower = base + row * stride + column;
+                let upper = lower + stride;
+                self.indices.extend_from_slice(&[
+                    lower,
+                    lower + 1,
+                    upper + 1,
+                    lower,
+                    upper + 1,
+                    upper,
+                ]);
+            }
+        }
+    }
+}
+
+/// A mesh uploaded to the GPU. The vertex array has the `Vertex` attributes at locations
+/// 0 (position), 1 (normal), 2 (uv), 3 (tangent) and 4 (colour), so any `Shader` declaring
+/// those inputs can draw it.
+#[allow(non_snake_case)]
+pub struct Mesh {
+    pub VAO: GLuint,
+    VBO: GLuint,
+    EBO: GLuint,
+    index_count: GLsizei,
+    /// bounds of the vertices, in the mesh's own space
+    pub bounds: Aabb,
+    /// sphere around the vertices, usually tighter than the one around `bounds`
+    pub sphere: BoundingSphere,
+    /// triangles kept on the CPU for ray picking, see `Mesh::with_bvh`
+    pub bvh: Option<MeshBvh>,
+}
+
+impl Mesh {
+    /// Upload vertices and indices; needs a current GL context.
+    /// ------------------------------------------------------------------------
+    #[allow(non_snake_case)]
+    pub fn new(data: &MeshData) -> Mesh {
+        let (mut VAO, mut VBO, mut EBO) = (0, 0, 0);
+        unsafe {
+            gl::GenVertexArrays(1, &mut VAO);
+            gl::GenBuffers(1, &mut VBO);
+            gl::GenBuffers(1, &mut EBO);
+
+            gl::BindVertexArray(VAO);
+            gl::BindBuffer(gl::ARRAY_BUFFER, VBO);
+            gl::BufferData(
+                gl::ARRAY_BUFFER,
+                (data.vertices.len() * mem::size_of::<Vertex>()) as GLsizeiptr,
+                data.vertices.as_ptr() as *const c_void,
+                gl::STATIC_DRAW,
+            );
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, EBO);
+            gl::BufferData(
+                gl::ELEMENT_ARRAY_BUFFER,
+                (data.indices.len() * mem::size_of::<u32>()) as GLsizeiptr,
+                data.indices.as_ptr() as *const c_void,
+                gl::STATIC_DRAW,
+            );
+
+            let stride = mem::size_of::<Vertex>() as GLsizei;
+            let attributes = [
+                (0, 3, offset_of!(Vertex, position)),
+                (1, 3, offset_of!(Vertex, normal)),
+                (2, 2, offset_of!(Vertex, uv)),
+                (3, 4, offset_of!(Vertex, tangent)),
+                (4, 4, offset_of!(Vertex, color)),
+            ];
+            for (location, size, offset) in attributes.iter() {
+                gl::EnableVertexAttribArray(*location);
+                gl::VertexAttribPointer(
+                    *location,
+                    *size,
+                    gl::FLOAT,
+                    gl::FALSE,
+                    stride,
+                    *offset as *const c_void,
+                );
+            }
+            gl::BindVertexArray(0);
+        }
+        Mesh {
+            VAO,
+            VBO,
+            EBO,
+            index_count: data.indices.len() as GLsizei,
+            bounds: data.bounds(),
+            sphere: data.bounding_sphere(),
+            bvh: None,
+        }
+    }
+
+    /// Upload like `Mesh::new` and keep a copy of the triangles with a BVH over them, so rays
+    /// can hit single triangles.
+    /// ------------------------------------------------------------------------
+    pub fn with_bvh(data: &MeshData) -> Mesh {
+        Mesh {
+            bvh: Some(MeshBvh::new(data)),
+            ..Mesh::new(data)
+        }
+    }
+
+    /// Closest triangle along `ray` in the mesh's space, or just where it enters the bounds
+    /// when there is no BVH.
+    /// ------------------------------------------------------------------------
+    pub fn raycast(&self, ray: &Ray) -> Option<RayHit> {
+        match &self.bvh {
+            Some(bvh) => bvh.raycast(ray),
+            None => ray
+                .intersect_aabb(&self.bounds)
+                .map(|t| RayHit { t, triangle: None }),
+        }
+    }
+
+    /// draw with whatever shader and textures are bound
+    pub unsafe fn draw(&self) {
+        gl::BindVertexArray(self.VAO);
+        gl::DrawElements(
+            gl::TRIANGLES,
+            self.index_count,
+            gl::UNSIGNED_INT,
+            ptr::null(),
+        );
+        gl::BindVertexArray(0);
+    }
+}
+
+impl Drop for Mesh {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteVertexArrays(1, &self.VAO);
+            gl::DeleteBuffers(1, &self.VBO);
+            gl::DeleteBuffers(1, &self.EBO);
+        }
+    }
+}
+
+/// some unit vector perpendicular to `normal`
+fn perpendicular(normal: Vector3<f32>) -> Vector3<f32> {
+    let axis = if normal.x.abs() < 0.9 {
+        Vector3::unit_x()
+    } else {
+        Vector3::unit_y()
+    };
+    (axis - normal * normal.dot(axis)).normalize()
+}
+
+/// one point of a lathe profile in the (radius, y) half plane
+struct ProfilePoint {
+    radius: f32,
+    y: f32,
+    /// outward normal as (radial, y)
+    normal: (f32, f32),
+    v: f32,
+}
+
+/// vertex on a sphere from its unit normal, with the same UV mapping as `uv_sphere`
+fn sphere_vertex(normal: Vector3<f32>, radius: f32) -> Vertex {
+    let mut u = normal.x.atan2(normal.z) / (2.0 * PI);
+    if u < 0.0 {
+        u += 1.0;
+    }
+    let v = 0.5 + normal.y.clamp(-1.0, 1.0).asin() / PI;
+    // direction of increasing u, undefined right at the poles
+    let tangent = vec3(normal.z, 0.0, -normal.x);
+    let tangent = if tangent.magnitude2() > 1e-12 {
+        tangent.normalize()
+    } else {
+        Vector3::unit_x()
+    };
+    Vertex {
+        position: normal * radius,
+        normal,
+        tangent: tangent.extend(1.0),
+        uv: vec2(u, v),
+        ..Vertex::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// every triangle is wound counter-clockwise around the vertex normals and the tangent
+    /// frames are orthonormal
+    fn assert_consistent(mesh: &MeshData) {
+        assert!(mesh
+            .indices
+            .iter()
+            .all(|&index| (index as usize) < mesh.vertices.len()));
+        for [a, b, c] in mesh.triangles() {
+            let (a, b, c) = (
+                mesh.vertices[a as usize],
+                mesh.vertices[b as usize],
+                mesh.vertices[c as usize],
+            );
+            let face = (b.position - a.position).cross(c.position - a.position);
+            assert!(face.magnitude() > 1e-8, "degenerate triangle");
+            for vertex in [a, b, c].iter() {
+                assert!(face.dot(vertex.normal) > 0.0, "triangle faces inwards");
+            }
+        }
+        for vertex in &mesh.vertices {
+            assert!((vertex.normal.magnitude() - 1.0).abs() < 1e-4);
+            assert!((vertex.tangent.truncate().magnitude() - 1.0).abs() < 1e-4);
+            assert!(vertex.normal.dot(vertex.tangent.truncate()).abs() < 1e-4);
+        }
+    }
+
+    /// normals of a closed shape around the origin point away from it
+    fn assert_outward(mesh: &MeshData) {
+        for vertex in &mesh.vertices {
+            assert!(vertex.position.dot(vertex.normal) > 0.0);
+        }
+    }
+
+    #[test]
+    fn grid() {
+        let mesh = MeshData::grid(2.0, 4.0, 4, 3);
+        assert_eq!(mesh.vertex_count(), 5 * 4);
+        assert_eq!(mesh.triangle_count(), 2 * 4 * 3);
+        assert_consistent(&mesh);
+        assert!(mesh.vertices.iter().all(|v| v.normal == Vector3::unit_y()));
+        assert_eq!(mesh.vertices[0].position, vec3(-1.0, 0.0, 2.0));
+        assert_eq!(MeshData::plane(1.0).triangle_count(), 2);
+    }
+
+    #[test]
+    fn cube() {
+        let mesh = MeshData::cube(2.0);
+        assert_eq!(mesh.vertex_count(), 24);
+        assert_eq!(mesh.triangle_count(), 12);
+        assert_consistent(&mesh);
+        assert_outward(&mesh);
+        for vertex in &mesh.vertices {
+            // flat faces: the normal is the axis the vertex sits furthest along
+            assert!((vertex.position.dot(vertex.normal) - 1.0).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn uv_sphere() {
+        let mesh = MeshData::uv_sphere(1.5, 16, 8);
+        assert_eq!(mesh.vertex_count(), 17 * 9);
+        assert_eq!(mesh.triangle_count(), 2 * 16 * (8 - 1));
+        assert_consistent(&mesh);
+        assert_outward(&mesh);
+        assert!(mesh
+            .vertices
+            .iter()
+            .all(|v| (v.position.magnitude() - 1.5).abs() < 1e-5));
+    }
+
+    #[test]
+    fn icosphere() {
+        for subdivisions in 0..3 {
+            let mesh = MeshData::icosphere(1.0, subdivisions);
+            assert_eq!(mesh.triangle_count(), 20 * 4usize.pow(subdivisions));
+            // 10 * 4^n + 2 shared vertices plus copies along the seam
+            assert!(mesh.vertex_count() >= 10 * 4usize.pow(subdivisions) + 2);
+            assert_consistent(&mesh);
+            assert_outward(&mesh);
+            for [a, b, c] in mesh.triangles() {
+                let us = [a, b, c].map(|index| mesh.vertices[index as usize].uv.x);
+                let spread =
+                    us.iter().cloned().fold(0.0, f32::max) - us.iter().cloned().fold(2.0, f32::min);
+                assert!(spread <= 0.5, "triangle wraps around the seam");
+            }
+        }
+    }
+
+    #[test]
+    fn cylinder_and_cone() {
+        let cylinder = MeshData::cylinder(0.5, 2.0, 12);
+        assert_eq!(cylinder.vertex_count(), 2 * 13 + 2 * (1 + 12));
+        assert_eq!(cylinder.triangle_count(), 2 * 12 + 2 * 12);
+        assert_consistent(&cylinder);
+        assert_outward(&cylinder);
+
+        let cone = MeshData::cone(0.5, 1.0, 12);
+        assert_eq!(cone.vertex_count(), 2 * 13 + 1 + 12);
+        assert_eq!(cone.triangle_count(), 12 + 12);
+        assert_consistent(&cone);
+        assert_outward(&cone);
+    }
+
+    #[test]
+    fn torus() {
+        let mesh = MeshData::torus(1.0, 0.25, 24, 12);
+        assert_eq!(mesh.vertex_count(), 25 * 13);
+        assert_eq!(mesh.triangle_count(), 2 * 24 * 12);
+        assert_consistent(&mesh);
+        for vertex in &mesh.vertices {
+            // normals point away from the middle of the tube
+            let radial = vec3(vertex.position.x, 0.0, vertex.position.z).normalize();
+            let tube_center = radial * 1.0;
+            assert!((vertex.position - tube_center).dot(vertex.normal) > 0.0);
+        }
+    }
+
+    #[test]
+    fn capsule() {
+        let mesh = MeshData::capsule(0.5, 1.0, 16, 4);
+        assert_eq!(mesh.vertex_count(), 17 * (2 * 4 + 2));
+        assert_eq!(mesh.triangle_count(), 4 * 4 * 16);
+        assert_consistent(&mesh);
+        assert_outward(&mesh);
+        let top = mesh
+            .vertices
+            .iter()
+            .map(|v| v.position.y)
+            .fold(f32::MIN, f32::max);
+        assert!((top - 1.0).abs() < 1e-6);
+        assert!(mesh.vertices.iter().all(|v| v.uv.y >= 0.0 && v.uv.y <= 1.0));
+    }
+
+    #[test]
+    fn computed_tangents_match_generated_ones() {
+        let sphere = MeshData::uv_sphere(1.0, 16, 8);
+        let mut recomputed = sphere.clone();
+        recomputed.compute_tangents();
+        for (a, b) in sphere.vertices.iter().zip(&recomputed.vertices) {
+            // the poles have no well defined tangent
+            if a.normal.y.abs() < 0.99 {
+                assert!(a.tangent.truncate().dot(b.tangent.truncate()) > 0.95);
+                assert_eq!(a.tangent.w, b.tangent.w);
+            }
+        }
+    }
+
+    #[test]
+    fn smooth_normals_ignore_triangulation() {
+        let sphere = MeshData::uv_sphere(2.0, 24, 12);
+        let mut recomputed = sphere.clone();
+        recomputed.compute_smooth_normals();
+        for (a, b) in sphere.vertices.iter().zip(&recomputed.vertices) {
+            // the seam and the poles are split, their normals only see one side
+            if a.uv.x > 0.0 && a.uv.x < 1.0 && a.normal.y.abs() < 0.99 {
+                assert!(a.normal.dot(b.normal) > 0.999);
+            }
+        }
+
+        // a corner where one side is cut into more triangles still gets the diagonal
+        let mut corner = MeshData::default();
+        for (normal, split) in [
+            (Vector3::unit_x(), 1),
+            (Vector3::unit_y(), 4),
+            (Vector3::unit_z(), 1),
+        ] {
+            let (u, v) = (
+                vec3(normal.y, normal.z, normal.x),
+                vec3(normal.z, normal.x, normal.y),
+            );
+            let mut face = MeshData::default();
+            let mut fan = vec![Vector3::zero()];
+            for i in 0..=split {
+                let t = i as f32 / split as f32;
+                fan.push(v * (1.0 - t) + u * t);
+            }
+            face.vertices = fan
+                .iter()
+                .map(|&position| Vertex {
+                    position,
+                    ..Vertex::default()
+                })
+                .collect();
+            face.indices = (1..=split as u32).flat_map(|i| [0, i, i + 1]).collect();
+            corner.append(&face);
+        }
+        corner.weld(1e-6);
+        corner.compute_smooth_normals();
+        let apex = corner
+            .vertices
+            .iter()
+            .find(|vertex| vertex.position == Vector3::zero())
+            .unwrap();
+        assert!((apex.normal - vec3(1.0, 1.0, 1.0).normalize()).magnitude() < 1e-5);
+    }
+
+    #[test]
+    fn tangents_match_mikktspace() {
+        // a bent 3x3 grid with sheared, uneven UVs; the expected tangents were produced by
+        // MikkTSpace's genTangSpace from the same positions, normals and UVs
+        let mut mesh = MeshData::default();
+        for y in [0.0, 0.6, 1.0] {
+            for x in [0.0, 1.0, 2.5] {
+                mesh.vertices.push(Vertex {
+                    position: vec3(x, y, 0.4 * x * y - 0.2 * x * x),
+                    normal: vec3(0.4 * x - 0.4 * y, -0.4 * x, 1.0).normalize(),
+                    uv: vec2(0.3 * x + 0.1 * y + 0.05 * x * y, 0.8 * y - 0.1 * x),
+                    ..Vertex::default()
+                });
+            }
+        }
+        for a in [0, 1, 3, 4] {
+            mesh.indices.extend([a, a + 1, a + 4, a, a + 4, a + 3]);
+        }
+        mesh.compute_tangents();
+        let expected = [
+            vec4(0.992_277_9, 0.124_034_72, 0.0, 1.0),
+            vec4(0.937_435, 0.131_198_76, -0.322_494_5, 1.0),
+            vec4(0.784_464_6, 0.196_116_12, -0.588_348_45, 1.0),
+            vec4(0.964_834_2, 0.124_397_54, 0.231_560_23, 1.0),
+            vec4(0.987_631_26, 0.105_836, -0.115_686_6, 1.0),
+            vec4(0.860_987_9, 0.177_822_86, -0.476_527_93, 1.0),
+            vec4(0.921_345_83, 0.123_699_24, 0.368_538_35, 1.0),
+            vec4(0.995_843_5, 0.084_565_89, 0.033_826_377, 1.0),
+            vec4(0.912_459_25, 0.180_053_85, -0.367_421_75, 1.0),
+        ];
+        assert_eq!(mesh.vertex_count(), expected.len());
+        for (vertex, expected) in mesh.vertices.iter().zip(expected) {
+            assert!(
+                (vertex.tangent - expected).magnitude() < 1e-5,
+                "{:?} {:?}",
+                vertex.tangent,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn mirrored_uvs_split_tangents() {
+        // two quads sharing an edge, the right one with its U mirrored
+        let mut mesh = MeshData::default();
+        for (x, u) in [(0.0, 0.0), (1.0, 1.0), (2.0, 0.0)] {
+            for y in [0.0, 1.0] {
+                mesh.vertices.push(Vertex {
+                    position: vec3(x, y, 0.0),
+                    normal: Vector3::unit_z(),
+                    uv: vec2(u, y),
+                    ..Vertex::default()
+                });
+            }
+        }
+        mesh.indices = vec![0, 2, 3, 0, 3, 1, 2, 4, 5, 2, 5, 3];
+        mesh.compute_tangents();
+        // the shared edge is duplicated for the mirrored side
+        assert_eq!(mesh.vertex_count(), 8);
+        for [a, b, c] in mesh.triangles() {
+            let mirrored = mesh.vertices[a as usize].position.x
+                + mesh.vertices[b as usize].position.x
+                + mesh.vertices[c as usize].position.x
+                > 3.0;
+            for index in [a, b, c] {
+                let tangent = mesh.vertices[index as usize].tangent;
+                if mirrored {
+                    assert_eq!(tangent, vec4(-1.0, 0.0, 0.0, -1.0));
+                } else {
+                    assert_eq!(tangent, vec4(1.0, 0.0, 0.0, 1.0));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn bounds_of_generated_meshes() {
+        let bounds = MeshData::capsule(0.5, 2.0, 16, 4).bounds();
+        assert!((bounds.min - Point3::new(-0.5, -1.5, -0.5)).magnitude() < 1e-5);
+        assert!((bounds.max - Point3::new(0.5, 1.5, 0.5)).magnitude() < 1e-5);
+        let sphere = MeshData::icosphere(3.0, 2).bounding_sphere();
+        assert!(sphere.center.to_vec().magnitude() < 0.3);
+        assert!(sphere.radius >= 3.0 && sphere.radius < 3.3);
+    }
+
+    #[test]
+    fn append_offsets_indices() {
+        let mut mesh = MeshData::plane(1.0);
+        mesh.append(&MeshData::plane(1.0));
+        assert_eq!(mesh.vertex_count(), 8);
+        assert_eq!(&mesh.indices[6..], &[4, 5, 7, 4, 7, 6]);
+    }
+
+    #[test]
+    fn transform_keeps_triangles_facing_their_normals() {
+        let mut mesh = MeshData::plane(1.0);
+        // move up and mirror the plane so it faces down
+        mesh.transform(
+            &(Matrix4::from_translation(vec3(0.0, 2.0, 0.0))
+                * Matrix4::from_nonuniform_scale(2.0, -1.0, 1.0)),
+        );
+        for vertex in &mesh.vertices {
+            assert_eq!(vertex.position.y, 2.0);
+            assert!((vertex.normal - vec3(0.0, -1.0, 0.0)).magnitude() < 1e-6);
+        }
+        let triangle = mesh.triangles().next().unwrap();
+        assert!(mesh.face_normal(triangle).dot(vec3(0.0, -1.0, 0.0)) > 0.0);
+        assert_eq!(mesh.bounds().extents().x, 1.0);
+    }
+
+    #[test]
+    fn mirroring_keeps_the_bitangent() {
+        let bitangent =
+            |vertex: &Vertex| vertex.normal.cross(vertex.tangent.truncate()) * vertex.tangent.w;
+        let mut mesh = MeshData::uv_sphere(1.0, 8, 4);
+        let before = mesh.clone();
+        mesh.transform(&Matrix4::from_nonuniform_scale(-1.0, 1.0, 1.0));
+        for (vertex, original) in mesh.vertices.iter().zip(&before.vertices) {
+            assert_eq!(vertex.tangent.w, -original.tangent.w);
+            // the bitangent is mirrored like any other direction
+            let expected = bitangent(original);
+            let expected = vec3(-expected.x, expected.y, expected.z);
+            assert!((bitangent(vertex) - expected).magnitude() < 1e-5);
+        }
+    }
+}
@@ -0,0 +1,315 @@
+use std::collections::HashMap;
+
+use cgmath::prelude::*;
+
+use crate::mesh::{MeshData, Vertex};
+
+/// size of the simulated post-transform cache `optimize_vertex_cache` orders for
+pub const VERTEX_CACHE_SIZE: usize = 32;
+
+impl MeshData {
+    /// Merge vertices whose positions lie within `tolerance` of each other and whose normals,
+    /// tangents, UVs and colours differ by at most `tolerance` per component. Triangles that
+    /// collapse are dropped. Returns how many vertices were removed.
+    /// ------------------------------------------------------------------------
+    pub fn weld(&mut self, tolerance: f32) -> usize {
+        let cell_size = tolerance.max(1e-6);
+        let cell = |vertex: &Vertex| {
+            let p = vertex.position / cell_size;
+            [p.x.floor() as i64, p.y.floor() as i64, p.z.floor() as i64]
+        };
+        let close = |difference: &[f32]| difference.iter().all(|c| c.abs() <= tolerance);
+        let same = |a: &Vertex, b: &Vertex| {
+            a.position.distance(b.position) <= tolerance
+                && close(AsRef::<[f32; 3]>::as_ref(&(a.normal - b.normal)))
+                && close(AsRef::<[f32; 4]>::as_ref(&(a.tangent - b.tangent)))
+                && close(AsRef::<[f32; 2]>::as_ref(&(a.uv - b.uv)))
+                && close(AsRef::<[f32; 4]>::as_ref(&(a.color - b.color)))
+        };
+
+        // kept vertices by grid cell; a match can be in any neighbouring cell
+        let mut grid: HashMap<[i64; 3], Vec<u32>> = HashMap::new();
+        let mut vertices: Vec<Vertex> = Vec::with_capacity(self.vertices.len());
+        let mut remap = Vec::with_capacity(self.vertices.len());
+        for vertex in &self.vertices {
+            let [x, y, z] = cell(vertex);
+            let mut found = None;
+            'search: for dx in -1..=1 {
+                for dy in -1..=1 {
+                    for dz in -1..=1 {
+                        let Some(candidates) = grid.get(&[x + dx, y + dy, z + dz]) else {
+                            continue;
+                        };
+                        found = candidates
+                            .iter()
+                            .copied()
+                            .find(|&kept| same(&vertices[kept as usize], vertex));
+                        if found.is_some() {
+                            break 'search;
+                        }
+                    }
+                }
+            }
+            let index = found.unwrap_or_else(|| {
+                let index = vertices.len() as u32;
+                vertices.push(*vertex);
+                grid.entry([x, y, z]).or_default().push(index);
+                index
+            });
+            remap.push(index);
+        }
+
+        let removed = self.vertices.len() - vertices.len();
+        let indices = self
+            .triangles()
+            .map(|triangle| triangle.map(|index| remap[index as usize]))
+            .filter(|[a, b, c]| a != b && b != c && c != a)
+            .flatten()
+            .collect();
+        self.vertices = vertices;
+        self.indices = indices;
+        removed
+    }
+
+    /// Reorder the triangles so that vertices are reused while they are still in the GPU's
+    /// post-transform cache (Tom Forsyth's linear-speed optimisation). The triangles
+    /// themselves and their winding stay the same.
+    /// ------------------------------------------------------------------------
+    pub fn optimize_vertex_cache(&mut self) {
+        let triangle_count = self.triangle_count();
+        if triangle_count == 0 {
+            return;
+        }
+        let mut vertex_triangles: Vec<Vec<usize>> = vec![Vec::new(); self.vertices.len()];
+        for (triangle, indices) in self.triangles().enumerate() {
+            for index in indices {
+                vertex_triangles[index as usize].push(triangle);
+            }
+        }
+        let mut remaining: Vec<usize> = vertex_triangles.iter().map(Vec::len).collect();
+        let mut cache_position: Vec<Option<usize>> = vec![None; self.vertices.len()];
+        let mut vertex_score: Vec<f32> = remaining
+            .iter()
+            .map(|&remaining| forsyth_score(None, remaining))
+            .collect();
+        let triangle_score = |triangle: usize, vertex_score: &[f32]| -> f32 {
+            self.indices[triangle * 3..triangle * 3 + 3]
+                .iter()
+                .map(|&index| vertex_score[index as usize])
+                .sum()
+        };
+        let mut scores: Vec<f32> = (0..triangle_count)
+            .map(|triangle| triangle_score(triangle, &vertex_score))
+            .collect();
+        let mut added = vec![false; triangle_count];
+        let mut cache: Vec<u32> = Vec::with_capacity(VERTEX_CACHE_SIZE + 3);
+        let mut order: Vec<u32> = Vec::with_capacity(self.indices.len());
+        // triangles that weren't reachable from the cache are picked in order from here
+        let mut next_unadded = 0;
+
+        let mut best = (0..triangle_count).max_by(|a, b| scores[*a].total_cmp(&scores[*b]));
+        while let Some(triangle) = best {
+            added[triangle] = true;
+            let indices = &self.indices[triangle * 3..triangle * 3 + 3];
+            order.extend_from_slice(indices);
+            for &index in indices {
+                remaining[index as usize] -= 1;
+                vertex_triangles[index as usize].retain(|&other| other != triangle);
+            }
+
+            // move the triangle's vertices to the front of the LRU cache
+            cache.retain(|index| !indices.contains(index));
+            for &index in indices.iter().rev() {
+                cache.insert(0, index);
+            }
+            for (position, &index) in cache.iter().enumerate() {
+                cache_position[index as usize] =
+                    Some(position).filter(|&position| position < VERTEX_CACHE_SIZE);
+            }
+            for &index in cache.iter().skip(VERTEX_CACHE_SIZE) {
+                cache_position[index as usize] = None;
+            }
+
+            // rescore everything touching the cache and pick the best of those triangles
+            best = None;
+            let mut best_score = f32::MIN;
+            for &index in &cache {
+                let index = index as usize;
+                vertex_score[index] = forsyth_score(cache_position[index], remaining[index]);
+            }
+            for &index in &cache {
+                for &other in &vertex_triangles[index as usize] {
+                    scores[other] = triangle_score(other, &vertex_score);
+                    if scores[other] > best_score {
+                        best_score = scores[other];
+                        best = Some(other);
+                    }
+                }
+            }
+            cache.truncate(VERTEX_CACHE_SIZE);
+
+            if best.is_none() {
+                while next_unadded < triangle_count && added[next_unadded] {
+                    next_unadded += 1;
+                }
+                best = Some(next_unadded).filter(|&next| next < triangle_count);
+            }
+        }
+        self.indices = order;
+    }
+
+    /// Store the vertices in the order the index buffer first uses them, so vertex fetches
+    /// walk through memory. Unreferenced vertices are dropped.
+    /// ------------------------------------------------------------------------
+    pub fn optimize_vertex_fetch(&mut self) {
+        let mut remap: Vec<Option<u32>> = vec![None; self.vertices.len()];
+        let mut vertices = Vec::with_capacity(self.vertices.len());
+        for index in self.indices.iter_mut() {
+            let new_index = *remap[*index as usize].get_or_insert_with(|| {
+                vertices.push(self.vertices[*index as usize]);
+                vertices.len() as u32 - 1
+            });
+            *index = new_index;
+        }
+        self.vertices = vertices;
+    }
+
+    /// Reorder a freshly loaded mesh for the GPU: triangles for the post-transform cache,
+    /// then vertices for fetching. The new triangle order is only kept when it misses the
+    /// cache less often, files exported by other optimisers are often better already.
+    /// ------------------------------------------------------------------------
+    pub fn optimize(&mut self) {
+        let before = self.acmr(VERTEX_CACHE_SIZE);
+        let indices = self.indices.clone();
+        self.optimize_vertex_cache();
+        if self.acmr(VERTEX_CACHE_SIZE) >= before {
+            self.indices = indices;
+        }
+        self.optimize_vertex_fetch();
+    }
+
+    /// Average cache miss ratio: vertices transformed per triangle with a FIFO cache of
+    /// `cache_size` entries. 3.0 is the worst case, about 0.6 the best for regular grids.
+    /// ------------------------------------------------------------------------
+    pub fn acmr(&self, cache_size: usize) -> f32 {
+        if self.indices.len() < 3 {
+            return 0.0;
+        }
+        let mut cache = std::collections::VecDeque::with_capacity(cache_size);
+        let mut misses = 0;
+        for index in &self.indices {
+            if !cache.contains(index) {
+                misses += 1;
+                if cache.len() == cache_size {
+                    cache.pop_front();
+                }
+                cache.push_back(*index);
+            }
+        }
+        misses as f32 / self.triangle_count() as f32
+    }
+}
+
+/// Forsyth's vertex score: recently used vertices and ones with few triangles left to draw
+/// score higher. `None` means the vertex isn't in the cache.
+fn forsyth_score(cache_position: Option<usize>, remaining: usize) -> f32 {
+    const CACHE_DECAY_POWER: f32 = 1.5;
+    const LAST_TRIANGLE_SCORE: f32 = 0.75;
+    const VALENCE_BOOST_SCALE: f32 = 2.0;
+    const VALENCE_BOOST_POWER: f32 = 0.5;
+    if remaining == 0 {
+        return -1.0;
+    }
+    let cache_score = match cache_position {
+        None => 0.0,
+        // the vertices of the triangle just drawn score the same, whatever their order
+        Some(position) if position < 3 => LAST_TRIANGLE_SCORE,
+        Some(position) => {
+            let scale = 1.0 / (VERTEX_CACHE_SIZE - 3) as f32;
+            (1.0 - (position - 3) as f32 * scale).powf(CACHE_DECAY_POWER)
+        }
+    };
+    cache_score + VALENCE_BOOST_SCALE * (remaining as f32).powf(-VALENCE_BOOST_POWER)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::vec3;
+
+    /// triangles as sorted index sets, to compare meshes independent of order and rotation
+    fn triangle_set(mesh: &MeshData) -> Vec<[u32; 3]> {
+        let mut triangles: Vec<[u32; 3]> = mesh
+            .triangles()
+            .map(|mut triangle| {
+                triangle.sort_unstable();
+                triangle
+            })
+            .collect();
+        triangles.sort_unstable();
+        triangles
+    }
+
+    #[test]
+    fn weld_undoes_flat_split() {
+        let grid = MeshData::grid(2.0, 2.0, 4, 4);
+        let mut split = grid.clone();
+        split.compute_flat_normals();
+        assert_eq!(split.vertex_count(), 4 * 4 * 2 * 3);
+        // nudge every copy a little, well inside the tolerance
+        for (i, vertex) in split.vertices.iter_mut().enumerate() {
+            vertex.position += vec3(1.0, -1.0, 0.5) * (i % 3) as f32 * 1e-5;
+        }
+        let removed = split.weld(1e-3);
+        assert_eq!(split.vertex_count(), grid.vertex_count());
+        assert_eq!(removed, 4 * 4 * 2 * 3 - grid.vertex_count());
+        assert_eq!(split.triangle_count(), grid.triangle_count());
+
+        // different normals keep vertices apart, collapsed triangles disappear
+        let mut cube = MeshData::cube(1.0);
+        assert_eq!(cube.weld(1e-3), 0);
+        assert_eq!(cube.vertex_count(), 24);
+        let mut degenerate = MeshData::grid(1.0, 1.0, 1, 1);
+        degenerate.vertices[1] = degenerate.vertices[0];
+        degenerate.weld(0.0);
+        assert_eq!(degenerate.triangle_count(), 1);
+    }
+
+    #[test]
+    fn cache_order_keeps_triangles_and_lowers_misses() {
+        let mut mesh = MeshData::grid(1.0, 1.0, 40, 40);
+        // scramble the triangle order deterministically
+        let mut triangles: Vec<[u32; 3]> = mesh.triangles().collect();
+        let mut seed = 12345u32;
+        for i in (1..triangles.len()).rev() {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            triangles.swap(i, (seed >> 8) as usize % (i + 1));
+        }
+        mesh.indices = triangles.into_iter().flatten().collect();
+        let before = mesh.acmr(VERTEX_CACHE_SIZE);
+        let expected = triangle_set(&mesh);
+
+        mesh.optimize_vertex_cache();
+        let after = mesh.acmr(VERTEX_CACHE_SIZE);
+        assert!(before > 2.0, "scrambled ACMR {}", before);
+        assert!(after < 0.9, "optimised ACMR {}", after);
+        assert_eq!(triangle_set(&mesh), expected);
+
+        let vertices = mesh.vertex_count();
+        let first = mesh.vertices[mesh.indices[0] as usize];
+        mesh.optimize_vertex_fetch();
+        assert_eq!(mesh.vertex_count(), vertices);
+        assert_eq!(mesh.indices[..3], [0, 1, 2]);
+        assert_eq!(mesh.vertices[0], first);
+    }
+
+    #[test]
+    fn optimize_never_adds_misses() {
+        let mut mesh = MeshData::grid(1.0, 1.0, 40, 40);
+        let before = mesh.acmr(VERTEX_CACHE_SIZE);
+        let expected = mesh.triangle_count();
+        mesh.optimize();
+        assert!(mesh.acmr(VERTEX_CACHE_SIZE) <= before);
+        assert_eq!(mesh.triangle_count(), expected);
+    }
+}
@@ -0,0 +1,183 @@
+use std::collections::HashMap;
+use std::ffi::CStr;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use cgmath::{vec3, Vector3};
+
+use crate::bounds::Aabb;
+use crate::config::ConfigError;
+use crate::mesh::{Mesh, MeshData};
+use crate::obj::{self, ObjMaterial};
+use crate::shader::Shader;
+use crate::texture::Texture;
+
+/// Surface parameters of a model part and its (shared) texture maps.
+pub struct ModelMaterial {
+    pub name: String,
+    pub diffuse: Vector3<f32>,
+    pub specular: Vector3<f32>,
+    pub shininess: f32,
+    pub diffuse_map: Option<Rc<Texture>>,
+    pub specular_map: Option<Rc<Texture>>,
+    pub normal_map: Option<Rc<Texture>>,
+}
+
+impl Default for ModelMaterial {
+    fn default() -> ModelMaterial {
+        ModelMaterial {
+            name: String::new(),
+            diffuse: vec3(0.8, 0.8, 0.8),
+            specular: vec3(0.0, 0.0, 0.0),
+            shininess: 32.0,
+            diffuse_map: None,
+            specular_map: None,
+            normal_map: None,
+        }
+    }
+}
+
+pub struct ModelMesh {
+    pub mesh: Mesh,
+    /// index into `Model::materials`
+    pub material: Option<usize>,
+}
+
+/// Meshes loaded from a model file together with their materials, ready to draw.
+pub struct Model {
+    pub meshes: Vec<ModelMesh>,
+    pub materials: Vec<ModelMaterial>,
+    /// axis-aligned bounds of all vertices
+    pub bounds: Aabb,
+    /// bound in place of missing maps
+    white: Texture,
+    flat_normal: Texture,
+}
+
+impl Model {
+    /// Load a Wavefront OBJ file with its materials and textures; needs a current GL context.
+    /// Textures that fail to load are reported and left out.
+    /// ------------------------------------------------------------------------
+    pub fn load_obj<P: AsRef<Path>>(path: P) -> Result<Model, ConfigError> {
+        let obj = obj::load_obj(path)?;
+        let mut textures = TextureCache::default();
+        let materials: Vec<ModelMaterial> = obj
+            .materials
+            .iter()
+            .map(|material| textures.material(material))
+            .collect();
+
+        let mut bounds = Aabb::empty();
+        let mut meshes = Vec::with_capacity(obj.meshes.len());
+        for part in &obj.meshes {
+            bounds = bounds.union(&part.data.bounds());
+            let material = part.material.as_ref().and_then(|name| {
+                let index = materials.iter().position(|material| &material.name == name);
+                if index.is_none() {
+                    println!("WARNING::MODEL::UNKNOWN_MATERIAL {}", name);
+                }
+                index
+            });
+            meshes.push(ModelMesh {
+                mesh: Mesh::with_bvh(&part.data),
+                material,
+            });
+        }
+
+        Ok(Model {
+            meshes,
+            materials,
+            bounds,
+            white: Texture::solid([255, 255, 255, 255]),
+            flat_normal: Texture::solid([128, 128, 255, 255]),
+        })
+    }
+
+    /// A single mesh with the default material, e.g. a PLY or STL scan; needs a current GL
+    /// context.
+    /// ------------------------------------------------------------------------
+    pub fn from_data(data: &MeshData) -> Model {
+        Model {
+            meshes: vec![ModelMesh {
+                mesh: Mesh::with_bvh(data),
+                material: None,
+            }],
+            materials: Vec::new(),
+            bounds: data.bounds(),
+            white: Texture::solid([255, 255, 255, 255]),
+            flat_normal: Texture::solid([128, 128, 255, 255]),
+        }
+    }
+
+    /// Draw every mesh with `shader`, which should be in use. Sets `texture_diffuse1` (unit 0),
+    /// `texture_specular1` (unit 1), `texture_normal` (unit 2, tangent space) and the
+    /// `diffuse_color` / `specular_color` / `shininess` uniforms per mesh.
+    /// ------------------------------------------------------------------------
+    pub unsafe fn draw(&self, shader: &Shader) {
+        let default = ModelMaterial::default();
+        shader.setInt(c_str!("texture_diffuse1"), 0);
+        shader.setInt(c_str!("texture_specular1"), 1);
+        shader.setInt(c_str!("texture_normal"), 2);
+        shader.setFloat(c_str!("normal_scale"), 1.0);
+        for part in &self.meshes {
+            let material = part
+                .material
+                .map_or(&default, |index| &self.materials[index]);
+            material
+                .diffuse_map
+                .as_deref()
+                .unwrap_or(&self.white)
+                .bind(0);
+            material
+                .specular_map
+                .as_deref()
+                .unwrap_or(&self.white)
+                .bind(1);
+            material
+                .normal_map
+                .as_deref()
+                .unwrap_or(&self.flat_normal)
+                .bind(2);
+            shader.setVector3(c_str!("diffuse_color"), &material.diffuse);
+            shader.setVector3(c_str!("specular_color"), &material.specular);
+            shader.setFloat(c_str!("shininess"), material.shininess);
+            part.mesh.draw();
+        }
+        gl::ActiveTexture(gl::TEXTURE0);
+    }
+}
+
+/// loads each texture file once, even when several materials use it
+#[derive(Default)]
+struct TextureCache {
+    loaded: HashMap<PathBuf, Option<Rc<Texture>>>,
+}
+
+impl TextureCache {
+    fn get(&mut self, path: &Option<PathBuf>) -> Option<Rc<Texture>> {
+        let path = path.as_ref()?;
+        self.loaded
+            .entry(path.clone())
+            .or_insert_with(|| match Texture::load(path, true) {
+                Ok(texture) => Some(Rc::new(texture)),
+                Err(err) => {
+                    println!("WARNING::MODEL::TEXTURE_NOT_LOADED\n{}", err);
+                    None
+                }
+            })
+            .clone()
+    }
+
+    fn material(&mut self, material: &ObjMaterial) -> ModelMaterial {
+        let color = |[r, g, b]: [f32; 3]| vec3(r, g, b);
+        ModelMaterial {
+            name: material.name.clone(),
+            diffuse: color(material.diffuse),
+            specular: color(material.specular),
+            shininess: material.shininess,
+            diffuse_map: self.get(&material.diffuse_map),
+            specular_map: self.get(&material.specular_map),
+            normal_map: self.get(&material.normal_map),
+        }
+    }
+}
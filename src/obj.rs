@@ -0,0 +1,714 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use cgmath::prelude::*;
+use cgmath::{vec2, vec3, Vector2, Vector3};
+
+use crate::config::ConfigError;
+use crate::mesh::{MeshData, Vertex};
+
+/// A material from a `.mtl` library. Map paths are resolved relative to the library.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjMaterial {
+    pub name: String,
+    pub ambient: [f32; 3],
+    pub diffuse: [f32; 3],
+    pub specular: [f32; 3],
+    pub emissive: [f32; 3],
+    /// specular exponent (`Ns`)
+    pub shininess: f32,
+    /// 1 is opaque (`d`, or `1 - Tr`)
+    pub opacity: f32,
+    pub diffuse_map: Option<PathBuf>,
+    pub specular_map: Option<PathBuf>,
+    pub emissive_map: Option<PathBuf>,
+    pub normal_map: Option<PathBuf>,
+    pub opacity_map: Option<PathBuf>,
+}
+
+impl ObjMaterial {
+    fn new(name: &str) -> ObjMaterial {
+        ObjMaterial {
+            name: name.to_string(),
+            ambient: [0.0; 3],
+            diffuse: [0.8; 3],
+            specular: [0.0; 3],
+            emissive: [0.0; 3],
+            shininess: 32.0,
+            opacity: 1.0,
+            diffuse_map: None,
+            specular_map: None,
+            emissive_map: None,
+            normal_map: None,
+            opacity_map: None,
+        }
+    }
+}
+
+/// The part of an OBJ file with one object, group and material.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjMesh {
+    pub object: String,
+    pub group: String,
+    pub material: Option<String>,
+    pub data: MeshData,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ObjModel {
+    pub meshes: Vec<ObjMesh>,
+    /// `mtllib` file names as written in the OBJ file
+    pub material_libs: Vec<String>,
+    pub materials: Vec<ObjMaterial>,
+}
+
+/// Load an OBJ file and the material libraries it references. Meshes are reordered for the
+/// vertex cache, see `MeshData::optimize`.
+///
+/// A missing or broken material library only prints a warning, the geometry is still usable.
+pub fn load_obj<P: AsRef<Path>>(path: P) -> Result<ObjModel, ConfigError> {
+    let path = path.as_ref();
+    let text = fs::read_to_string(path).map_err(|err| ConfigError::Io(path.to_path_buf(), err))?;
+    let mut model = parse_obj(&text).map_err(|err| ConfigError::Parse(path.to_path_buf(), err))?;
+    for mesh in &mut model.meshes {
+        mesh.data.optimize();
+    }
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+    for lib in &model.material_libs {
+        let lib_path = directory.join(lib);
+        let materials = fs::read_to_string(&lib_path)
+            .map_err(|err| ConfigError::Io(lib_path.clone(), err))
+            .and_then(|text| {
+                let base = lib_path.parent().unwrap_or_else(|| Path::new(""));
+                parse_mtl(&text, base).map_err(|err| ConfigError::Parse(lib_path.clone(), err))
+            });
+        match materials {
+            Ok(materials) => model.materials.extend(materials),
+            Err(err) => println!("WARNING::OBJ::MATERIAL_LIBRARY_NOT_LOADED\n{}", err),
+        }
+    }
+    Ok(model)
+}
+
+/// one face corner as indices into the position / uv / normal lists
+#[derive(Debug, Clone, Copy)]
+struct Corner {
+    position: usize,
+    uv: Option<usize>,
+    normal: Option<usize>,
+}
+
+struct Triangle {
+    corners: [Corner; 3],
+    /// index of the polygon the triangle was cut from
+    face: usize,
+    /// smoothing group, 0 is off
+    smoothing: u32,
+}
+
+#[derive(Default)]
+struct MeshBuilder {
+    object: String,
+    group: String,
+    material: Option<String>,
+    triangles: Vec<Triangle>,
+}
+
+/// where a vertex normal comes from; part of the key used to share vertices
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum NormalSource {
+    File(usize),
+    Smooth(u32),
+    /// generated flat normal of the given face
+    Flat(usize),
+}
+
+/// Parse the text of an OBJ file. Errors name the offending line.
+///
+/// Polygons are triangulated, faces without normals get normals generated from their smoothing
+/// group (flat when smoothing is off) and a new mesh starts at every `o`, `g` or `usemtl`.
+pub fn parse_obj(text: &str) -> Result<ObjModel, String> {
+    let mut positions: Vec<Vector3<f32>> = Vec::new();
+    let mut uvs: Vec<Vector2<f32>> = Vec::new();
+    let mut normals: Vec<Vector3<f32>> = Vec::new();
+    let mut material_libs = Vec::new();
+    let mut builders = vec![MeshBuilder::default()];
+    let mut smoothing = 0;
+    let mut face_count = 0;
+
+    for (number, line) in logical_lines(text) {
+        let error = |message: String| format!("line {}: {}", number, message);
+        let mut words = line.split_whitespace();
+        let keyword = match words.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+        let rest = line[keyword.len()..].trim();
+        match keyword {
+            "v" => positions.push(parse_vec3(words).map_err(error)?),
+            "vt" => {
+                let values = parse_floats(words).map_err(error)?;
+                let u = *values
+                    .first()
+                    .ok_or_else(|| error("missing u".to_string()))?;
+                uvs.push(vec2(u, values.get(1).copied().unwrap_or(0.0)));
+            }
+            "vn" => normals.push(parse_vec3(words).map_err(error)?),
+            "f" => {
+                let corners = words
+                    .map(|word| {
+                        parse_corner(word, positions.len(), uvs.len(), normals.len())
+                            .map_err(|err| error(format!("{} in '{}'", err, word)))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                if corners.len() < 3 {
+                    return Err(error("face with fewer than 3 vertices".to_string()));
+                }
+                let points: Vec<Vector3<f32>> = corners
+                    .iter()
+                    .map(|corner| positions[corner.position])
+                    .collect();
+                let builder = builders.last_mut().unwrap();
+                for [a, b, c] in triangulate(&points) {
+                    builder.triangles.push(Triangle {
+                        corners: [corners[a], corners[b], corners[c]],
+                        face: face_count,
+                        smoothing,
+                    });
+                }
+                face_count += 1;
+            }
+            "o" | "g" | "usemtl" => {
+                let current = builders.last().unwrap();
+                let mut next = MeshBuilder {
+                    object: current.object.clone(),
+                    group: current.group.clone(),
+                    material: current.material.clone(),
+                    triangles: Vec::new(),
+                };
+                match keyword {
+                    "o" => {
+                        next.object = rest.to_string();
+                        next.group = String::new();
+                    }
+                    "g" => next.group = rest.to_string(),
+                    _ => next.material = Some(rest.to_string()),
+                }
+                if current.triangles.is_empty() {
+                    *builders.last_mut().unwrap() = next;
+                } else {
+                    builders.push(next);
+                }
+            }
+            "s" => {
+                smoothing = match rest {
+                    "off" | "" => 0,
+                    group => group
+                        .parse()
+                        .map_err(|_| error(format!("invalid smoothing group '{}'", group)))?,
+                }
+            }
+            "mtllib" => material_libs.extend(words.map(str::to_string)),
+            // lines, points, curves and display attributes are not supported
+            _ => {}
+        }
+    }
+
+    let meshes = builders
+        .into_iter()
+        .filter(|builder| !builder.triangles.is_empty())
+        .map(|builder| build_mesh(builder, &positions, &uvs, &normals))
+        .collect();
+    Ok(ObjModel {
+        meshes,
+        material_libs,
+        materials: Vec::new(),
+    })
+}
+
+/// Parse the text of a `.mtl` file; texture paths are joined to `base`.
+pub fn parse_mtl(text: &str, base: &Path) -> Result<Vec<ObjMaterial>, String> {
+    let mut materials: Vec<ObjMaterial> = Vec::new();
+    for (number, line) in logical_lines(text) {
+        let error = |message: String| format!("line {}: {}", number, message);
+        let mut words = line.split_whitespace();
+        let keyword = match words.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+        if keyword == "newmtl" {
+            materials.push(ObjMaterial::new(line[keyword.len()..].trim()));
+            continue;
+        }
+        let material = match materials.last_mut() {
+            Some(material) => material,
+            None => return Err(error(format!("'{}' before newmtl", keyword))),
+        };
+        let map = || map_file(&line[keyword.len()..]).map(|file| base.join(file));
+        match keyword {
+            "Ka" => material.ambient = parse_color(words).map_err(error)?,
+            "Kd" => material.diffuse = parse_color(words).map_err(error)?,
+            "Ks" => material.specular = parse_color(words).map_err(error)?,
+            "Ke" => material.emissive = parse_color(words).map_err(error)?,
+            "Ns" => material.shininess = parse_float(words.next()).map_err(error)?,
+            "d" => material.opacity = parse_float(words.next()).map_err(error)?,
+            "Tr" => material.opacity = 1.0 - parse_float(words.next()).map_err(error)?,
+            "map_Kd" => material.diffuse_map = map(),
+            "map_Ks" => material.specular_map = map(),
+            "map_Ke" => material.emissive_map = map(),
+            "map_Bump" | "map_bump" | "bump" | "norm" => material.normal_map = map(),
+            "map_d" => material.opacity_map = map(),
+            _ => {}
+        }
+    }
+    Ok(materials)
+}
+
+/// the file name of a map statement: options like `-bm 0.5` come first and the rest of the
+/// line is the file name, which may contain spaces
+fn map_file(mut rest: &str) -> Option<&str> {
+    loop {
+        rest = rest.trim_start();
+        let option = match rest.split_whitespace().next() {
+            Some(word) if word.starts_with('-') && word.len() > 1 => word,
+            _ => break,
+        };
+        rest = &rest[option.len()..];
+        // -o, -s and -t take one to three numbers, -mm two, the others a single value
+        let (min, max) = match option {
+            "-o" | "-s" | "-t" => (1, 3),
+            "-mm" => (2, 2),
+            _ => (1, 1),
+        };
+        for taken in 0..max {
+            let trimmed = rest.trim_start();
+            let word = trimmed.split_whitespace().next()?;
+            if taken >= min && word.parse::<f32>().is_err() {
+                break;
+            }
+            rest = &trimmed[word.len()..];
+        }
+    }
+    Some(rest.trim_end()).filter(|file| !file.is_empty())
+}
+
+/// non-empty lines without comments, with `\` continuations joined, and their line numbers
+fn logical_lines(text: &str) -> Vec<(usize, String)> {
+    let mut lines = Vec::new();
+    let mut pending: Option<(usize, String)> = None;
+    for (index, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("").trim_end();
+        let (number, mut joined) = pending.take().unwrap_or((index + 1, String::new()));
+        match line.strip_suffix('\\') {
+            Some(start) => {
+                joined.push_str(start);
+                joined.push(' ');
+                pending = Some((number, joined));
+            }
+            None => {
+                joined.push_str(line);
+                if !joined.trim().is_empty() {
+                    lines.push((number, joined.trim().to_string()));
+                }
+            }
+        }
+    }
+    if let Some((number, joined)) = pending {
+        lines.push((number, joined.trim().to_string()));
+    }
+    lines
+}
+
+fn parse_float(word: Option<&str>) -> Result<f32, String> {
+    let word = word.ok_or_else(|| "missing number".to_string())?;
+    word.parse()
+        .map_err(|_| format!("invalid number '{}'", word))
+}
+
+fn parse_floats<'a, I: Iterator<Item = &'a str>>(words: I) -> Result<Vec<f32>, String> {
+    words.map(|word| parse_float(Some(word))).collect()
+}
+
+fn parse_vec3<'a, I: Iterator<Item = &'a str>>(mut words: I) -> Result<Vector3<f32>, String> {
+    // extra values (w, vertex colours) are ignored
+    Ok(vec3(
+        parse_float(words.next())?,
+        parse_float(words.next())?,
+        parse_float(words.next())?,
+    ))
+}
+
+fn parse_color<'a, I: Iterator<Item = &'a str>>(words: I) -> Result<[f32; 3], String> {
+    let values = parse_floats(words)?;
+    match values.len() {
+        // a single value means grey
+        1 => Ok([values[0]; 3]),
+        n if n >= 3 => Ok([values[0], values[1], values[2]]),
+        _ => Err("expected 1 or 3 colour components".to_string()),
+    }
+}
+
+/// `v`, `v/vt`, `v//vn` or `v/vt/vn`; indices are 1-based, negative ones count back from the
+/// end of what has been read so far
+fn parse_corner(
+    word: &str,
+    position_count: usize,
+    uv_count: usize,
+    normal_count: usize,
+) -> Result<Corner, String> {
+    let mut parts = word.split('/');
+    let position = resolve_index(parts.next(), position_count)?
+        .ok_or_else(|| "missing position index".to_string())?;
+    let uv = resolve_index(parts.next(), uv_count)?;
+    let normal = resolve_index(parts.next(), normal_count)?;
+    Ok(Corner {
+        position,
+        uv,
+        normal,
+    })
+}
+
+fn resolve_index(part: Option<&str>, count: usize) -> Result<Option<usize>, String> {
+    let part = match part {
+        Some(part) if !part.is_empty() => part,
+        _ => return Ok(None),
+    };
+    let index: i64 = part
+        .parse()
+        .map_err(|_| format!("invalid index '{}'", part))?;
+    let resolved = if index > 0 {
+        index - 1
+    } else {
+        count as i64 + index
+    };
+    if index == 0 || resolved < 0 || resolved >= count as i64 {
+        return Err(format!("index {} out of range", index));
+    }
+    Ok(Some(resolved as usize))
+}
+
+/// Split a polygon into triangles by ear clipping in the polygon's plane, keeping its winding.
+/// Falls back to a fan for degenerate polygons.
+pub fn triangulate(points: &[Vector3<f32>]) -> Vec<[usize; 3]> {
+    let count = points.len();
+    if count < 3 {
+        return Vec::new();
+    }
+    if count == 3 {
+        return vec![[0, 1, 2]];
+    }
+    let fan = || (1..count - 1).map(|i| [0, i, i + 1]).collect();
+
+    // Newell's method gives a robust normal for non-planar polygons too
+    let mut normal = Vector3::zero();
+    for (i, a) in points.iter().enumerate() {
+        let b = points[(i + 1) % count];
+        normal += vec3(
+            (a.y - b.y) * (a.z + b.z),
+            (a.z - b.z) * (a.x + b.x),
+            (a.x - b.x) * (a.y + b.y),
+        );
+    }
+    if normal.magnitude2() < 1e-20 {
+        return fan();
+    }
+    let normal = normal.normalize();
+    let u_axis = if normal.x.abs() < 0.9 {
+        Vector3::unit_x()
+    } else {
+        Vector3::unit_y()
+    };
+    let u_axis = (u_axis - normal * normal.dot(u_axis)).normalize();
+    let v_axis = normal.cross(u_axis);
+    // counter-clockwise in this 2D frame
+    let flat: Vec<Vector2<f32>> = points
+        .iter()
+        .map(|point| vec2(point.dot(u_axis), point.dot(v_axis)))
+        .collect();
+
+    let cross = |o: Vector2<f32>, a: Vector2<f32>, b: Vector2<f32>| {
+        (a.x - o.x) * (b.y - o.y) - (a.y - o.y) * (b.x - o.x)
+    };
+    let mut remaining: Vec<usize> = (0..count).collect();
+    let mut triangles = Vec::with_capacity(count - 2);
+    while remaining.len() > 3 {
+        let n = remaining.len();
+        let ear = (0..n).find(|&i| {
+            let (prev, current, next) = (
+                remaining[(i + n - 1) % n],
+                remaining[i],
+                remaining[(i + 1) % n],
+            );
+            let (a, b, c) = (flat[prev], flat[current], flat[next]);
+            if cross(a, b, c) <= 0.0 {
+                // reflex corner
+                return false;
+            }
+            remaining.iter().all(|&other| {
+                other == prev
+                    || other == current
+                    || other == next
+                    || cross(a, b, flat[other]) < 0.0
+                    || cross(b, c, flat[other]) < 0.0
+                    || cross(c, a, flat[other]) < 0.0
+            })
+        });
+        match ear {
+            Some(i) => {
+                triangles.push([
+                    remaining[(i + n - 1) % n],
+                    remaining[i],
+                    remaining[(i + 1) % n],
+                ]);
+                remaining.remove(i);
+            }
+            // self-intersecting or collinear leftovers
+            None => return fan(),
+        }
+    }
+    triangles.push([remaining[0], remaining[1], remaining[2]]);
+    triangles
+}
+
+fn build_mesh(
+    builder: MeshBuilder,
+    positions: &[Vector3<f32>],
+    uvs: &[Vector2<f32>],
+    normals: &[Vector3<f32>],
+) -> ObjMesh {
+    let face_normal = |triangle: &Triangle| {
+        let [a, b, c] = triangle.corners;
+        let (a, b, c) = (
+            positions[a.position],
+            positions[b.position],
+            positions[c.position],
+        );
+        (b - a).cross(c - a)
+    };
+
+    // smooth normals are summed per position and smoothing group, weighted by the angle of
+    // each corner so the result doesn't depend on how polygons were triangulated
+    let mut smooth: HashMap<(usize, u32), Vector3<f32>> = HashMap::new();
+    for triangle in &builder.triangles {
+        let normal = face_normal(triangle);
+        if triangle.smoothing == 0 || normal.magnitude2() == 0.0 {
+            continue;
+        }
+        let normal = normal.normalize();
+        for (i, corner) in triangle.corners.iter().enumerate() {
+            if corner.normal.is_some() {
+                continue;
+            }
+            let point = positions[corner.position];
+            let next = positions[triangle.corners[(i + 1) % 3].position] - point;
+            let prev = positions[triangle.corners[(i + 2) % 3].position] - point;
+            let angle = next.angle(prev).0;
+            *smooth
+                .entry((corner.position, triangle.smoothing))
+                .or_insert_with(Vector3::zero) += normal * angle;
+        }
+    }
+
+    let mut data = MeshData::default();
+    let mut shared: HashMap<(usize, Option<usize>, NormalSource), u32> = HashMap::new();
+    for triangle in &builder.triangles {
+        for corner in triangle.corners.iter() {
+            let source = match corner.normal {
+                Some(normal) => NormalSource::File(normal),
+                None if triangle.smoothing != 0 => NormalSource::Smooth(triangle.smoothing),
+                None => NormalSource::Flat(triangle.face),
+            };
+            let index = *shared
+                .entry((corner.position, corner.uv, source))
+                .or_insert_with(|| {
+                    let normal = match source {
+                        NormalSource::File(normal) => normals[normal],
+                        NormalSource::Smooth(group) => smooth[&(corner.position, group)],
+                        NormalSource::Flat(_) => face_normal(triangle),
+                    };
+                    data.vertices.push(Vertex {
+                        position: positions[corner.position],
+                        normal: if normal.magnitude2() > 0.0 {
+                            normal.normalize()
+                        } else {
+                            Vector3::unit_y()
+                        },
+                        uv: corner.uv.map_or(Vector2::zero(), |uv| uvs[uv]),
+                        ..Vertex::default()
+                    });
+                    data.vertices.len() as u32 - 1
+                });
+            data.indices.push(index);
+        }
+    }
+    data.compute_tangents();
+    ObjMesh {
+        object: builder.object,
+        group: builder.group,
+        material: builder.material,
+        data,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CUBE: &str = "
+# unit cube, quads, no normals
+mtllib cube.mtl
+o Cube
+v -0.5 -0.5  0.5
+v  0.5 -0.5  0.5
+v  0.5  0.5  0.5
+v -0.5  0.5  0.5
+v -0.5 -0.5 -0.5
+v  0.5 -0.5 -0.5
+v  0.5  0.5 -0.5
+v -0.5  0.5 -0.5
+usemtl red
+s off
+f 1 2 3 4
+f 6 5 8 7
+f 5 1 4 8
+usemtl blue
+f 2 6 7 3
+f 4 3 7 8
+f 5 6 2 1
+";
+
+    #[test]
+    fn quads_are_split_by_material() {
+        let model = parse_obj(CUBE).unwrap();
+        assert_eq!(model.material_libs, vec!["cube.mtl"]);
+        assert_eq!(model.meshes.len(), 2);
+        for mesh in &model.meshes {
+            assert_eq!(mesh.object, "Cube");
+            assert_eq!(mesh.data.triangle_count(), 6);
+            // flat shading: every face keeps its own 4 corners
+            assert_eq!(mesh.data.vertex_count(), 12);
+        }
+        assert_eq!(model.meshes[0].material.as_deref(), Some("red"));
+        assert_eq!(model.meshes[1].material.as_deref(), Some("blue"));
+        for mesh in &model.meshes {
+            for vertex in &mesh.data.vertices {
+                // outward facing
+                assert!(vertex.position.dot(vertex.normal) > 0.0);
+            }
+        }
+    }
+
+    #[test]
+    fn smoothing_groups_share_normals() {
+        let text = CUBE.replace("s off", "s 1").replace("usemtl blue\n", "");
+        let model = parse_obj(&text).unwrap();
+        let mesh = &model.meshes[0].data;
+        assert_eq!(mesh.triangle_count(), 12);
+        assert_eq!(mesh.vertex_count(), 8);
+        for vertex in &mesh.vertices {
+            // corner normals point along the diagonal
+            let diagonal = vertex.position.normalize();
+            assert!(vertex.normal.dot(diagonal) > 0.99);
+        }
+    }
+
+    #[test]
+    fn negative_indices_and_normals() {
+        let text = "
+v 0 0 0
+v 1 0 0
+v 1 1 0
+vt 0 0
+vt 1 0
+vt 1 1
+vn 0 0 1
+g first second
+f -3/-3/-1 -2/-2/-1 -1/-1/-1
+f 1//1 2//1 3//1
+";
+        let model = parse_obj(text).unwrap();
+        assert_eq!(model.meshes.len(), 1);
+        let mesh = &model.meshes[0];
+        assert_eq!(mesh.group, "first second");
+        // the second face has no UVs, so it can't share the first face's vertices
+        assert_eq!(mesh.data.vertex_count(), 6);
+        assert_eq!(mesh.data.vertices[2].uv, vec2(1.0, 1.0));
+        assert!(mesh
+            .data
+            .vertices
+            .iter()
+            .all(|vertex| vertex.normal == Vector3::unit_z()));
+    }
+
+    #[test]
+    fn errors_name_the_line() {
+        let err = parse_obj("v 0 0 0\nv 1 0 0\nf 1 2 5\n").unwrap_err();
+        assert!(err.starts_with("line 3:"), "{}", err);
+        let err = parse_obj("v 0 0 zero\n").unwrap_err();
+        assert!(err.starts_with("line 1:"), "{}", err);
+    }
+
+    #[test]
+    fn concave_polygon_is_triangulated_inside() {
+        // an L shape, the fan from vertex 0 would cover the notch
+        let points: Vec<Vector3<f32>> = [
+            (2.0, 1.0),
+            (1.0, 1.0),
+            (1.0, 2.0),
+            (0.0, 2.0),
+            (0.0, 0.0),
+            (2.0, 0.0),
+        ]
+        .iter()
+        .map(|&(x, y)| vec3(x, y, 0.0))
+        .collect();
+        let triangles = triangulate(&points);
+        assert_eq!(triangles.len(), 4);
+        let mut area = 0.0;
+        for [a, b, c] in triangles {
+            let normal = (points[b] - points[a]).cross(points[c] - points[a]);
+            // same winding as the polygon
+            assert!(normal.z > 0.0);
+            area += normal.z / 2.0;
+        }
+        assert!((area - 3.0f32).abs() < 1e-5);
+    }
+
+    #[test]
+    fn mtl_materials() {
+        let text = "
+newmtl red
+Kd 1 0 0
+Ns 10
+d 0.5
+map_Kd textures/red.png
+map_Bump -bm 0.5 red_normal.png
+map_Ks -s 2 2 -clamp on red specular.png
+newmtl grey
+Ka 0.2
+Tr 0.25
+";
+        let materials = parse_mtl(text, Path::new("models")).unwrap();
+        assert_eq!(materials.len(), 2);
+        let red = &materials[0];
+        assert_eq!(red.diffuse, [1.0, 0.0, 0.0]);
+        assert_eq!(red.shininess, 10.0);
+        assert_eq!(red.opacity, 0.5);
+        assert_eq!(
+            red.diffuse_map.as_deref(),
+            Some(Path::new("models/textures/red.png"))
+        );
+        assert_eq!(
+            red.normal_map.as_deref(),
+            Some(Path::new("models/red_normal.png"))
+        );
+        assert_eq!(
+            red.specular_map.as_deref(),
+            Some(Path::new("models/red specular.png"))
+        );
+        assert_eq!(materials[1].ambient, [0.2; 3]);
+        assert_eq!(materials[1].opacity, 0.75);
+        assert!(parse_mtl("Kd 1 1 1\n", Path::new("")).is_err());
+    }
+}
@@ -0,0 +1,177 @@
+use cgmath::prelude::*;
+use cgmath::{perspective, vec3, Deg, Matrix4, Point3, Quaternion, Rad, Vector3};
+
+use glfw::{Action, MouseButton, WindowEvent};
+
+use crate::bounds::Aabb;
+use crate::camera::CameraView;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Drag {
+    /// arcball rotation, remembers the point on the ball where the last cursor position was
+    Rotate(Vector3<f32>),
+    Pan,
+}
+
+/// Camera orbiting a target point, for inspecting a single model.
+///
+/// Left-drag rotates with an arcball (rotations are accumulated in a quaternion, so there is no
+/// gimbal lock and the camera can go over the poles), right-drag pans the target in the view
+/// plane and the scroll wheel dollies between `min_distance` and `max_distance`.
+pub struct OrbitCamera {
+    pub target: Point3<f32>,
+    pub distance: f32,
+    pub min_distance: f32,
+    pub max_distance: f32,
+    /// orientation of the camera in world space
+    pub rotation: Quaternion<f32>,
+    /// vertical field of view in degrees
+    pub fovy: f32,
+    pub near: f32,
+    pub far: f32,
+    /// fraction of the distance moved per scroll step
+    pub dolly_speed: f32,
+    drag: Option<Drag>,
+    cursor: (f64, f64),
+}
+
+impl Default for OrbitCamera {
+    fn default() -> OrbitCamera {
+        OrbitCamera::new(Point3::new(0.0, 0.0, 0.0), 3.0)
+    }
+}
+
+impl OrbitCamera {
+    pub fn new(target: Point3<f32>, distance: f32) -> OrbitCamera {
+        OrbitCamera {
+            target,
+            distance,
+            min_distance: 0.1,
+            max_distance: 100.0,
+            rotation: Quaternion::one(),
+            fovy: 45.0,
+            near: 0.1,
+            far: 100.0,
+            dolly_speed: 0.1,
+            drag: None,
+            cursor: (0.0, 0.0),
+        }
+    }
+
+    /// Feed mouse events; `window_size` is in screen coordinates like the cursor position.
+    /// ------------------------------------------------------------------------
+    pub fn handle_event(&mut self, event: &WindowEvent, window_size: (i32, i32)) {
+        match *event {
+            WindowEvent::MouseButton(MouseButton::Button1, Action::Press, _) => {
+                let (x, y) = self.cursor;
+                self.drag = Some(Drag::Rotate(arcball_point(x, y, window_size)));
+            }
+            WindowEvent::MouseButton(MouseButton::Button2, Action::Press, _) => {
+                self.drag = Some(Drag::Pan)
+            }
+            WindowEvent::MouseButton(MouseButton::Button1, Action::Release, _)
+            | WindowEvent::MouseButton(MouseButton::Button2, Action::Release, _) => {
+                self.drag = None
+            }
+            WindowEvent::CursorPos(x, y) => {
+                let (last_x, last_y) = self.cursor;
+                self.cursor = (x, y);
+                match self.drag {
+                    Some(Drag::Rotate(from)) => {
+                        let to = arcball_point(x, y, window_size);
+                        self.rotate(from, to);
+                        self.drag = Some(Drag::Rotate(to));
+                    }
+                    Some(Drag::Pan) => {
+                        self.pan((x - last_x) as f32, (y - last_y) as f32, window_size.1)
+                    }
+                    None => {}
+                }
+            }
+            WindowEvent::Scroll(_, yoffset) => self.dolly(yoffset as f32),
+            _ => {}
+        }
+    }
+
+    /// Rotate as if the scene was grabbed at `from` and dragged to `to`, both points on the
+    /// unit arcball in view space.
+    /// ------------------------------------------------------------------------
+    pub fn rotate(&mut self, from: Vector3<f32>, to: Vector3<f32>) {
+        let axis = from.cross(to);
+        if axis.magnitude2() < 1e-12 {
+            return;
+        }
+        let angle = Rad(from.dot(to).clamp(-1.0, 1.0).acos());
+        // the ball turns the scene, so the camera turns the opposite way around the target
+        let spin = Quaternion::from_axis_angle(axis.normalize(), -angle);
+        self.rotation = (self.rotation * spin).normalize();
+    }
+
+    /// Move the target in the view plane; offsets are in screen coordinates, y down.
+    /// ------------------------------------------------------------------------
+    pub fn pan(&mut self, dx: f32, dy: f32, viewport_height: i32) {
+        // world units per screen unit at the target's depth, so the target follows the cursor
+        let half_height = self.distance * (Deg(self.fovy) / 2.0).tan();
+        let scale = 2.0 * half_height / viewport_height.max(1) as f32;
+        let right = self.rotation.rotate_vector(Vector3::unit_x());
+        let up = self.rotation.rotate_vector(Vector3::unit_y());
+        self.target += (up * dy - right * dx) * scale;
+    }
+
+    /// Move towards (positive steps) or away from the target.
+    /// ------------------------------------------------------------------------
+    pub fn dolly(&mut self, steps: f32) {
+        let factor = (1.0 - self.dolly_speed).powf(steps);
+        self.distance = (self.distance * factor)
+            .max(self.min_distance)
+            .min(self.max_distance);
+    }
+
+    /// Centre the target on the box and move back until its bounding sphere fits the view.
+    /// ------------------------------------------------------------------------
+    pub fn frame(&mut self, bounds: &Aabb, aspect: f32) {
+        self.target = bounds.center();
+        let radius = bounds.extents().magnitude();
+        // the narrower of the two field of view angles decides
+        let half_fovy = Deg(self.fovy) / 2.0;
+        let half_fovx = Rad((half_fovy.tan() * aspect).atan());
+        let half_fov = if aspect < 1.0 {
+            half_fovx
+        } else {
+            half_fovy.into()
+        };
+        let distance = radius / half_fov.sin();
+        self.max_distance = self.max_distance.max(distance);
+        self.distance = distance.max(self.min_distance);
+        self.far = self.far.max(distance + radius * 2.0);
+    }
+}
+
+impl CameraView for OrbitCamera {
+    fn position(&self) -> Point3<f32> {
+        self.target + self.rotation.rotate_vector(vec3(0.0, 0.0, self.distance))
+    }
+
+    fn view_matrix(&self) -> Matrix4<f32> {
+        Matrix4::from(self.rotation.invert()) * Matrix4::from_translation(-self.position().to_vec())
+    }
+
+    fn projection(&self, aspect: f32) -> Matrix4<f32> {
+        perspective(Deg(self.fovy), aspect, self.near, self.far)
+    }
+}
+
+/// Map a cursor position to the arcball: a unit sphere over the viewport, points outside the
+/// ball are pulled onto its rim.
+fn arcball_point(x: f64, y: f64, window_size: (i32, i32)) -> Vector3<f32> {
+    let (width, height) = (window_size.0.max(1) as f32, window_size.1.max(1) as f32);
+    let radius = width.min(height) / 2.0;
+    let px = (x as f32 - width / 2.0) / radius;
+    let py = (height / 2.0 - y as f32) / radius;
+    let length2 = px * px + py * py;
+    if length2 <= 1.0 {
+        vec3(px, py, (1.0 - length2).sqrt())
+    } else {
+        vec3(px, py, 0.0).normalize()
+    }
+}
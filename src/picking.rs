@@ -0,0 +1,124 @@
+use std::rc::Rc;
+
+use cgmath::Matrix4;
+
+use crate::framebuffer::IdTarget;
+use crate::scene::{NodeId, Scene};
+use crate::shader::Shader;
+use crate::surface::{ResizeListener, Surface};
+
+/// Picks scene nodes on the GPU: the scene's draw list is drawn into an `R32UI` target with
+/// one ID per node and the pixel under the cursor is read back. Unlike ray casting this
+/// sees exactly what the vertex shaders drew, skinned or displaced geometry included.
+pub struct IdPicker {
+    target: IdTarget,
+    shader: Rc<Shader>,
+}
+
+impl IdPicker {
+    /// Create the ID target at the framebuffer size; needs a current GL context.
+    /// ------------------------------------------------------------------------
+    pub fn new(surface: &Surface) -> IdPicker {
+        let (width, height) = surface.framebuffer_size;
+        IdPicker {
+            target: IdTarget::new(width, height),
+            shader: Rc::new(Shader::new(
+                "src/shaders/object_id.vs",
+                "src/shaders/object_id.fs",
+            )),
+        }
+    }
+
+    /// Node drawn at `cursor` (window coordinates) by a camera with `view` and `projection`,
+    /// using the same culled draw list as `Scene::draw`. Leaves the default framebuffer
+    /// bound; the read back waits for the GPU.
+    /// ------------------------------------------------------------------------
+    pub unsafe fn pick(
+        &self,
+        scene: &Scene,
+        view: &Matrix4<f32>,
+        projection: &Matrix4<f32>,
+        surface: &Surface,
+        cursor: (f64, f64),
+    ) -> Option<NodeId> {
+        let (x, y) = cursor_pixel(cursor, surface.window_size, surface.framebuffer_size)?;
+        let (nodes, _) = scene.view_list(view, projection);
+        self.target.bind();
+        // the ID of a blended or multisampled edge would be meaningless
+        gl::Disable(gl::BLEND);
+        gl::Enable(gl::DEPTH_TEST);
+        scene.draw_ids(&nodes, view, projection, &self.shader);
+        let id = self.target.read(x, y);
+        gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        node_for_id(&nodes, id)
+    }
+}
+
+impl ResizeListener for IdPicker {
+    fn on_resize(&mut self, surface: &Surface) {
+        self.target.on_resize(surface);
+    }
+}
+
+/// Framebuffer pixel under a cursor position: window coordinates are scaled to framebuffer
+/// pixels (they differ on high DPI screens) and flipped so y points up. `None` outside the
+/// window.
+/// ------------------------------------------------------------------------
+pub fn cursor_pixel(
+    cursor: (f64, f64),
+    window_size: (i32, i32),
+    framebuffer_size: (i32, i32),
+) -> Option<(i32, i32)> {
+    if window_size.0 <= 0 || window_size.1 <= 0 {
+        return None;
+    }
+    let x = (cursor.0 * framebuffer_size.0 as f64 / window_size.0 as f64).floor() as i32;
+    let y = (cursor.1 * framebuffer_size.1 as f64 / window_size.1 as f64).floor() as i32;
+    let inside = (0..framebuffer_size.0).contains(&x) && (0..framebuffer_size.1).contains(&y);
+    inside.then(|| (x, framebuffer_size.1 - 1 - y))
+}
+
+/// The node an ID written by `Scene::draw_ids` stands for, `None` for the background.
+pub fn node_for_id(nodes: &[NodeId], id: u32) -> Option<NodeId> {
+    let index = (id as usize).checked_sub(1)?;
+    nodes.get(index).copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene::Transform;
+
+    #[test]
+    fn cursor_maps_to_framebuffer_pixels() {
+        assert_eq!(
+            cursor_pixel((0.0, 0.0), (800, 600), (800, 600)),
+            Some((0, 599))
+        );
+        assert_eq!(
+            cursor_pixel((799.9, 599.9), (800, 600), (800, 600)),
+            Some((799, 0))
+        );
+        // a 2x scaled screen has twice the pixels under the same cursor position
+        assert_eq!(
+            cursor_pixel((400.5, 300.25), (800, 600), (1600, 1200)),
+            Some((801, 1199 - 600))
+        );
+        assert_eq!(cursor_pixel((-1.0, 10.0), (800, 600), (800, 600)), None);
+        assert_eq!(cursor_pixel((10.0, 600.0), (800, 600), (800, 600)), None);
+        // minimised
+        assert_eq!(cursor_pixel((10.0, 10.0), (0, 0), (0, 0)), None);
+    }
+
+    #[test]
+    fn ids_index_the_draw_list() {
+        let mut scene = Scene::new();
+        let a = scene.add_node("a", Transform::default(), None);
+        let b = scene.add_node("b", Transform::default(), None);
+        let nodes = [b, a];
+        assert_eq!(node_for_id(&nodes, 0), None);
+        assert_eq!(node_for_id(&nodes, 1), Some(b));
+        assert_eq!(node_for_id(&nodes, 2), Some(a));
+        assert_eq!(node_for_id(&nodes, 3), None);
+    }
+}
@@ -0,0 +1,559 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use cgmath::prelude::*;
+use cgmath::Vector3;
+
+use crate::config::ConfigError;
+use crate::mesh::{MeshData, Vertex};
+use crate::obj::triangulate;
+
+/// Encoding of a PLY file body.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlyFormat {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+/// Read a PLY file (ASCII or binary) into a mesh. Vertex colours are kept, polygons are
+/// triangulated, normals are computed when the file has none and the result is reordered
+/// for the vertex cache.
+/// ------------------------------------------------------------------------
+pub fn load_ply<P: AsRef<Path>>(path: P) -> Result<MeshData, ConfigError> {
+    let path = path.as_ref();
+    let bytes = fs::read(path).map_err(|err| ConfigError::Io(path.to_path_buf(), err))?;
+    let mut mesh = parse_ply(&bytes).map_err(|err| ConfigError::Parse(path.to_path_buf(), err))?;
+    mesh.optimize();
+    Ok(mesh)
+}
+
+/// Write `mesh` as PLY with positions, normals, UVs (`s`, `t`) and 8-bit RGBA colours.
+/// ------------------------------------------------------------------------
+pub fn save_ply<P: AsRef<Path>>(
+    path: P,
+    mesh: &MeshData,
+    format: PlyFormat,
+) -> Result<(), ConfigError> {
+    let path = path.as_ref();
+    let io_error = |err| ConfigError::Io(path.to_path_buf(), err);
+    let mut out = BufWriter::new(File::create(path).map_err(io_error)?);
+    write_ply(&mut out, mesh, format).map_err(io_error)?;
+    out.flush().map_err(io_error)
+}
+
+pub fn write_ply<W: Write>(out: &mut W, mesh: &MeshData, format: PlyFormat) -> io::Result<()> {
+    let format_name = match format {
+        PlyFormat::Ascii => "ascii",
+        PlyFormat::BinaryLittleEndian => "binary_little_endian",
+        PlyFormat::BinaryBigEndian => "binary_big_endian",
+    };
+    writeln!(out, "ply")?;
+    writeln!(out, "format {} 1.0", format_name)?;
+    writeln!(out, "comment written by LearnOpenGL_rs")?;
+    writeln!(out, "element vertex {}", mesh.vertex_count())?;
+    for name in ["x", "y", "z", "nx", "ny", "nz", "s", "t"].iter() {
+        writeln!(out, "property float {}", name)?;
+    }
+    for name in ["red", "green", "blue", "alpha"].iter() {
+        writeln!(out, "property uchar {}", name)?;
+    }
+    writeln!(out, "element face {}", mesh.triangle_count())?;
+    writeln!(out, "property list uchar uint vertex_indices")?;
+    writeln!(out, "end_header")?;
+
+    let color = |c: f32| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+    for vertex in &mesh.vertices {
+        let floats = [
+            vertex.position.x,
+            vertex.position.y,
+            vertex.position.z,
+            vertex.normal.x,
+            vertex.normal.y,
+            vertex.normal.z,
+            vertex.uv.x,
+            vertex.uv.y,
+        ];
+        let bytes = [
+            color(vertex.color.x),
+            color(vertex.color.y),
+            color(vertex.color.z),
+            color(vertex.color.w),
+        ];
+        match format {
+            PlyFormat::Ascii => {
+                for value in floats.iter() {
+                    write!(out, "{} ", value)?;
+                }
+                writeln!(out, "{} {} {} {}", bytes[0], bytes[1], bytes[2], bytes[3])?;
+            }
+            PlyFormat::BinaryLittleEndian => {
+                for value in floats.iter() {
+                    out.write_all(&value.to_le_bytes())?;
+                }
+                out.write_all(&bytes)?;
+            }
+            PlyFormat::BinaryBigEndian => {
+                for value in floats.iter() {
+                    out.write_all(&value.to_be_bytes())?;
+                }
+                out.write_all(&bytes)?;
+            }
+        }
+    }
+    for [a, b, c] in mesh.triangles() {
+        match format {
+            PlyFormat::Ascii => writeln!(out, "3 {} {} {}", a, b, c)?,
+            PlyFormat::BinaryLittleEndian => {
+                out.write_all(&[3])?;
+                for index in [a, b, c].iter() {
+                    out.write_all(&index.to_le_bytes())?;
+                }
+            }
+            PlyFormat::BinaryBigEndian => {
+                out.write_all(&[3])?;
+                for index in [a, b, c].iter() {
+                    out.write_all(&index.to_be_bytes())?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// scalar types of PLY properties, under their old and their sized names
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Scalar {
+    fn parse(name: &str) -> Option<Scalar> {
+        Some(match name {
+            "char" | "int8" => Scalar::I8,
+            "uchar" | "uint8" => Scalar::U8,
+            "short" | "int16" => Scalar::I16,
+            "ushort" | "uint16" => Scalar::U16,
+            "int" | "int32" => Scalar::I32,
+            "uint" | "uint32" => Scalar::U32,
+            "float" | "float32" => Scalar::F32,
+            "double" | "float64" => Scalar::F64,
+            _ => return None,
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Scalar::I8 | Scalar::U8 => 1,
+            Scalar::I16 | Scalar::U16 => 2,
+            Scalar::I32 | Scalar::U32 | Scalar::F32 => 4,
+            Scalar::F64 => 8,
+        }
+    }
+
+    /// the value that means full intensity for a colour channel of this type
+    fn color_scale(self) -> f64 {
+        match self {
+            Scalar::U8 | Scalar::I8 => 255.0,
+            Scalar::U16 | Scalar::I16 => 65535.0,
+            Scalar::U32 | Scalar::I32 => u32::MAX as f64,
+            Scalar::F32 | Scalar::F64 => 1.0,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Property {
+    Scalar(String, Scalar),
+    /// name, type of the length, type of the items
+    List(String, Scalar, Scalar),
+}
+
+#[derive(Debug)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+/// Parse a whole PLY file. Errors name the header line or the element that is broken.
+/// ------------------------------------------------------------------------
+pub fn parse_ply(bytes: &[u8]) -> Result<MeshData, String> {
+    let (format, elements, body) = parse_header(bytes)?;
+    let mut values = Values {
+        format,
+        bytes: &bytes[body..],
+        position: 0,
+    };
+
+    let mut vertices: Vec<Vertex> = Vec::new();
+    let mut polygons: Vec<Vec<u32>> = Vec::new();
+    let mut has_normals = false;
+    for element in &elements {
+        let mut slots = Vec::new();
+        if element.name == "vertex" {
+            for property in &element.properties {
+                slots.push(match property {
+                    Property::Scalar(name, ty) => vertex_slot(name, *ty),
+                    Property::List(..) => None,
+                });
+            }
+            has_normals = slots
+                .iter()
+                .filter(|slot| matches!(slot, Some((VertexSlot::Normal(_), _))))
+                .count()
+                == 3;
+            if slots
+                .iter()
+                .filter(|slot| matches!(slot, Some((VertexSlot::Position(_), _))))
+                .count()
+                < 3
+            {
+                return Err("vertex element needs x, y and z properties".to_string());
+            }
+        }
+        for number in 0..element.count {
+            let error = |message: String| format!("{} {}: {}", element.name, number, message);
+            let mut vertex = Vertex::default();
+            let mut polygon = None;
+            for (index, property) in element.properties.iter().enumerate() {
+                match property {
+                    Property::Scalar(_, ty) => {
+                        let value = values.read(*ty).map_err(error)?;
+                        if let Some(Some((slot, scale))) = slots.get(index) {
+                            slot.set(&mut vertex, (value / scale) as f32);
+                        }
+                    }
+                    Property::List(name, count_type, item_type) => {
+                        let count = values.read(*count_type).map_err(error)?;
+                        if count < 0.0 || count.fract() != 0.0 {
+                            return Err(error(format!("bad list length {}", count)));
+                        }
+                        let items = (0..count as usize)
+                            .map(|_| values.read(*item_type))
+                            .collect::<Result<Vec<f64>, String>>()
+                            .map_err(error)?;
+                        if element.name == "face"
+                            && (name == "vertex_indices" || name == "vertex_index")
+                        {
+                            polygon = Some(items);
+                        }
+                    }
+                }
+            }
+            if element.name == "vertex" {
+                vertices.push(vertex);
+            }
+            if let Some(polygon) = polygon {
+                polygons.push(polygon.into_iter().map(|index| index as u32).collect());
+            }
+        }
+    }
+    if values.format == PlyFormat::Ascii && values.next_token().is_some() {
+        println!("WARNING::PLY::TRAILING_DATA");
+    }
+
+    let mut indices = Vec::with_capacity(polygons.len() * 3);
+    for (number, polygon) in polygons.iter().enumerate() {
+        if let Some(index) = polygon
+            .iter()
+            .find(|&&index| index as usize >= vertices.len())
+        {
+            return Err(format!(
+                "face {}: vertex index {} out of range ({} vertices)",
+                number,
+                index,
+                vertices.len()
+            ));
+        }
+        let points: Vec<Vector3<f32>> = polygon
+            .iter()
+            .map(|&index| vertices[index as usize].position)
+            .collect();
+        for triangle in triangulate(&points) {
+            indices.extend(triangle.iter().map(|&corner| polygon[corner]));
+        }
+    }
+
+    let mut mesh = MeshData { vertices, indices };
+    if has_normals {
+        for vertex in mesh.vertices.iter_mut() {
+            if vertex.normal.magnitude2() > 0.0 {
+                vertex.normal = vertex.normal.normalize();
+            }
+        }
+    } else {
+        mesh.compute_smooth_normals();
+    }
+    mesh.compute_tangents();
+    Ok(mesh)
+}
+
+/// Split off and parse the header, returning the element layout and where the body starts.
+fn parse_header(bytes: &[u8]) -> Result<(PlyFormat, Vec<Element>, usize), String> {
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+    let mut position = 0;
+    let mut number = 0;
+    loop {
+        let end = bytes[position..]
+            .iter()
+            .position(|&byte| byte == b'\n')
+            .map(|end| position + end)
+            .ok_or_else(|| "header has no end_header line".to_string())?;
+        let line = String::from_utf8_lossy(&bytes[position..end]);
+        position = end + 1;
+        number += 1;
+        let error = |message: &str| format!("header line {}: {}", number, message);
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["ply"] if number == 1 => {}
+            _ if number == 1 => return Err("not a PLY file".to_string()),
+            ["format", name, _version] => {
+                format = Some(match *name {
+                    "ascii" => PlyFormat::Ascii,
+                    "binary_little_endian" => PlyFormat::BinaryLittleEndian,
+                    "binary_big_endian" => PlyFormat::BinaryBigEndian,
+                    _ => return Err(error(&format!("unknown format {}", name))),
+                });
+            }
+            ["comment", ..] | ["obj_info", ..] | [] => {}
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count
+                    .parse()
+                    .map_err(|_| error(&format!("bad element count {}", count)))?,
+                properties: Vec::new(),
+            }),
+            ["property", rest @ ..] => {
+                let element = elements
+                    .last_mut()
+                    .ok_or_else(|| error("property before any element"))?;
+                let scalar = |name: &str| {
+                    Scalar::parse(name).ok_or_else(|| error(&format!("unknown type {}", name)))
+                };
+                element.properties.push(match rest {
+                    ["list", count, item, name] => {
+                        Property::List(name.to_string(), scalar(count)?, scalar(item)?)
+                    }
+                    [ty, name] => Property::Scalar(name.to_string(), scalar(ty)?),
+                    _ => return Err(error("malformed property")),
+                });
+            }
+            ["end_header"] => break,
+            _ => return Err(error(&format!("unexpected '{}'", line.trim()))),
+        }
+    }
+    let format = format.ok_or_else(|| "header has no format line".to_string())?;
+    Ok((format, elements, position))
+}
+
+#[derive(Debug, Clone, Copy)]
+enum VertexSlot {
+    Position(usize),
+    Normal(usize),
+    Uv(usize),
+    Color(usize),
+}
+
+impl VertexSlot {
+    fn set(self, vertex: &mut Vertex, value: f32) {
+        match self {
+            VertexSlot::Position(i) => vertex.position[i] = value,
+            VertexSlot::Normal(i) => vertex.normal[i] = value,
+            VertexSlot::Uv(i) => vertex.uv[i] = value,
+            VertexSlot::Color(i) => vertex.color[i] = value,
+        }
+    }
+}
+
+/// where a vertex property goes, and what its values are divided by
+fn vertex_slot(name: &str, ty: Scalar) -> Option<(VertexSlot, f64)> {
+    let slot = match name {
+        "x" => VertexSlot::Position(0),
+        "y" => VertexSlot::Position(1),
+        "z" => VertexSlot::Position(2),
+        "nx" => VertexSlot::Normal(0),
+        "ny" => VertexSlot::Normal(1),
+        "nz" => VertexSlot::Normal(2),
+        "s" | "u" | "texture_u" => VertexSlot::Uv(0),
+        "t" | "v" | "texture_v" => VertexSlot::Uv(1),
+        "red" | "r" | "diffuse_red" => VertexSlot::Color(0),
+        "green" | "g" | "diffuse_green" => VertexSlot::Color(1),
+        "blue" | "b" | "diffuse_blue" => VertexSlot::Color(2),
+        "alpha" | "a" => VertexSlot::Color(3),
+        _ => return None,
+    };
+    let scale = match slot {
+        VertexSlot::Color(_) => ty.color_scale(),
+        _ => 1.0,
+    };
+    Some((slot, scale))
+}
+
+/// reads property values from the body in the file's encoding
+struct Values<'a> {
+    format: PlyFormat,
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Values<'a> {
+    fn read(&mut self, ty: Scalar) -> Result<f64, String> {
+        if self.format == PlyFormat::Ascii {
+            let token = self
+                .next_token()
+                .ok_or_else(|| "unexpected end of file".to_string())?;
+            return token
+                .parse::<f64>()
+                .map_err(|_| format!("'{}' is not a number", token));
+        }
+        let bytes = self
+            .bytes
+            .get(self.position..self.position + ty.size())
+            .ok_or_else(|| "unexpected end of file".to_string())?;
+        self.position += ty.size();
+        let big = self.format == PlyFormat::BinaryBigEndian;
+        macro_rules! number {
+            ($ty:ty) => {{
+                let array = bytes.try_into().unwrap();
+                (if big {
+                    <$ty>::from_be_bytes(array)
+                } else {
+                    <$ty>::from_le_bytes(array)
+                }) as f64
+            }};
+        }
+        Ok(match ty {
+            Scalar::I8 => bytes[0] as i8 as f64,
+            Scalar::U8 => bytes[0] as f64,
+            Scalar::I16 => number!(i16),
+            Scalar::U16 => number!(u16),
+            Scalar::I32 => number!(i32),
+            Scalar::U32 => number!(u32),
+            Scalar::F32 => number!(f32),
+            Scalar::F64 => number!(f64),
+        })
+    }
+
+    fn next_token(&mut self) -> Option<String> {
+        let rest = &self.bytes[self.position..];
+        let start = rest.iter().position(|byte| !byte.is_ascii_whitespace())?;
+        let length = rest[start..]
+            .iter()
+            .position(|byte| byte.is_ascii_whitespace())
+            .unwrap_or(rest.len() - start);
+        self.position += start + length;
+        Some(String::from_utf8_lossy(&rest[start..start + length]).into_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::vec4;
+
+    const SCAN: &str = "ply
+format ascii 1.0
+comment a quad and a triangle with colours
+element vertex 5
+property float x
+property float y
+property float z
+property uchar red
+property uchar green
+property uchar blue
+element face 2
+property uchar flags
+property list uchar int vertex_indices
+element edge 1
+property int vertex1
+property int vertex2
+end_header
+0 0 0 255 0 0
+1 0 0 0 255 0
+1 1 0 0 0 255
+0 1 0 255 255 255
+2 1 0 51 102 153
+0 4 0 1 2 3
+0 3 1 4 2
+0 1
+";
+
+    #[test]
+    fn ascii_polygons_and_colours() {
+        let mesh = parse_ply(SCAN.as_bytes()).unwrap();
+        assert_eq!(mesh.vertex_count(), 5);
+        assert_eq!(mesh.triangle_count(), 3);
+        assert_eq!(mesh.vertices[1].color, vec4(0.0, 1.0, 0.0, 1.0));
+        assert!((mesh.vertices[4].color - vec4(0.2, 0.4, 0.6, 1.0)).magnitude() < 1e-6);
+        // no normals in the file: computed, facing the counter-clockwise side
+        for vertex in &mesh.vertices {
+            assert!((vertex.normal - Vector3::unit_z()).magnitude() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        let mut mesh = MeshData::uv_sphere(1.5, 12, 6);
+        for (i, vertex) in mesh.vertices.iter_mut().enumerate() {
+            vertex.color = vec4((i % 5) as f32 / 4.0, 0.5, 1.0, 1.0);
+        }
+        for format in [
+            PlyFormat::Ascii,
+            PlyFormat::BinaryLittleEndian,
+            PlyFormat::BinaryBigEndian,
+        ] {
+            let mut bytes = Vec::new();
+            write_ply(&mut bytes, &mesh, format).unwrap();
+            let read = parse_ply(&bytes).unwrap();
+            assert_eq!(read.indices, mesh.indices, "{:?}", format);
+            for (a, b) in mesh.vertices.iter().zip(&read.vertices) {
+                assert!((a.position - b.position).magnitude() < 1e-5);
+                assert!((a.normal - b.normal).magnitude() < 1e-5);
+                assert_eq!(a.uv, b.uv);
+                assert!((a.color - b.color).magnitude() < 1.0 / 255.0);
+            }
+        }
+    }
+
+    #[test]
+    fn errors_name_the_element() {
+        let bad_index = SCAN.replace("0 3 1 4 2", "0 3 1 4 9");
+        assert_eq!(
+            parse_ply(bad_index.as_bytes()).unwrap_err(),
+            "face 1: vertex index 9 out of range (5 vertices)"
+        );
+        let bad_number = SCAN.replace("1 1 0 0 0 255", "1 1 zero 0 0 255");
+        assert_eq!(
+            parse_ply(bad_number.as_bytes()).unwrap_err(),
+            "vertex 2: 'zero' is not a number"
+        );
+        let bad_type = SCAN.replace("property uchar red", "property colour red");
+        assert_eq!(
+            parse_ply(bad_type.as_bytes()).unwrap_err(),
+            "header line 8: unknown type colour"
+        );
+
+        let mut bytes = Vec::new();
+        write_ply(
+            &mut bytes,
+            &MeshData::cube(1.0),
+            PlyFormat::BinaryLittleEndian,
+        )
+        .unwrap();
+        bytes.truncate(bytes.len() - 3);
+        assert_eq!(
+            parse_ply(&bytes).unwrap_err(),
+            "face 11: unexpected end of file"
+        );
+    }
+}
@@ -0,0 +1,236 @@
+use cgmath::prelude::*;
+use cgmath::{vec3, vec4, Matrix4, Point3, Vector3};
+
+use crate::bounds::Aabb;
+
+/// Half-line `origin + t * direction` for `t >= 0`. The direction doesn't have to be
+/// normalised; distances along the ray are in units of its length.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ray {
+    pub origin: Point3<f32>,
+    pub direction: Vector3<f32>,
+}
+
+/// Where a ray crossed a triangle: `barycentric` weighs the triangle's three corners, so the
+/// hit point (or any interpolated attribute) is `a * b.x + b * b.y + c * b.z`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TriangleHit {
+    /// index of the sub-mesh for drawables made of several meshes, 0 otherwise
+    pub mesh: usize,
+    /// triangle index, the corners are indices `3 * triangle ..= 3 * triangle + 2`
+    pub triangle: usize,
+    pub barycentric: Vector3<f32>,
+}
+
+/// A ray hit on something drawn: the distance along the ray and the triangle, when the
+/// drawable keeps its triangles for picking.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RayHit {
+    pub t: f32,
+    /// `None` when only the bounds were tested
+    pub triangle: Option<TriangleHit>,
+}
+
+impl Ray {
+    pub fn new(origin: Point3<f32>, direction: Vector3<f32>) -> Ray {
+        Ray { origin, direction }
+    }
+
+    /// Ray through a cursor position in window coordinates (origin top left, y down), from
+    /// the near plane towards the far plane of the camera. Works for perspective and
+    /// orthographic projections; the direction is normalised.
+    /// ------------------------------------------------------------------------
+    pub fn from_cursor(
+        cursor: (f64, f64),
+        window_size: (i32, i32),
+        view: &Matrix4<f32>,
+        projection: &Matrix4<f32>,
+    ) -> Ray {
+        let x = 2.0 * cursor.0 as f32 / window_size.0.max(1) as f32 - 1.0;
+        let y = 1.0 - 2.0 * cursor.1 as f32 / window_size.1.max(1) as f32;
+        let inverse = (projection * view)
+            .invert()
+            .unwrap_or_else(Matrix4::identity);
+        let unproject = |z: f32| Point3::from_homogeneous(inverse * vec4(x, y, z, 1.0));
+        let near = unproject(-1.0);
+        let far = unproject(1.0);
+        let direction = far - near;
+        // an infinite far plane unprojects to a point at infinity, go towards it instead
+        let direction = if direction.magnitude2().is_finite() {
+            direction
+        } else {
+            let far = inverse * vec4(x, y, 1.0, 0.0);
+            far.truncate()
+        };
+        Ray::new(near, direction.normalize())
+    }
+
+    pub fn at(&self, t: f32) -> Point3<f32> {
+        self.origin + self.direction * t
+    }
+
+    /// The ray in the space `transform` maps to. The direction isn't renormalised, so `t`
+    /// values stay the same in both spaces.
+    /// ------------------------------------------------------------------------
+    pub fn transformed(&self, transform: &Matrix4<f32>) -> Ray {
+        Ray {
+            origin: transform.transform_point(self.origin),
+            direction: transform.transform_vector(self.direction),
+        }
+    }
+
+    /// Distance to where the ray enters the box (slab test), 0 when it starts inside, `None`
+    /// when it misses or the box is empty.
+    /// ------------------------------------------------------------------------
+    pub fn intersect_aabb(&self, aabb: &Aabb) -> Option<f32> {
+        if aabb.is_empty() {
+            return None;
+        }
+        let mut near = 0.0f32;
+        let mut far = f32::INFINITY;
+        for axis in 0..3 {
+            let (origin, min, max) = (self.origin[axis], aabb.min[axis], aabb.max[axis]);
+            if self.direction[axis] == 0.0 {
+                // parallel to the slab, either always inside it or never
+                if origin < min || origin > max {
+                    return None;
+                }
+                continue;
+            }
+            let inverse = 1.0 / self.direction[axis];
+            let t0 = (min - origin) * inverse;
+            let t1 = (max - origin) * inverse;
+            near = near.max(t0.min(t1));
+            far = far.min(t0.max(t1));
+        }
+        Some(near).filter(|&near| near <= far)
+    }
+
+    /// Möller-Trumbore ray/triangle test, both sides count. Returns the distance and the
+    /// barycentric weights of `a`, `b` and `c`.
+    /// ------------------------------------------------------------------------
+    pub fn intersect_triangle(
+        &self,
+        a: Point3<f32>,
+        b: Point3<f32>,
+        c: Point3<f32>,
+    ) -> Option<(f32, Vector3<f32>)> {
+        let edge1 = b - a;
+        let edge2 = c - a;
+        let p = self.direction.cross(edge2);
+        let determinant = edge1.dot(p);
+        if determinant.abs() < 1e-12 {
+            // parallel to the triangle's plane
+            return None;
+        }
+        let inverse = 1.0 / determinant;
+        let offset = self.origin - a;
+        let u = offset.dot(p) * inverse;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+        let q = offset.cross(edge1);
+        let v = self.direction.dot(q) * inverse;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+        let t = edge2.dot(q) * inverse;
+        (t >= 0.0).then(|| (t, vec3(1.0 - u - v, u, v)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::{ortho, perspective, Deg};
+
+    fn close(a: Vector3<f32>, b: Vector3<f32>) -> bool {
+        (a - b).magnitude() < 1e-5
+    }
+
+    #[test]
+    fn triangles_report_barycentrics() {
+        let (a, b, c) = (
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(2.0, 0.0, 0.0),
+            Point3::new(0.0, 2.0, 0.0),
+        );
+        let down = -Vector3::unit_z();
+        let (t, weights) = Ray::new(Point3::new(0.5, 0.5, 3.0), down)
+            .intersect_triangle(a, b, c)
+            .unwrap();
+        assert!((t - 3.0).abs() < 1e-6);
+        assert!(close(weights, vec3(0.5, 0.25, 0.25)));
+        // the weights give back the hit point
+        let point = a.to_vec() * weights.x + b.to_vec() * weights.y + c.to_vec() * weights.z;
+        assert!(close(point, vec3(0.5, 0.5, 0.0)));
+
+        // from below, beside, behind and parallel
+        assert!(Ray::new(Point3::new(0.5, 0.5, -3.0), -down)
+            .intersect_triangle(a, b, c)
+            .is_some());
+        assert!(Ray::new(Point3::new(1.5, 1.5, 3.0), down)
+            .intersect_triangle(a, b, c)
+            .is_none());
+        assert!(Ray::new(Point3::new(0.5, 0.5, -3.0), down)
+            .intersect_triangle(a, b, c)
+            .is_none());
+        assert!(Ray::new(Point3::new(0.5, 0.5, 1.0), Vector3::unit_x())
+            .intersect_triangle(a, b, c)
+            .is_none());
+    }
+
+    #[test]
+    fn boxes_use_the_slab_test() {
+        let aabb = Aabb::new(Point3::new(-1.0, -1.0, -1.0), Point3::new(1.0, 1.0, 1.0));
+        let ray = Ray::new(Point3::new(-5.0, 0.5, 0.0), Vector3::unit_x());
+        assert_eq!(ray.intersect_aabb(&aabb), Some(4.0));
+        // inside, pointing away, axis aligned outside a slab, along a face
+        assert_eq!(
+            Ray::new(Point3::origin(), Vector3::unit_y()).intersect_aabb(&aabb),
+            Some(0.0)
+        );
+        assert_eq!(
+            Ray::new(Point3::new(5.0, 0.0, 0.0), Vector3::unit_x()).intersect_aabb(&aabb),
+            None
+        );
+        assert_eq!(
+            Ray::new(Point3::new(-5.0, 2.0, 0.0), Vector3::unit_x()).intersect_aabb(&aabb),
+            None
+        );
+        assert_eq!(
+            Ray::new(Point3::new(-5.0, 1.0, 0.0), Vector3::unit_x()).intersect_aabb(&aabb),
+            Some(4.0)
+        );
+        assert_eq!(ray.intersect_aabb(&Aabb::empty()), None);
+
+        // scaling the space keeps t
+        let scale = Matrix4::from_scale(3.0);
+        assert_eq!(
+            ray.transformed(&scale)
+                .intersect_aabb(&aabb.transformed(&scale)),
+            Some(4.0)
+        );
+    }
+
+    #[test]
+    fn cursor_rays_go_through_the_view() {
+        let eye = Point3::new(0.0, 0.0, 5.0);
+        let view = Matrix4::look_at_rh(eye, Point3::origin(), Vector3::unit_y());
+        let projection = perspective(Deg(90.0), 2.0, 0.5, 50.0);
+        let size = (800, 400);
+
+        let centre = Ray::from_cursor((400.0, 200.0), size, &view, &projection);
+        assert!(close(centre.direction, -Vector3::unit_z()));
+        assert!(close(centre.origin.to_vec(), vec3(0.0, 0.0, 4.5)));
+        // the top right corner runs along the frustum edge, 45 degrees up and 2:1 wide
+        let corner = Ray::from_cursor((800.0, 0.0), size, &view, &projection);
+        assert!(close(corner.direction, vec3(2.0, 1.0, -1.0).normalize()));
+
+        // orthographic rays are parallel
+        let flat = ortho(-4.0, 4.0, -2.0, 2.0, 0.5, 50.0);
+        let left = Ray::from_cursor((0.0, 200.0), size, &view, &flat);
+        assert!(close(left.direction, -Vector3::unit_z()));
+        assert!(close(left.origin.to_vec(), vec3(-4.0, 0.0, 4.5)));
+    }
+}
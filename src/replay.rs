@@ -0,0 +1,501 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::os::raw::c_void;
+use std::path::{Path, PathBuf};
+
+use gl::types::GLuint;
+use glfw::{Action, Modifiers, MouseButton, WindowEvent};
+
+use crate::config::ConfigError;
+use crate::gamepad::{GamepadInput, GAMEPAD_AXES};
+use crate::input::key_from_code;
+use crate::surface::Surface;
+
+const MAGIC: &[u8; 4] = b"LGLR";
+const VERSION: u32 = 1;
+
+/// One recorded frame: the real frame time, the window events delivered that frame, the
+/// gamepad state and a hash of the rendered image.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub delta: f64,
+    pub events: Vec<WindowEvent>,
+    pub gamepad: GamepadInput,
+    pub hash: u64,
+}
+
+/// Writes frames to a recording as they happen.
+///
+/// File layout (little endian): `LGLR`, version, framebuffer width and height, then per frame
+/// the raw delta time, the event count, the events, the gamepad buttons and axes and the frame
+/// hash.
+pub struct Recorder {
+    path: PathBuf,
+    out: BufWriter<File>,
+    frames: usize,
+}
+
+impl Recorder {
+    pub fn create<P: AsRef<Path>>(path: P, surface: &Surface) -> Result<Recorder, ConfigError> {
+        let path = path.as_ref().to_path_buf();
+        let file = File::create(&path).map_err(|err| ConfigError::Io(path.clone(), err))?;
+        let mut recorder = Recorder {
+            path,
+            out: BufWriter::new(file),
+            frames: 0,
+        };
+        let (width, height) = surface.framebuffer_size;
+        write_header(&mut recorder.out, width, height)
+            .map_err(|err| ConfigError::Io(recorder.path.clone(), err))?;
+        Ok(recorder)
+    }
+
+    /// Append a frame; `raw_delta` is the unscaled frame time from `FrameTimer::raw_delta`.
+    /// ------------------------------------------------------------------------
+    pub fn record_frame(
+        &mut self,
+        raw_delta: f64,
+        events: &[WindowEvent],
+        gamepad: &GamepadInput,
+        hash: u64,
+    ) -> Result<(), ConfigError> {
+        write_frame(&mut self.out, raw_delta, events, gamepad, hash)
+            .map_err(|err| ConfigError::Io(self.path.clone(), err))?;
+        self.frames += 1;
+        Ok(())
+    }
+
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    pub fn finish(mut self) -> Result<(), ConfigError> {
+        self.out
+            .flush()
+            .map_err(|err| ConfigError::Io(self.path.clone(), err))
+    }
+}
+
+/// A recording loaded back for playback, frame by frame.
+pub struct Replay {
+    /// framebuffer size the recording was made at; hashes only match at the same size
+    pub framebuffer_size: (i32, i32),
+    frames: Vec<Frame>,
+    next: usize,
+}
+
+impl Replay {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Replay, ConfigError> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|err| ConfigError::Io(path.to_path_buf(), err))?;
+        Replay::read(&mut BufReader::new(file)).map_err(|err| match err.kind() {
+            io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof => {
+                ConfigError::Parse(path.to_path_buf(), err.to_string())
+            }
+            _ => ConfigError::Io(path.to_path_buf(), err),
+        })
+    }
+
+    fn read<R: Read>(input: &mut R) -> io::Result<Replay> {
+        let mut magic = [0; 4];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("not an input recording"));
+        }
+        let version = read_u32(input)?;
+        if version != VERSION {
+            return Err(invalid(&format!(
+                "unsupported recording version {}",
+                version
+            )));
+        }
+        let framebuffer_size = (read_i32(input)?, read_i32(input)?);
+
+        let mut frames = Vec::new();
+        // a clean end of file can only happen between frames
+        while let Some(delta) = read_frame_start(input)? {
+            let count = read_u32(input)?;
+            let events = (0..count)
+                .map(|_| read_event(input))
+                .collect::<io::Result<Vec<_>>>()?;
+            let gamepad = read_gamepad(input)?;
+            let hash = read_u64(input)?;
+            frames.push(Frame {
+                delta,
+                events,
+                gamepad,
+                hash,
+            });
+        }
+        Ok(Replay {
+            framebuffer_size,
+            frames,
+            next: 0,
+        })
+    }
+
+    pub fn next_frame(&mut self) -> Option<&Frame> {
+        let frame = self.frames.get(self.next)?;
+        self.next += 1;
+        Some(frame)
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+}
+
+/// Hash of a framebuffer's colour buffer (FNV-1a over RGBA8 pixels). Call after the frame is
+/// complete. Read from `MsaaTarget::read_framebuffer` of an offscreen target: the default
+/// framebuffer of a hidden or covered window has undefined pixels.
+pub fn frame_hash(fbo: GLuint, width: i32, height: i32) -> u64 {
+    let mut pixels = vec![0u8; (width.max(0) * height.max(0) * 4) as usize];
+    if pixels.is_empty() {
+        return 0;
+    }
+    unsafe {
+        gl::BindFramebuffer(gl::READ_FRAMEBUFFER, fbo);
+        gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+        gl::ReadPixels(
+            0,
+            0,
+            width,
+            height,
+            gl::RGBA,
+            gl::UNSIGNED_BYTE,
+            pixels.as_mut_ptr() as *mut c_void,
+        );
+        gl::PixelStorei(gl::PACK_ALIGNMENT, 4);
+        gl::BindFramebuffer(gl::READ_FRAMEBUFFER, 0);
+    }
+    fnv1a(&pixels)
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+// event tags, in the order of the `WindowEvent` variants
+const POS: u8 = 0;
+const SIZE: u8 = 1;
+const CLOSE: u8 = 2;
+const REFRESH: u8 = 3;
+const FOCUS: u8 = 4;
+const ICONIFY: u8 = 5;
+const FRAMEBUFFER_SIZE: u8 = 6;
+const MOUSE_BUTTON: u8 = 7;
+const CURSOR_POS: u8 = 8;
+const CURSOR_ENTER: u8 = 9;
+const SCROLL: u8 = 10;
+const KEY: u8 = 11;
+const CHAR: u8 = 12;
+const CHAR_MODIFIERS: u8 = 13;
+const FILE_DROP: u8 = 14;
+const MAXIMIZE: u8 = 15;
+const CONTENT_SCALE: u8 = 16;
+
+fn write_header<W: Write>(out: &mut W, width: i32, height: i32) -> io::Result<()> {
+    out.write_all(MAGIC)?;
+    write_u32(out, VERSION)?;
+    write_i32(out, width)?;
+    write_i32(out, height)
+}
+
+fn write_frame<W: Write>(
+    out: &mut W,
+    raw_delta: f64,
+    events: &[WindowEvent],
+    gamepad: &GamepadInput,
+    hash: u64,
+) -> io::Result<()> {
+    write_f64(out, raw_delta)?;
+    write_u32(out, events.len() as u32)?;
+    for event in events {
+        write_event(out, event)?;
+    }
+    write_u32(out, gamepad.buttons)?;
+    for axis in gamepad.axes {
+        out.write_all(&axis.to_le_bytes())?;
+    }
+    write_u64(out, hash)
+}
+
+/// The delta time a frame starts with, `None` at the end of the file. Running out of bytes
+/// part way through is an error like anywhere else in a frame.
+fn read_frame_start<R: Read>(input: &mut R) -> io::Result<Option<f64>> {
+    let mut bytes = [0; 8];
+    let mut read = 0;
+    while read < bytes.len() {
+        match input.read(&mut bytes[read..]) {
+            Ok(0) if read == 0 => return Ok(None),
+            Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(count) => read += count,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(Some(f64::from_le_bytes(bytes)))
+}
+
+fn read_gamepad<R: Read>(input: &mut R) -> io::Result<GamepadInput> {
+    let mut gamepad = GamepadInput {
+        buttons: read_u32(input)?,
+        axes: [0.0; GAMEPAD_AXES],
+    };
+    for axis in gamepad.axes.iter_mut() {
+        *axis = read_f32(input)?;
+    }
+    Ok(gamepad)
+}
+
+fn write_event<W: Write>(out: &mut W, event: &WindowEvent) -> io::Result<()> {
+    match event {
+        WindowEvent::Pos(x, y) => write_tagged_pair(out, POS, *x, *y),
+        WindowEvent::Size(width, height) => write_tagged_pair(out, SIZE, *width, *height),
+        WindowEvent::Close => write_u8(out, CLOSE),
+        WindowEvent::Refresh => write_u8(out, REFRESH),
+        WindowEvent::Focus(focused) => {
+            write_u8(out, FOCUS)?;
+            write_u8(out, *focused as u8)
+        }
+        WindowEvent::Iconify(iconified) => {
+            write_u8(out, ICONIFY)?;
+            write_u8(out, *iconified as u8)
+        }
+        WindowEvent::FramebufferSize(width, height) => {
+            write_tagged_pair(out, FRAMEBUFFER_SIZE, *width, *height)
+        }
+        WindowEvent::MouseButton(button, action, modifiers) => {
+            write_u8(out, MOUSE_BUTTON)?;
+            write_i32(out, *button as i32)?;
+            write_i32(out, *action as i32)?;
+            write_i32(out, modifiers.bits())
+        }
+        WindowEvent::CursorPos(x, y) => {
+            write_u8(out, CURSOR_POS)?;
+            write_f64(out, *x)?;
+            write_f64(out, *y)
+        }
+        WindowEvent::CursorEnter(entered) => {
+            write_u8(out, CURSOR_ENTER)?;
+            write_u8(out, *entered as u8)
+        }
+        WindowEvent::Scroll(x, y) => {
+            write_u8(out, SCROLL)?;
+            write_f64(out, *x)?;
+            write_f64(out, *y)
+        }
+        WindowEvent::Key(key, scancode, action, modifiers) => {
+            write_u8(out, KEY)?;
+            write_i32(out, *key as i32)?;
+            write_i32(out, *scancode)?;
+            write_i32(out, *action as i32)?;
+            write_i32(out, modifiers.bits())
+        }
+        WindowEvent::Char(character) => {
+            write_u8(out, CHAR)?;
+            write_u32(out, *character as u32)
+        }
+        WindowEvent::CharModifiers(character, modifiers) => {
+            write_u8(out, CHAR_MODIFIERS)?;
+            write_u32(out, *character as u32)?;
+            write_i32(out, modifiers.bits())
+        }
+        WindowEvent::FileDrop(paths) => {
+            write_u8(out, FILE_DROP)?;
+            write_u32(out, paths.len() as u32)?;
+            for path in paths {
+                let path = path.to_string_lossy();
+                write_u32(out, path.len() as u32)?;
+                out.write_all(path.as_bytes())?;
+            }
+            Ok(())
+        }
+        WindowEvent::Maximize(maximized) => {
+            write_u8(out, MAXIMIZE)?;
+            write_u8(out, *maximized as u8)
+        }
+        WindowEvent::ContentScale(x, y) => {
+            write_u8(out, CONTENT_SCALE)?;
+            out.write_all(&x.to_le_bytes())?;
+            out.write_all(&y.to_le_bytes())
+        }
+    }
+}
+
+fn read_event<R: Read>(input: &mut R) -> io::Result<WindowEvent> {
+    Ok(match read_u8(input)? {
+        POS => WindowEvent::Pos(read_i32(input)?, read_i32(input)?),
+        SIZE => WindowEvent::Size(read_i32(input)?, read_i32(input)?),
+        CLOSE => WindowEvent::Close,
+        REFRESH => WindowEvent::Refresh,
+        FOCUS => WindowEvent::Focus(read_u8(input)? != 0),
+        ICONIFY => WindowEvent::Iconify(read_u8(input)? != 0),
+        FRAMEBUFFER_SIZE => WindowEvent::FramebufferSize(read_i32(input)?, read_i32(input)?),
+        MOUSE_BUTTON => {
+            let button = MouseButton::from_i32(read_i32(input)?)
+                .ok_or_else(|| invalid("unknown mouse button"))?;
+            WindowEvent::MouseButton(button, read_action(input)?, read_modifiers(input)?)
+        }
+        CURSOR_POS => WindowEvent::CursorPos(read_f64(input)?, read_f64(input)?),
+        CURSOR_ENTER => WindowEvent::CursorEnter(read_u8(input)? != 0),
+        SCROLL => WindowEvent::Scroll(read_f64(input)?, read_f64(input)?),
+        KEY => WindowEvent::Key(
+            key_from_code(read_i32(input)?),
+            read_i32(input)?,
+            read_action(input)?,
+            read_modifiers(input)?,
+        ),
+        CHAR => WindowEvent::Char(read_char(input)?),
+        CHAR_MODIFIERS => WindowEvent::CharModifiers(read_char(input)?, read_modifiers(input)?),
+        FILE_DROP => {
+            let count = read_u32(input)?;
+            let mut paths = Vec::new();
+            for _ in 0..count {
+                let mut bytes = vec![0; read_u32(input)? as usize];
+                input.read_exact(&mut bytes)?;
+                let path = String::from_utf8(bytes).map_err(|_| invalid("invalid path"))?;
+                paths.push(PathBuf::from(path));
+            }
+            WindowEvent::FileDrop(paths)
+        }
+        MAXIMIZE => WindowEvent::Maximize(read_u8(input)? != 0),
+        CONTENT_SCALE => WindowEvent::ContentScale(read_f32(input)?, read_f32(input)?),
+        tag => return Err(invalid(&format!("unknown event tag {}", tag))),
+    })
+}
+
+fn write_tagged_pair<W: Write>(out: &mut W, tag: u8, a: i32, b: i32) -> io::Result<()> {
+    write_u8(out, tag)?;
+    write_i32(out, a)?;
+    write_i32(out, b)
+}
+
+fn write_u8<W: Write>(out: &mut W, value: u8) -> io::Result<()> {
+    out.write_all(&[value])
+}
+
+fn write_u32<W: Write>(out: &mut W, value: u32) -> io::Result<()> {
+    out.write_all(&value.to_le_bytes())
+}
+
+fn write_i32<W: Write>(out: &mut W, value: i32) -> io::Result<()> {
+    out.write_all(&value.to_le_bytes())
+}
+
+fn write_u64<W: Write>(out: &mut W, value: u64) -> io::Result<()> {
+    out.write_all(&value.to_le_bytes())
+}
+
+fn write_f64<W: Write>(out: &mut W, value: f64) -> io::Result<()> {
+    out.write_all(&value.to_le_bytes())
+}
+
+fn read_bytes<R: Read, const N: usize>(input: &mut R) -> io::Result<[u8; N]> {
+    let mut bytes = [0; N];
+    input.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn read_u8<R: Read>(input: &mut R) -> io::Result<u8> {
+    Ok(read_bytes::<R, 1>(input)?[0])
+}
+
+fn read_u32<R: Read>(input: &mut R) -> io::Result<u32> {
+    read_bytes(input).map(u32::from_le_bytes)
+}
+
+fn read_i32<R: Read>(input: &mut R) -> io::Result<i32> {
+    read_bytes(input).map(i32::from_le_bytes)
+}
+
+fn read_u64<R: Read>(input: &mut R) -> io::Result<u64> {
+    read_bytes(input).map(u64::from_le_bytes)
+}
+
+fn read_f32<R: Read>(input: &mut R) -> io::Result<f32> {
+    read_bytes(input).map(f32::from_le_bytes)
+}
+
+fn read_f64<R: Read>(input: &mut R) -> io::Result<f64> {
+    read_bytes(input).map(f64::from_le_bytes)
+}
+
+fn read_char<R: Read>(input: &mut R) -> io::Result<char> {
+    char::from_u32(read_u32(input)?).ok_or_else(|| invalid("invalid character"))
+}
+
+fn read_action<R: Read>(input: &mut R) -> io::Result<Action> {
+    match read_i32(input)? {
+        0 => Ok(Action::Release),
+        1 => Ok(Action::Press),
+        2 => Ok(Action::Repeat),
+        _ => Err(invalid("unknown action")),
+    }
+}
+
+fn read_modifiers<R: Read>(input: &mut R) -> io::Result<Modifiers> {
+    Ok(Modifiers::from_bits_truncate(read_i32(input)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glfw::{GamepadButton, Key};
+
+    #[test]
+    fn events_round_trip() {
+        let events = vec![
+            WindowEvent::FramebufferSize(1600, 1200),
+            WindowEvent::Key(Key::W, 17, Action::Press, Modifiers::Shift),
+            WindowEvent::CursorPos(400.5, -12.25),
+            WindowEvent::MouseButton(MouseButton::Button2, Action::Release, Modifiers::empty()),
+            WindowEvent::Scroll(0.0, -1.0),
+            WindowEvent::CharModifiers('é', Modifiers::Control | Modifiers::Alt),
+            WindowEvent::FileDrop(vec![PathBuf::from("resources/textures/container.jpg")]),
+            WindowEvent::ContentScale(2.0, 2.0),
+            WindowEvent::Close,
+        ];
+        let mut bytes = Vec::new();
+        for event in &events {
+            write_event(&mut bytes, event).unwrap();
+        }
+        let mut input = &bytes[..];
+        for event in &events {
+            assert_eq!(&read_event(&mut input).unwrap(), event);
+        }
+        assert!(input.is_empty());
+    }
+
+    #[test]
+    fn truncated_recording_is_rejected() {
+        let mut bytes = Vec::new();
+        write_header(&mut bytes, 800, 600).unwrap();
+        let gamepad = GamepadInput {
+            buttons: 1 << GamepadButton::ButtonA as u32,
+            axes: [0.5, -0.25, 0.0, 0.0, 0.0, 1.0],
+        };
+        let events = [WindowEvent::Scroll(0.0, 1.0)];
+        write_frame(&mut bytes, 1.0 / 60.0, &events, &gamepad, 42).unwrap();
+
+        let mut replay = Replay::read(&mut &bytes[..]).unwrap();
+        assert_eq!(replay.framebuffer_size, (800, 600));
+        let frame = replay.next_frame().unwrap();
+        assert_eq!((frame.gamepad, frame.hash), (gamepad, 42));
+        assert!(replay.next_frame().is_none());
+
+        // cut in the hash, then in the next frame's delta time
+        let frame_end = bytes.len();
+        write_f64(&mut bytes, 1.0 / 60.0).unwrap();
+        for cut in [frame_end - 3, frame_end + 5] {
+            let error = Replay::read(&mut &bytes[..cut]).err().unwrap();
+            assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+        }
+    }
+}
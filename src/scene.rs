@@ -0,0 +1,762 @@
+use std::cell::Cell;
+use std::ffi::CStr;
+use std::rc::Rc;
+
+use cgmath::prelude::*;
+use cgmath::{vec4, Matrix4, Point3, Quaternion, Vector3};
+
+use crate::bounds::{Aabb, BoundingSphere};
+use crate::frustum::{CullStats, Frustum};
+use crate::gltf_scene::{GltfCamera, GltfModel, LightKind, NodeCamera};
+use crate::material::Material;
+use crate::mesh::Mesh;
+use crate::model::Model;
+use crate::ray::{Ray, RayHit};
+use crate::shader::Shader;
+
+/// Local transform of a node: scale first, then rotation, then translation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    pub translation: Vector3<f32>,
+    pub rotation: Quaternion<f32>,
+    pub scale: Vector3<f32>,
+}
+
+impl Default for Transform {
+    fn default() -> Transform {
+        Transform {
+            translation: Vector3::zero(),
+            rotation: Quaternion::one(),
+            scale: Vector3::new(1.0, 1.0, 1.0),
+        }
+    }
+}
+
+impl Transform {
+    #[cfg(test)]
+    pub fn from_translation(translation: Vector3<f32>) -> Transform {
+        Transform {
+            translation,
+            ..Transform::default()
+        }
+    }
+
+    pub fn matrix(&self) -> Matrix4<f32> {
+        Matrix4::from_translation(self.translation)
+            * Matrix4::from(self.rotation)
+            * Matrix4::from_nonuniform_scale(self.scale.x, self.scale.y, self.scale.z)
+    }
+}
+
+/// Anything a scene node can draw. The shader is in use with `view` and `projection` set;
+/// the drawable sets `model` itself, so drawables with their own hierarchy can append to it.
+pub trait Drawable {
+    unsafe fn draw(&self, shader: &Shader, model: &Matrix4<f32>);
+
+    /// bounds in the drawable's own space; empty bounds are never culled
+    fn bounds(&self) -> Aabb {
+        Aabb::empty()
+    }
+
+    /// sphere in the drawable's own space for the quick culling test, by default the one
+    /// around `bounds`
+    fn bounding_sphere(&self) -> BoundingSphere {
+        BoundingSphere::from(self.bounds())
+    }
+
+    /// Closest hit of a ray in the drawable's own space. By default only the bounds are
+    /// tested; drawables that keep their triangles report the one hit.
+    fn raycast(&self, ray: &Ray) -> Option<RayHit> {
+        ray.intersect_aabb(&self.bounds())
+            .map(|t| RayHit { t, triangle: None })
+    }
+}
+
+impl Drawable for Mesh {
+    unsafe fn draw(&self, shader: &Shader, model: &Matrix4<f32>) {
+        shader.setMat4(c_str!("model"), model);
+        Mesh::draw(self);
+    }
+
+    fn bounds(&self) -> Aabb {
+        self.bounds
+    }
+
+    fn bounding_sphere(&self) -> BoundingSphere {
+        self.sphere
+    }
+
+    fn raycast(&self, ray: &Ray) -> Option<RayHit> {
+        Mesh::raycast(self, ray)
+    }
+}
+
+impl Drawable for Model {
+    unsafe fn draw(&self, shader: &Shader, model: &Matrix4<f32>) {
+        shader.setMat4(c_str!("model"), model);
+        Model::draw(self, shader);
+    }
+
+    fn bounds(&self) -> Aabb {
+        self.bounds
+    }
+
+    /// closest hit over all meshes, `TriangleHit::mesh` indexes `Model::meshes`
+    fn raycast(&self, ray: &Ray) -> Option<RayHit> {
+        let hits = self.meshes.iter().enumerate().filter_map(|(index, part)| {
+            let mut hit = part.mesh.raycast(ray)?;
+            if let Some(triangle) = hit.triangle.as_mut() {
+                triangle.mesh = index;
+            }
+            Some(hit)
+        });
+        hits.min_by(|a, b| a.t.total_cmp(&b.t))
+    }
+}
+
+impl Drawable for GltfModel {
+    unsafe fn draw(&self, shader: &Shader, model: &Matrix4<f32>) {
+        GltfModel::draw(self, shader, model);
+    }
+
+    fn bounds(&self) -> Aabb {
+        self.bounds
+    }
+
+    fn raycast(&self, ray: &Ray) -> Option<RayHit> {
+        GltfModel::raycast(self, ray)
+    }
+}
+
+/// What a node draws: a drawable and the material to draw it with.
+#[derive(Clone)]
+pub struct Renderable {
+    pub drawable: Rc<dyn Drawable>,
+    pub material: Rc<Material>,
+    /// Replaces the default shader of the ID pass for geometry moved in the vertex shader
+    /// (skinning, displacement). It gets the material's parameters and writes the
+    /// `uint object_id` uniform to its first output. Scene files set it through a material's
+    /// `id_shader`.
+    pub id_shader: Option<Rc<Shader>>,
+}
+
+impl Renderable {
+    pub fn new(drawable: Rc<dyn Drawable>, material: Rc<Material>) -> Renderable {
+        Renderable {
+            drawable,
+            material,
+            id_shader: None,
+        }
+    }
+}
+
+/// A light attached to a node. Lights shine down their node's -Z axis.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Light {
+    pub kind: LightKind,
+    pub color: Vector3<f32>,
+    pub intensity: f32,
+    /// `None` means no cutoff
+    pub range: Option<f32>,
+}
+
+/// A light in world space, as returned by `Scene::lights`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlacedLight {
+    pub node: NodeId,
+    pub position: Point3<f32>,
+    pub direction: Vector3<f32>,
+    pub light: Light,
+}
+
+/// Handle of a node in a `Scene`. Stays valid until the node is removed and is never reused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeId(usize);
+
+pub struct Node {
+    pub name: String,
+    /// hidden nodes are skipped by `Scene::draw`, together with their children
+    pub visible: bool,
+    pub renderable: Option<Renderable>,
+    pub light: Option<Light>,
+    /// a camera looking down the node's -Z axis
+    pub camera: Option<GltfCamera>,
+    transform: Transform,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    /// cached parent world * local, only valid while `dirty` is false
+    world: Cell<Matrix4<f32>>,
+    dirty: Cell<bool>,
+}
+
+impl Node {
+    pub fn transform(&self) -> &Transform {
+        &self.transform
+    }
+
+    pub fn parent(&self) -> Option<NodeId> {
+        self.parent
+    }
+
+    pub fn children(&self) -> &[NodeId] {
+        &self.children
+    }
+}
+
+/// A hierarchy of nodes with local transforms. World matrices are computed when first asked
+/// for after a transform above them changed, so moving a node costs nothing until the next
+/// draw.
+pub struct Scene {
+    nodes: Vec<Option<Node>>,
+    roots: Vec<NodeId>,
+    /// skip renderables outside the view frustum in `draw`, on by default
+    pub culling: bool,
+}
+
+impl Default for Scene {
+    fn default() -> Scene {
+        Scene {
+            nodes: Vec::new(),
+            roots: Vec::new(),
+            culling: true,
+        }
+    }
+}
+
+impl Scene {
+    pub fn new() -> Scene {
+        Scene::default()
+    }
+
+    /// Add a node at the end of `parent`'s children, or as a new root.
+    /// ------------------------------------------------------------------------
+    pub fn add_node(&mut self, name: &str, transform: Transform, parent: Option<NodeId>) -> NodeId {
+        let id = NodeId(self.nodes.len());
+        self.nodes.push(Some(Node {
+            name: name.to_string(),
+            visible: true,
+            renderable: None,
+            light: None,
+            camera: None,
+            transform,
+            parent,
+            children: Vec::new(),
+            world: Cell::new(Matrix4::identity()),
+            dirty: Cell::new(true),
+        }));
+        match parent {
+            Some(parent) => self.node_mut(parent).children.push(id),
+            None => self.roots.push(id),
+        }
+        id
+    }
+
+    /// Remove a node and everything below it.
+    /// ------------------------------------------------------------------------
+    #[cfg(test)]
+    pub fn remove(&mut self, id: NodeId) {
+        self.detach(id);
+        let mut stack = vec![id];
+        while let Some(id) = stack.pop() {
+            if let Some(node) = self.nodes[id.0].take() {
+                stack.extend(node.children);
+            }
+        }
+    }
+
+    /// Move a node (with its subtree) under another parent, or make it a root. Fails when the
+    /// new parent is the node itself or one of its descendants.
+    /// ------------------------------------------------------------------------
+    #[cfg(test)]
+    pub fn set_parent(&mut self, id: NodeId, parent: Option<NodeId>) -> Result<(), String> {
+        let mut ancestor = parent;
+        while let Some(above) = ancestor {
+            if above == id {
+                return Err(format!(
+                    "node '{}' can't be moved below itself",
+                    self.node(id).name
+                ));
+            }
+            ancestor = self.node(above).parent;
+        }
+        self.detach(id);
+        self.node_mut(id).parent = parent;
+        match parent {
+            Some(parent) => self.node_mut(parent).children.push(id),
+            None => self.roots.push(id),
+        }
+        self.mark_dirty(id);
+        Ok(())
+    }
+
+    /// Panics for removed nodes, like indexing out of bounds.
+    /// ------------------------------------------------------------------------
+    pub fn node(&self, id: NodeId) -> &Node {
+        self.nodes[id.0]
+            .as_ref()
+            .unwrap_or_else(|| panic!("node {} was removed", id.0))
+    }
+
+    fn node_mut(&mut self, id: NodeId) -> &mut Node {
+        self.nodes[id.0]
+            .as_mut()
+            .unwrap_or_else(|| panic!("node {} was removed", id.0))
+    }
+
+    #[cfg(test)]
+    pub fn contains(&self, id: NodeId) -> bool {
+        matches!(self.nodes.get(id.0), Some(Some(_)))
+    }
+
+    pub fn roots(&self) -> &[NodeId] {
+        &self.roots
+    }
+
+    /// every live node, parents before their children
+    pub fn iter(&self) -> impl Iterator<Item = NodeId> + '_ {
+        let mut stack: Vec<NodeId> = self.roots.iter().rev().copied().collect();
+        std::iter::from_fn(move || {
+            let id = stack.pop()?;
+            stack.extend(self.node(id).children.iter().rev());
+            Some(id)
+        })
+    }
+
+    /// first node with this name, parents before children
+    #[cfg(test)]
+    pub fn find(&self, name: &str) -> Option<NodeId> {
+        self.iter().find(|&id| self.node(id).name == name)
+    }
+
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        self.nodes.iter().flatten().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.roots.is_empty()
+    }
+
+    /// names from the root down to a node, joined like `table/lamp`
+    pub fn path(&self, id: NodeId) -> String {
+        let node = self.node(id);
+        match node.parent() {
+            Some(parent) => format!("{}/{}", self.path(parent), node.name),
+            None => node.name.clone(),
+        }
+    }
+
+    #[cfg(test)]
+    pub fn set_translation(&mut self, id: NodeId, translation: Vector3<f32>) {
+        self.node_mut(id).transform.translation = translation;
+        self.mark_dirty(id);
+    }
+
+    #[cfg(test)]
+    pub fn set_rotation(&mut self, id: NodeId, rotation: Quaternion<f32>) {
+        self.node_mut(id).transform.rotation = rotation;
+        self.mark_dirty(id);
+    }
+
+    #[cfg(test)]
+    pub fn set_scale(&mut self, id: NodeId, scale: Vector3<f32>) {
+        self.node_mut(id).transform.scale = scale;
+        self.mark_dirty(id);
+    }
+
+    pub fn set_renderable(&mut self, id: NodeId, renderable: Option<Renderable>) {
+        self.node_mut(id).renderable = renderable;
+    }
+
+    pub fn set_visible(&mut self, id: NodeId, visible: bool) {
+        self.node_mut(id).visible = visible;
+    }
+
+    pub fn set_light(&mut self, id: NodeId, light: Option<Light>) {
+        self.node_mut(id).light = light;
+    }
+
+    pub fn set_camera(&mut self, id: NodeId, camera: Option<GltfCamera>) {
+        self.node_mut(id).camera = camera;
+    }
+
+    /// Every light with its world space position and direction, parents first. Lights below
+    /// hidden nodes are included.
+    /// ------------------------------------------------------------------------
+    pub fn lights(&self) -> Vec<PlacedLight> {
+        self.iter()
+            .filter_map(|id| {
+                let light = self.node(id).light?;
+                let world = self.world_matrix(id);
+                Some(PlacedLight {
+                    node: id,
+                    position: Point3::from_homogeneous(world * vec4(0.0, 0.0, 0.0, 1.0)),
+                    direction: (world * vec4(0.0, 0.0, -1.0, 0.0)).truncate().normalize(),
+                    light,
+                })
+            })
+            .collect()
+    }
+
+    /// The camera of a node placed at the node's world transform.
+    /// ------------------------------------------------------------------------
+    pub fn camera(&self, id: NodeId) -> Option<NodeCamera> {
+        Some(NodeCamera {
+            world: self.world_matrix(id),
+            camera: self.node(id).camera.clone()?,
+        })
+    }
+
+    /// Local to world matrix of a node, recomputed (along with any dirty ancestors) if a
+    /// transform above it changed since the last call.
+    /// ------------------------------------------------------------------------
+    pub fn world_matrix(&self, id: NodeId) -> Matrix4<f32> {
+        let node = self.node(id);
+        if node.dirty.get() {
+            let local = node.transform.matrix();
+            let world = match node.parent {
+                Some(parent) => self.world_matrix(parent) * local,
+                None => local,
+            };
+            node.world.set(world);
+            node.dirty.set(false);
+        }
+        node.world.get()
+    }
+
+    /// World space bounds of what a node draws. Empty without a renderable or when the
+    /// drawable doesn't know its bounds.
+    /// ------------------------------------------------------------------------
+    pub fn world_bounds(&self, id: NodeId) -> Aabb {
+        match &self.node(id).renderable {
+            Some(renderable) => renderable
+                .drawable
+                .bounds()
+                .transformed(&self.world_matrix(id)),
+            None => Aabb::empty(),
+        }
+    }
+
+    /// World space bounds of every visible node with a renderable.
+    /// ------------------------------------------------------------------------
+    pub fn bounds(&self) -> Aabb {
+        let (nodes, _) = self.draw_list(None);
+        nodes.iter().fold(Aabb::empty(), |bounds, &id| {
+            bounds.union(&self.world_bounds(id))
+        })
+    }
+
+    /// Nodes to draw, parents first: every visible node with a renderable whose world bounds
+    /// touch `frustum`. The bounding sphere rejects far away nodes cheaply, the box catches
+    /// the rest. Nodes without bounds are always drawn.
+    /// ------------------------------------------------------------------------
+    pub fn draw_list(&self, frustum: Option<&Frustum>) -> (Vec<NodeId>, CullStats) {
+        let mut list = Vec::new();
+        let mut stats = CullStats::default();
+        let mut stack: Vec<NodeId> = self.roots.iter().rev().copied().collect();
+        while let Some(id) = stack.pop() {
+            let node = self.node(id);
+            if !node.visible {
+                continue;
+            }
+            stack.extend(node.children.iter().rev());
+            let Some(renderable) = &node.renderable else {
+                continue;
+            };
+            let bounds = self.world_bounds(id);
+            let inside = match frustum {
+                Some(frustum) if !bounds.is_empty() => {
+                    let sphere = renderable.drawable.bounding_sphere();
+                    frustum.intersects_sphere(&sphere.transformed(&self.world_matrix(id)))
+                        && frustum.intersects_aabb(&bounds)
+                }
+                _ => true,
+            };
+            if inside {
+                list.push(id);
+                stats.visible += 1;
+            } else {
+                stats.culled += 1;
+            }
+        }
+        (list, stats)
+    }
+
+    /// Draw every visible node with a renderable, culling the ones outside the view frustum
+    /// unless `culling` is off.
+    /// ------------------------------------------------------------------------
+    pub unsafe fn draw(&self, view: &Matrix4<f32>, projection: &Matrix4<f32>) -> CullStats {
+        let (list, stats) = self.view_list(view, projection);
+        self.draw_nodes(&list, view, projection);
+        stats
+    }
+
+    /// `draw_list` for a camera, culled against its frustum when `culling` is on.
+    /// ------------------------------------------------------------------------
+    pub fn view_list(
+        &self,
+        view: &Matrix4<f32>,
+        projection: &Matrix4<f32>,
+    ) -> (Vec<NodeId>, CullStats) {
+        let frustum = Frustum::from_matrix(&(projection * view));
+        self.draw_list(Some(&frustum).filter(|_| self.culling))
+    }
+
+    /// Draw `nodes` into an ID target: node `nodes[i]` gets `object_id = i + 1`, leaving 0
+    /// for the background. Each node is drawn with its renderable's `id_shader`, or
+    /// `id_shader` when it has none; textures aren't bound.
+    /// ------------------------------------------------------------------------
+    pub unsafe fn draw_ids(
+        &self,
+        nodes: &[NodeId],
+        view: &Matrix4<f32>,
+        projection: &Matrix4<f32>,
+        id_shader: &Rc<Shader>,
+    ) {
+        let mut current: Option<&Rc<Shader>> = None;
+        for (index, &id) in nodes.iter().enumerate() {
+            let Some(renderable) = &self.node(id).renderable else {
+                continue;
+            };
+            let shader = renderable.id_shader.as_ref().unwrap_or(id_shader);
+            if !current.is_some_and(|current| Rc::ptr_eq(current, shader)) {
+                shader.useProgram();
+                shader.setMat4(c_str!("view"), view);
+                shader.setMat4(c_str!("projection"), projection);
+                current = Some(shader);
+            }
+            if renderable.id_shader.is_some() {
+                renderable.material.apply_params(shader);
+            }
+            shader.setUint(c_str!("object_id"), index as u32 + 1);
+            renderable.drawable.draw(shader, &self.world_matrix(id));
+        }
+    }
+
+    /// Draw just the depth of `nodes` with `shader`, which gets the model matrix and
+    /// `light_space`, the light's projection times view. Materials aren't applied.
+    /// ------------------------------------------------------------------------
+    pub unsafe fn draw_depth(&self, nodes: &[NodeId], light_space: &Matrix4<f32>, shader: &Shader) {
+        shader.useProgram();
+        shader.setMat4(c_str!("light_space"), light_space);
+        for &id in nodes {
+            if let Some(renderable) = &self.node(id).renderable {
+                renderable.drawable.draw(shader, &self.world_matrix(id));
+            }
+        }
+    }
+
+    /// Draw the renderables of `nodes` in order, switching programs only when the shader
+    /// changes and applying materials only when the material does.
+    /// ------------------------------------------------------------------------
+    pub unsafe fn draw_nodes(
+        &self,
+        nodes: &[NodeId],
+        view: &Matrix4<f32>,
+        projection: &Matrix4<f32>,
+    ) {
+        let mut shader: Option<&Rc<Shader>> = None;
+        let mut material: Option<&Rc<Material>> = None;
+        for &id in nodes {
+            let Some(renderable) = &self.node(id).renderable else {
+                continue;
+            };
+            let current = &renderable.material;
+            if !shader.is_some_and(|shader| Rc::ptr_eq(shader, &current.shader)) {
+                current.shader.useProgram();
+                current.shader.setMat4(c_str!("view"), view);
+                current.shader.setMat4(c_str!("projection"), projection);
+                shader = Some(&current.shader);
+                material = None;
+            }
+            if !material.is_some_and(|material| Rc::ptr_eq(material, current)) {
+                current.apply();
+                material = Some(current);
+            }
+            renderable
+                .drawable
+                .draw(&current.shader, &self.world_matrix(id));
+        }
+        gl::ActiveTexture(gl::TEXTURE0);
+    }
+
+    /// unlink a node from its parent's children or from the roots
+    #[cfg(test)]
+    fn detach(&mut self, id: NodeId) {
+        match self.node(id).parent {
+            Some(parent) => self.node_mut(parent).children.retain(|&child| child != id),
+            None => self.roots.retain(|&root| root != id),
+        }
+    }
+
+    /// Flag a node and its whole subtree for recomputing. A clean node never has a dirty
+    /// ancestor (computing it cleans them), so the walk can stop at nodes already flagged.
+    #[cfg(test)]
+    fn mark_dirty(&self, id: NodeId) {
+        let mut stack = vec![id];
+        while let Some(id) = stack.pop() {
+            let node = self.node(id);
+            if !node.dirty.replace(true) {
+                stack.extend(node.children.iter().copied());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::Transform as _;
+    use cgmath::{perspective, vec3, Deg, Point3, Rotation3};
+
+    fn close(a: Point3<f32>, b: Point3<f32>) -> bool {
+        (a - b).magnitude() < 1e-5
+    }
+
+    /// sun -> planet -> moon, each offset along X, the planet spinning
+    fn solar_system() -> (Scene, NodeId, NodeId, NodeId) {
+        let mut scene = Scene::new();
+        let sun = scene.add_node("sun", Transform::default(), None);
+        let planet = scene.add_node(
+            "planet",
+            Transform::from_translation(vec3(10.0, 0.0, 0.0)),
+            Some(sun),
+        );
+        let moon = scene.add_node(
+            "moon",
+            Transform::from_translation(vec3(2.0, 0.0, 0.0)),
+            Some(planet),
+        );
+        (scene, sun, planet, moon)
+    }
+
+    #[test]
+    fn world_matrices_follow_parents() {
+        let (mut scene, sun, planet, moon) = solar_system();
+        let origin = Point3::origin();
+        assert!(close(
+            scene.world_matrix(moon).transform_point(origin),
+            Point3::new(12.0, 0.0, 0.0)
+        ));
+
+        // turning the planet swings the moon around it, moving the sun moves everything
+        scene.set_rotation(planet, Quaternion::from_angle_y(Deg(90.0)));
+        assert!(close(
+            scene.world_matrix(moon).transform_point(origin),
+            Point3::new(10.0, 0.0, -2.0)
+        ));
+        scene.set_translation(sun, vec3(0.0, 5.0, 0.0));
+        scene.set_scale(sun, vec3(2.0, 2.0, 2.0));
+        assert!(close(
+            scene.world_matrix(moon).transform_point(origin),
+            Point3::new(20.0, 5.0, -4.0)
+        ));
+        assert!(close(
+            scene.world_matrix(planet).transform_point(origin),
+            Point3::new(20.0, 5.0, 0.0)
+        ));
+    }
+
+    #[test]
+    fn only_changed_subtrees_are_dirty() {
+        let (mut scene, sun, planet, moon) = solar_system();
+        let comet = scene.add_node("comet", Transform::default(), None);
+        for id in [sun, planet, moon, comet] {
+            scene.world_matrix(id);
+        }
+        scene.set_translation(planet, vec3(3.0, 0.0, 0.0));
+        let dirty = |scene: &Scene, id| scene.node(id).dirty.get();
+        assert!(!dirty(&scene, sun) && !dirty(&scene, comet));
+        assert!(dirty(&scene, planet) && dirty(&scene, moon));
+        // asking for the moon cleans the planet on the way
+        scene.world_matrix(moon);
+        assert!(!dirty(&scene, planet) && !dirty(&scene, moon));
+    }
+
+    #[test]
+    fn reparenting_and_removal() {
+        let (mut scene, sun, planet, moon) = solar_system();
+        assert_eq!(scene.path(moon), "sun/planet/moon");
+        assert!(scene.set_parent(planet, Some(moon)).is_err());
+        assert!(scene.set_parent(sun, Some(sun)).is_err());
+
+        scene.set_parent(moon, None).unwrap();
+        assert_eq!(scene.roots(), &[sun, moon]);
+        assert_eq!(scene.path(moon), "moon");
+        assert!(scene.node(planet).children().is_empty());
+        assert!(close(
+            scene.world_matrix(moon).transform_point(Point3::origin()),
+            Point3::new(2.0, 0.0, 0.0)
+        ));
+        assert_eq!(scene.find("moon"), Some(moon));
+        assert_eq!(scene.iter().collect::<Vec<_>>(), vec![sun, planet, moon]);
+
+        scene.remove(sun);
+        assert!(!scene.contains(planet));
+        assert_eq!(scene.len(), 1);
+        assert_eq!(scene.roots(), &[moon]);
+        // ids aren't reused
+        let again = scene.add_node("planet", Transform::default(), Some(moon));
+        assert_ne!(again, planet);
+        assert_eq!(scene.find("planet"), Some(again));
+    }
+
+    /// unit box around the origin, no GL needed
+    struct UnitBox;
+
+    impl Drawable for UnitBox {
+        unsafe fn draw(&self, _shader: &Shader, _model: &Matrix4<f32>) {}
+
+        fn bounds(&self) -> Aabb {
+            Aabb::new(Point3::new(-0.5, -0.5, -0.5), Point3::new(0.5, 0.5, 0.5))
+        }
+    }
+
+    #[test]
+    fn draw_list_culls_outside_the_frustum() {
+        let (mut scene, sun, planet, moon) = solar_system();
+        let material = Rc::new(Material::new(Rc::new(Shader { ID: 0 })));
+        let renderable = Renderable::new(Rc::new(UnitBox), material);
+        for id in [sun, planet, moon] {
+            scene.set_renderable(id, Some(renderable.clone()));
+        }
+        // looking down -Z from z = 5 with a 90 degree view: |x| < 5 is visible at z = 0
+        let view = Matrix4::look_at_rh(
+            Point3::new(0.0, 0.0, 5.0),
+            Point3::origin(),
+            Vector3::unit_y(),
+        );
+        let frustum = Frustum::from_matrix(&(perspective(Deg(90.0), 1.0, 0.1, 100.0) * view));
+
+        let (list, stats) = scene.draw_list(Some(&frustum));
+        assert_eq!(list, vec![sun]);
+        assert_eq!(
+            stats,
+            CullStats {
+                visible: 1,
+                culled: 2
+            }
+        );
+
+        // bounds follow the world transforms: moving the planet brings the moon to the edge,
+        // scaling the sun pushes both out again
+        scene.set_translation(planet, vec3(3.0, 0.0, 0.0));
+        assert_eq!(scene.draw_list(Some(&frustum)).0, vec![sun, planet, moon]);
+        scene.set_scale(sun, vec3(3.0, 3.0, 3.0));
+        assert_eq!(scene.draw_list(Some(&frustum)).0, vec![sun]);
+        assert_eq!(scene.world_bounds(planet).size(), vec3(3.0, 3.0, 3.0));
+
+        // hidden subtrees aren't counted, no frustum draws everything visible
+        scene.set_visible(planet, false);
+        let (list, stats) = scene.draw_list(None);
+        assert_eq!(list, vec![sun]);
+        assert_eq!(
+            stats,
+            CullStats {
+                visible: 1,
+                culled: 0
+            }
+        );
+        assert_eq!(scene.bounds(), scene.world_bounds(sun));
+    }
+}
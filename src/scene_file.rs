@@ -0,0 +1,1104 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::rc::Rc;
+
+use cgmath::{Deg, Quaternion, Rad};
+use serde::{Deserialize, Serialize};
+
+use crate::config::ConfigError;
+use crate::gltf_scene::{GltfCamera, GltfModel, GltfProjection, GltfScene, LightKind};
+use crate::material::{Material, Uniform};
+use crate::mesh::{Mesh, MeshData};
+use crate::model::Model;
+use crate::obj;
+use crate::ply::{self, PlyFormat};
+use crate::scene::{Drawable, Light, NodeId, Renderable, Scene, Transform};
+use crate::shader::Shader;
+use crate::shadow::{texture_size_limits, ShadowSettings, MAX_CASCADES, MAX_POINT_SHADOWS};
+use crate::stl::{self, StlFormat};
+use crate::texture::Texture;
+
+/// Text format of a scene file, picked from its extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SceneFormat {
+    Ron,
+    Json,
+}
+
+impl SceneFormat {
+    pub fn from_path(path: &Path) -> Option<SceneFormat> {
+        match path.extension()?.to_str()? {
+            "ron" => Some(SceneFormat::Ron),
+            "json" => Some(SceneFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+/// A scene as written in a `.ron` or `.json` file: named meshes, textures, shaders and
+/// materials, and a tree of nodes using them by name. Paths are relative to the working
+/// directory, like every other asset path.
+///
+/// ```ron
+/// (
+///     meshes: { "box": Cube(size: 1.0), "teapot": File("resources/models/teapot.obj") },
+///     textures: { "container": (path: "resources/textures/container.jpg") },
+///     shaders: { "model": "src/shaders/model_loading" },
+///     materials: {
+///         "crate": (
+///             shader: "model",
+///             textures: { "texture_diffuse1": "container" },
+///             uniforms: { "diffuse_color": (1.0, 1.0, 1.0) },
+///         ),
+///     },
+///     shadows: (resolution: 4096, pcf_radius: 2),
+///     nodes: [
+///         (name: "box", translation: (0.0, 0.5, 0.0), mesh: "box", material: "crate"),
+///         (name: "sun", rotation: (-0.38, 0.0, 0.0, 0.92), light: (kind: Directional)),
+///     ],
+/// )
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SceneFile {
+    pub meshes: BTreeMap<String, MeshSource>,
+    pub textures: BTreeMap<String, TextureEntry>,
+    pub shaders: BTreeMap<String, ShaderSource>,
+    pub materials: BTreeMap<String, MaterialEntry>,
+    pub shadows: ShadowSettings,
+    pub nodes: Vec<NodeEntry>,
+}
+
+/// Where a mesh comes from: a model file or one of the `MeshData` generators.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub enum MeshSource {
+    /// OBJ, glTF, PLY or STL file, drawn with its own materials
+    File(String),
+    Plane {
+        size: f32,
+    },
+    Grid {
+        width: f32,
+        depth: f32,
+        columns: u32,
+        rows: u32,
+    },
+    Cube {
+        size: f32,
+    },
+    UvSphere {
+        radius: f32,
+        sectors: u32,
+        stacks: u32,
+    },
+    Icosphere {
+        radius: f32,
+        subdivisions: u32,
+    },
+    Cylinder {
+        radius: f32,
+        height: f32,
+        sectors: u32,
+    },
+    Cone {
+        radius: f32,
+        height: f32,
+        sectors: u32,
+    },
+    Torus {
+        major_radius: f32,
+        minor_radius: f32,
+        major_segments: u32,
+        minor_segments: u32,
+    },
+    Capsule {
+        radius: f32,
+        height: f32,
+        sectors: u32,
+        rings: u32,
+    },
+}
+
+impl MeshSource {
+    /// vertices of a generated mesh, `None` for files
+    pub fn generate(&self) -> Option<MeshData> {
+        Some(match *self {
+            MeshSource::File(_) => return None,
+            MeshSource::Plane { size } => MeshData::plane(size),
+            MeshSource::Grid {
+                width,
+                depth,
+                columns,
+                rows,
+            } => MeshData::grid(width, depth, columns, rows),
+            MeshSource::Cube { size } => MeshData::cube(size),
+            MeshSource::UvSphere {
+                radius,
+                sectors,
+                stacks,
+            } => MeshData::uv_sphere(radius, sectors, stacks),
+            MeshSource::Icosphere {
+                radius,
+                subdivisions,
+            } => MeshData::icosphere(radius, subdivisions),
+            MeshSource::Cylinder {
+                radius,
+                height,
+                sectors,
+            } => MeshData::cylinder(radius, height, sectors),
+            MeshSource::Cone {
+                radius,
+                height,
+                sectors,
+            } => MeshData::cone(radius, height, sectors),
+            MeshSource::Torus {
+                major_radius,
+                minor_radius,
+                major_segments,
+                minor_segments,
+            } => MeshData::torus(major_radius, minor_radius, major_segments, minor_segments),
+            MeshSource::Capsule {
+                radius,
+                height,
+                sectors,
+                rings,
+            } => MeshData::capsule(radius, height, sectors, rings),
+        })
+    }
+
+    /// upload the mesh; needs a current GL context
+    fn load(&self) -> Result<Rc<dyn Drawable>, ConfigError> {
+        match (self, self.generate()) {
+            (MeshSource::File(path), _) => load_model(path),
+            (_, Some(data)) => Ok(Rc::new(Mesh::with_bvh(&data))),
+            (_, None) => unreachable!("only files have no generator"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TextureEntry {
+    pub path: String,
+    /// flip rows so the first one is at the bottom, as generated and OBJ UVs expect
+    #[serde(default = "flip_by_default")]
+    pub flip_v: bool,
+}
+
+fn flip_by_default() -> bool {
+    true
+}
+
+/// A vertex and fragment shader, either as a common path without the extension
+/// (`"src/shaders/coordinate_1"` for `coordinate_1.vs` and `coordinate_1.fs`) or as two paths.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ShaderSource {
+    Pair(String),
+    Files { vertex: String, fragment: String },
+}
+
+impl ShaderSource {
+    /// vertex and fragment shader paths
+    pub fn paths(&self) -> (String, String) {
+        match self {
+            ShaderSource::Pair(base) => (format!("{}.vs", base), format!("{}.fs", base)),
+            ShaderSource::Files { vertex, fragment } => (vertex.clone(), fragment.clone()),
+        }
+    }
+}
+
+/// A shader with the textures and uniform values to draw with.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MaterialEntry {
+    pub shader: String,
+    /// texture for each sampler uniform; units are assigned in sampler name order
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub textures: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub uniforms: BTreeMap<String, Uniform>,
+    /// shader for the picking ID pass when the material's vertex shader moves the geometry,
+    /// see `Renderable::id_shader`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id_shader: Option<String>,
+}
+
+impl MaterialEntry {
+    /// the material with its uniforms but without textures, which need a GL context
+    fn material(&self, shader: Rc<Shader>) -> Material {
+        self.uniforms
+            .iter()
+            .fold(Material::new(shader), |material, (name, value)| {
+                material.with(name, *value)
+            })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum LightType {
+    Directional,
+    Point,
+    /// cone angles in degrees from the light's -Z axis
+    Spot {
+        inner_cone: f32,
+        outer_cone: f32,
+    },
+}
+
+/// A light shining down its node's -Z axis.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LightEntry {
+    pub kind: LightType,
+    pub color: [f32; 3],
+    pub intensity: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub range: Option<f32>,
+}
+
+impl Default for LightEntry {
+    fn default() -> LightEntry {
+        LightEntry {
+            kind: LightType::Point,
+            color: [1.0, 1.0, 1.0],
+            intensity: 1.0,
+            range: None,
+        }
+    }
+}
+
+impl From<&LightEntry> for Light {
+    fn from(entry: &LightEntry) -> Light {
+        Light {
+            kind: match entry.kind {
+                LightType::Directional => LightKind::Directional,
+                LightType::Point => LightKind::Point,
+                LightType::Spot {
+                    inner_cone,
+                    outer_cone,
+                } => LightKind::Spot {
+                    inner_cone: Rad::from(Deg(inner_cone)).0,
+                    outer_cone: Rad::from(Deg(outer_cone)).0,
+                },
+            },
+            color: entry.color.into(),
+            intensity: entry.intensity,
+            range: entry.range,
+        }
+    }
+}
+
+impl From<&Light> for LightEntry {
+    fn from(light: &Light) -> LightEntry {
+        LightEntry {
+            kind: match light.kind {
+                LightKind::Directional => LightType::Directional,
+                LightKind::Point => LightType::Point,
+                LightKind::Spot {
+                    inner_cone,
+                    outer_cone,
+                } => LightType::Spot {
+                    inner_cone: degrees(inner_cone),
+                    outer_cone: degrees(outer_cone),
+                },
+            },
+            color: light.color.into(),
+            intensity: light.intensity,
+            range: light.range,
+        }
+    }
+}
+
+/// Radians to degrees, rounded to 1e-4 so angles typed into a file are saved unchanged.
+fn degrees(radians: f32) -> f32 {
+    (Deg::from(Rad(radians)).0 * 1e4).round() / 1e4
+}
+
+/// A camera looking down its node's -Z axis.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub enum CameraEntry {
+    /// `fov` is the vertical field of view in degrees; without `far` the projection is
+    /// infinite, without `aspect` it follows the viewport
+    Perspective {
+        fov: f32,
+        near: f32,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        far: Option<f32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        aspect: Option<f32>,
+    },
+    /// half width and height of the view volume
+    Orthographic {
+        xmag: f32,
+        ymag: f32,
+        near: f32,
+        far: f32,
+    },
+}
+
+impl CameraEntry {
+    fn to_camera(self) -> GltfCamera {
+        let projection = match self {
+            CameraEntry::Perspective {
+                fov,
+                near,
+                far,
+                aspect,
+            } => GltfProjection::Perspective {
+                yfov: Rad::from(Deg(fov)).0,
+                aspect,
+                znear: near,
+                zfar: far,
+            },
+            CameraEntry::Orthographic {
+                xmag,
+                ymag,
+                near,
+                far,
+            } => GltfProjection::Orthographic {
+                xmag,
+                ymag,
+                znear: near,
+                zfar: far,
+            },
+        };
+        GltfCamera { projection }
+    }
+
+    fn from_camera(camera: &GltfCamera) -> CameraEntry {
+        match camera.projection {
+            GltfProjection::Perspective {
+                yfov,
+                aspect,
+                znear,
+                zfar,
+            } => CameraEntry::Perspective {
+                fov: degrees(yfov),
+                near: znear,
+                far: zfar,
+                aspect,
+            },
+            GltfProjection::Orthographic {
+                xmag,
+                ymag,
+                znear,
+                zfar,
+            } => CameraEntry::Orthographic {
+                xmag,
+                ymag,
+                near: znear,
+                far: zfar,
+            },
+        }
+    }
+}
+
+/// A node with its local transform, what it carries and its children. A node drawing
+/// something names both a mesh and a material.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NodeEntry {
+    pub name: String,
+    pub translation: [f32; 3],
+    /// quaternion as x, y, z, w
+    pub rotation: [f32; 4],
+    pub scale: [f32; 3],
+    #[serde(skip_serializing_if = "is_true")]
+    pub visible: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mesh: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub material: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub light: Option<LightEntry>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub camera: Option<CameraEntry>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<NodeEntry>,
+}
+
+fn is_true(value: &bool) -> bool {
+    *value
+}
+
+impl Default for NodeEntry {
+    fn default() -> NodeEntry {
+        NodeEntry {
+            name: String::new(),
+            translation: [0.0; 3],
+            rotation: [0.0, 0.0, 0.0, 1.0],
+            scale: [1.0; 3],
+            visible: true,
+            mesh: None,
+            material: None,
+            light: None,
+            camera: None,
+            children: Vec::new(),
+        }
+    }
+}
+
+impl NodeEntry {
+    pub fn transform(&self) -> Transform {
+        let [x, y, z, w] = self.rotation;
+        Transform {
+            translation: self.translation.into(),
+            rotation: Quaternion::new(w, x, y, z),
+            scale: self.scale.into(),
+        }
+    }
+}
+
+impl SceneFile {
+    /// Read a `.ron` or `.json` scene file and check that every name it uses is defined.
+    /// Nothing is loaded yet, see `SceneAssets::new`.
+    /// ------------------------------------------------------------------------
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<SceneFile, ConfigError> {
+        let path = path.as_ref();
+        let format = SceneFormat::from_path(path).ok_or_else(|| {
+            ConfigError::Parse(
+                path.to_path_buf(),
+                "unknown scene format, expected .ron or .json".to_string(),
+            )
+        })?;
+        let text =
+            fs::read_to_string(path).map_err(|err| ConfigError::Io(path.to_path_buf(), err))?;
+        SceneFile::parse(&text, format).map_err(|err| ConfigError::Parse(path.to_path_buf(), err))
+    }
+
+    /// Parse and validate a scene. Optional RON fields can be written without `Some(...)`.
+    /// ------------------------------------------------------------------------
+    pub fn parse(text: &str, format: SceneFormat) -> Result<SceneFile, String> {
+        let file: SceneFile = match format {
+            SceneFormat::Ron => ron::Options::default()
+                .with_default_extension(ron::extensions::Extensions::IMPLICIT_SOME)
+                .from_str(text)
+                .map_err(|err| err.to_string())?,
+            SceneFormat::Json => serde_json::from_str(text).map_err(|err| err.to_string())?,
+        };
+        file.validate()?;
+        Ok(file)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), ConfigError> {
+        let path = path.as_ref();
+        let format = SceneFormat::from_path(path).ok_or_else(|| {
+            ConfigError::Parse(
+                path.to_path_buf(),
+                "unknown scene format, expected .ron or .json".to_string(),
+            )
+        })?;
+        let text = self
+            .to_text(format)
+            .map_err(|err| ConfigError::Parse(path.to_path_buf(), err))?;
+        fs::write(path, text).map_err(|err| ConfigError::Io(path.to_path_buf(), err))
+    }
+
+    pub fn to_text(&self, format: SceneFormat) -> Result<String, String> {
+        match format {
+            SceneFormat::Ron => {
+                let pretty = ron::ser::PrettyConfig::new()
+                    .extensions(ron::extensions::Extensions::IMPLICIT_SOME);
+                ron::ser::to_string_pretty(self, pretty).map_err(|err| err.to_string())
+            }
+            SceneFormat::Json => serde_json::to_string_pretty(self).map_err(|err| err.to_string()),
+        }
+    }
+
+    /// Check the shadow settings, that materials use defined shaders and textures, and that
+    /// nodes name a mesh and a material together, both defined. Errors name the offending
+    /// entry, nodes by their path from the root like `node 'table/lamp'`.
+    /// ------------------------------------------------------------------------
+    pub fn validate(&self) -> Result<(), String> {
+        // the upper limits depend on the GL implementation, `SceneAssets::new` checks them
+        if self.shadows.resolution == 0 {
+            return Err("shadows: resolution must be at least 1".to_string());
+        }
+        if !(1..=MAX_CASCADES as u32).contains(&self.shadows.cascades) {
+            return Err(format!(
+                "shadows: {} cascades is not between 1 and {}",
+                self.shadows.cascades, MAX_CASCADES
+            ));
+        }
+        if !(0.0..=1.0).contains(&self.shadows.split_lambda) {
+            return Err(format!(
+                "shadows: split_lambda {} is not between 0 and 1",
+                self.shadows.split_lambda
+            ));
+        }
+        if self.shadows.point_resolution == 0 {
+            return Err("shadows: point_resolution must be at least 1".to_string());
+        }
+        if self.shadows.point_shadows as usize > MAX_POINT_SHADOWS {
+            return Err(format!(
+                "shadows: {} point_shadows is more than {}",
+                self.shadows.point_shadows, MAX_POINT_SHADOWS
+            ));
+        }
+        for (name, material) in &self.materials {
+            if !self.shaders.contains_key(&material.shader) {
+                return Err(format!(
+                    "material '{}': unknown shader '{}'",
+                    name, material.shader
+                ));
+            }
+            if let Some(id_shader) = material
+                .id_shader
+                .as_ref()
+                .filter(|id_shader| !self.shaders.contains_key(*id_shader))
+            {
+                return Err(format!(
+                    "material '{}': unknown id_shader '{}'",
+                    name, id_shader
+                ));
+            }
+            if let Some(texture) = material
+                .textures
+                .values()
+                .find(|texture| !self.textures.contains_key(*texture))
+            {
+                return Err(format!(
+                    "material '{}': unknown texture '{}'",
+                    name, texture
+                ));
+            }
+        }
+        self.validate_nodes(&self.nodes, "")
+    }
+
+    fn validate_nodes(&self, nodes: &[NodeEntry], parent_path: &str) -> Result<(), String> {
+        for node in nodes {
+            let path = format!("{}{}", parent_path, node.name);
+            let error = |message: String| Err(format!("node '{}': {}", path, message));
+            match (&node.mesh, &node.material) {
+                (Some(mesh), _) if !self.meshes.contains_key(mesh) => {
+                    return error(format!("unknown mesh '{}'", mesh))
+                }
+                (_, Some(material)) if !self.materials.contains_key(material) => {
+                    return error(format!("unknown material '{}'", material))
+                }
+                (Some(mesh), None) => return error(format!("mesh '{}' needs a material", mesh)),
+                (None, Some(material)) => {
+                    return error(format!("material '{}' needs a mesh", material))
+                }
+                _ => {}
+            }
+            if let Some(LightType::Spot {
+                inner_cone,
+                outer_cone,
+            }) = node.light.map(|light| light.kind)
+            {
+                if !(0.0..=outer_cone).contains(&inner_cone) || outer_cone > 90.0 {
+                    return error(format!(
+                        "spot cone angles must satisfy 0 <= inner ({}) <= outer ({}) <= 90",
+                        inner_cone, outer_cone
+                    ));
+                }
+            }
+            self.validate_nodes(&node.children, &format!("{}/", path))?;
+        }
+        Ok(())
+    }
+}
+
+/// The GL resources of a scene file: every mesh, texture, shader and material, created once
+/// and shared by the nodes using them. Keeps the resource lists of the file so the scene can
+/// be written back out with `to_file`.
+pub struct SceneAssets {
+    /// the file the assets were made from, without its nodes
+    resources: SceneFile,
+    meshes: BTreeMap<String, Rc<dyn Drawable>>,
+    materials: BTreeMap<String, Rc<Material>>,
+    /// ID pass shaders by material name, for the materials that have one
+    id_shaders: BTreeMap<String, Rc<Shader>>,
+}
+
+impl SceneAssets {
+    /// Load every resource of a scene file; needs a current GL context.
+    /// ------------------------------------------------------------------------
+    pub fn new(file: &SceneFile) -> Result<SceneAssets, String> {
+        file.validate()?;
+        file.shadows.check_limits(texture_size_limits())?;
+        let mut meshes = BTreeMap::new();
+        for (name, source) in &file.meshes {
+            let drawable = source
+                .load()
+                .map_err(|err| format!("mesh '{}': {}", name, err))?;
+            meshes.insert(name.clone(), drawable);
+        }
+        let mut textures = BTreeMap::new();
+        for (name, entry) in &file.textures {
+            let texture = Texture::load(&entry.path, entry.flip_v)
+                .map_err(|err| format!("texture '{}': {}", name, err))?;
+            textures.insert(name.as_str(), Rc::new(texture));
+        }
+        let mut shaders = BTreeMap::new();
+        for (name, source) in &file.shaders {
+            let (vertex, fragment) = source.paths();
+            // Shader::new panics on missing files
+            if let Some(missing) = [&vertex, &fragment]
+                .into_iter()
+                .find(|path| !Path::new(path).is_file())
+            {
+                return Err(format!("shader '{}': can't open {}", name, missing));
+            }
+            shaders.insert(name.as_str(), Rc::new(Shader::new(&vertex, &fragment)));
+        }
+        let mut materials = BTreeMap::new();
+        let mut id_shaders = BTreeMap::new();
+        for (name, entry) in &file.materials {
+            let mut material = entry.material(shaders[entry.shader.as_str()].clone());
+            for (sampler, texture) in &entry.textures {
+                material.set_texture(sampler, textures[texture.as_str()].clone());
+            }
+            // a mistyped name only loses one uniform, the scene still draws
+            for problem in unsafe { material.validate() } {
+                println!(
+                    "WARNING::MATERIAL::INVALID material '{}': {}",
+                    name, problem
+                );
+            }
+            materials.insert(name.clone(), Rc::new(material));
+            if let Some(id_shader) = &entry.id_shader {
+                id_shaders.insert(name.clone(), shaders[id_shader.as_str()].clone());
+            }
+        }
+        Ok(SceneAssets {
+            resources: SceneFile {
+                nodes: Vec::new(),
+                ..file.clone()
+            },
+            meshes,
+            materials,
+            id_shaders,
+        })
+    }
+
+    pub fn shadows(&self) -> ShadowSettings {
+        self.resources.shadows
+    }
+
+    /// A renderable drawing mesh `mesh` with material `material`, for nodes added at runtime.
+    /// ------------------------------------------------------------------------
+    pub fn renderable(&self, mesh: &str, material: &str) -> Option<Renderable> {
+        Some(Renderable {
+            id_shader: self.id_shaders.get(material).cloned(),
+            ..Renderable::new(
+                self.meshes.get(mesh)?.clone(),
+                self.materials.get(material)?.clone(),
+            )
+        })
+    }
+
+    /// Add `nodes` and their children to `scene` below `parent`, returning the new top level
+    /// nodes.
+    /// ------------------------------------------------------------------------
+    pub fn instantiate(
+        &self,
+        nodes: &[NodeEntry],
+        scene: &mut Scene,
+        parent: Option<NodeId>,
+    ) -> Result<Vec<NodeId>, String> {
+        let mut added = Vec::with_capacity(nodes.len());
+        for entry in nodes {
+            let id = scene.add_node(&entry.name, entry.transform(), parent);
+            added.push(id);
+            scene.set_visible(id, entry.visible);
+            if let (Some(mesh), Some(material)) = (&entry.mesh, &entry.material) {
+                let renderable = self.renderable(mesh, material).ok_or_else(|| {
+                    format!(
+                        "node '{}': unknown mesh '{}' or material '{}'",
+                        entry.name, mesh, material
+                    )
+                })?;
+                scene.set_renderable(id, Some(renderable));
+            }
+            scene.set_light(id, entry.light.as_ref().map(Light::from));
+            scene.set_camera(id, entry.camera.map(|camera| camera.to_camera()));
+            self.instantiate(&entry.children, scene, Some(id))?;
+        }
+        Ok(added)
+    }
+
+    /// Describe `scene` as a scene file with these assets' resources, so it can be saved.
+    /// Renderables that weren't made from these assets are left out with a warning.
+    /// ------------------------------------------------------------------------
+    pub fn to_file(&self, scene: &Scene) -> SceneFile {
+        SceneFile {
+            nodes: scene
+                .roots()
+                .iter()
+                .map(|&root| self.describe(scene, root))
+                .collect(),
+            ..self.resources.clone()
+        }
+    }
+
+    fn describe(&self, scene: &Scene, id: NodeId) -> NodeEntry {
+        let node = scene.node(id);
+        let transform = node.transform();
+        let q = transform.rotation;
+        let (mesh, material) = match &node.renderable {
+            Some(renderable) => {
+                let mesh = self
+                    .meshes
+                    .iter()
+                    .find(|(_, drawable)| Rc::ptr_eq(drawable, &renderable.drawable));
+                let material = self
+                    .materials
+                    .iter()
+                    .find(|(_, material)| Rc::ptr_eq(material, &renderable.material));
+                match (mesh, material) {
+                    (Some((mesh, _)), Some((material, _))) => {
+                        (Some(mesh.clone()), Some(material.clone()))
+                    }
+                    _ => {
+                        println!(
+                            "WARNING::SCENE::RENDERABLE_NOT_SAVED node '{}' draws something \
+                             that isn't in the scene file",
+                            node.name
+                        );
+                        (None, None)
+                    }
+                }
+            }
+            None => (None, None),
+        };
+        NodeEntry {
+            name: node.name.clone(),
+            translation: transform.translation.into(),
+            rotation: [q.v.x, q.v.y, q.v.z, q.s],
+            scale: transform.scale.into(),
+            visible: node.visible,
+            mesh,
+            material,
+            light: node.light.as_ref().map(LightEntry::from),
+            camera: node.camera.as_ref().map(CameraEntry::from_camera),
+            children: node
+                .children()
+                .iter()
+                .map(|&child| self.describe(scene, child))
+                .collect(),
+        }
+    }
+}
+
+/// Read a scene file, load its resources and add its nodes to `scene` as new roots; needs a
+/// current GL context.
+/// ------------------------------------------------------------------------
+pub fn load_scene<P: AsRef<Path>>(path: P, scene: &mut Scene) -> Result<SceneAssets, ConfigError> {
+    let path = path.as_ref();
+    let file = SceneFile::load(path)?;
+    let error = |err| ConfigError::Parse(path.to_path_buf(), err);
+    let assets = SceneAssets::new(&file).map_err(error)?;
+    assets
+        .instantiate(&file.nodes, scene, None)
+        .map_err(error)?;
+    Ok(assets)
+}
+
+/// Load a model file, picking the format from the extension; needs a current GL context.
+/// ------------------------------------------------------------------------
+pub fn load_model(path: &str) -> Result<Rc<dyn Drawable>, ConfigError> {
+    Ok(match model_extension(path).as_deref() {
+        Some("gltf") | Some("glb") => Rc::new(GltfModel::load(path)?),
+        Some("ply") => Rc::new(Model::from_data(&ply::load_ply(path)?)),
+        Some("stl") => Rc::new(Model::from_data(&stl::load_stl(path)?)),
+        _ => Rc::new(Model::load_obj(path)?),
+    })
+}
+
+/// Add a model file to `scene` as a new root node drawn with `material`. The cameras and
+/// lights of a glTF file come along as children of that node.
+/// ------------------------------------------------------------------------
+pub fn add_model(
+    scene: &mut Scene,
+    path: &str,
+    material: Rc<Material>,
+) -> Result<NodeId, ConfigError> {
+    let drawable: Rc<dyn Drawable> = match model_extension(path).as_deref() {
+        Some("gltf") | Some("glb") => {
+            let model = GltfModel::load(path)?;
+            let node = scene.add_node(path, Transform::default(), None);
+            model.scene.add_cameras_and_lights(scene, node);
+            scene.set_renderable(node, Some(Renderable::new(Rc::new(model), material)));
+            return Ok(node);
+        }
+        _ => load_model(path)?,
+    };
+    let node = scene.add_node(path, Transform::default(), None);
+    scene.set_renderable(node, Some(Renderable::new(drawable, material)));
+    Ok(node)
+}
+
+/// The triangles of a model file as a single mesh, e.g. to export it. OBJ parts and glTF
+/// primitives are merged, glTF ones placed where their nodes put them.
+/// ------------------------------------------------------------------------
+pub fn load_mesh_data(path: &str) -> Result<MeshData, ConfigError> {
+    let mut merged = MeshData::default();
+    match model_extension(path).as_deref() {
+        Some("gltf") | Some("glb") => {
+            let file = GltfScene::load(path)?;
+            for (node, world) in file.nodes.iter().zip(file.world_matrices()) {
+                let Some(mesh) = node.mesh else { continue };
+                for primitive in &file.meshes[mesh].primitives {
+                    let mut data = primitive.data.clone();
+                    data.transform(&world);
+                    merged.append(&data);
+                }
+            }
+        }
+        Some("ply") => merged = ply::load_ply(path)?,
+        Some("stl") => merged = stl::load_stl(path)?,
+        _ => {
+            for part in obj::load_obj(path)?.meshes {
+                merged.append(&part.data);
+            }
+        }
+    }
+    Ok(merged)
+}
+
+/// Write `mesh` as PLY or STL, picking the format from the extension; binary unless `ascii`.
+/// ------------------------------------------------------------------------
+pub fn save_mesh_data(path: &str, mesh: &MeshData, ascii: bool) -> Result<(), ConfigError> {
+    match model_extension(path).as_deref() {
+        Some("ply") if ascii => ply::save_ply(path, mesh, PlyFormat::Ascii),
+        Some("ply") => ply::save_ply(path, mesh, PlyFormat::BinaryLittleEndian),
+        Some("stl") if ascii => stl::save_stl(path, mesh, StlFormat::Ascii),
+        Some("stl") => stl::save_stl(path, mesh, StlFormat::Binary),
+        _ => Err(ConfigError::Arg(format!(
+            "can't export {}, only .ply and .stl are written",
+            path
+        ))),
+    }
+}
+
+fn model_extension(path: &str) -> Option<String> {
+    let extension = Path::new(path).extension()?.to_str()?;
+    Some(extension.to_ascii_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::InnerSpace;
+    use cgmath::{vec3, Matrix4, Point3};
+    use std::f32::consts::FRAC_PI_6;
+
+    const EXAMPLE: &str = r#"(
+    meshes: {
+        "box": Cube(size: 1.0),
+        "ball": UvSphere(radius: 0.5, sectors: 16, stacks: 8),
+    },
+    textures: { "container": (path: "resources/textures/container.jpg") },
+    shaders: {
+        "quad": "src/shaders/coordinate_1",
+        "model": (vertex: "src/shaders/model_loading.vs", fragment: "src/shaders/model_loading.fs"),
+        "ids": "src/shaders/object_id",
+    },
+    materials: {
+        "crate": (
+            shader: "model",
+            textures: { "texture_diffuse1": "container" },
+            uniforms: { "diffuse_color": (1.0, 0.5, 0.25), "lit": true },
+            id_shader: "ids",
+        ),
+        "plain": (shader: "quad", uniforms: { "alpha": 0.5 }),
+    },
+    nodes: [
+        (
+            name: "table",
+            translation: (0.0, 1.0, 0.0),
+            mesh: "box",
+            material: "crate",
+            children: [
+                (name: "ball", translation: (0.0, 0.75, 0.0), mesh: "ball", material: "plain"),
+                (name: "lamp", light: (kind: Spot(inner_cone: 20.0, outer_cone: 30.0), range: 8.0)),
+            ],
+        ),
+        (name: "eye", visible: false, camera: Perspective(fov: 45.0, near: 0.1)),
+    ],
+)"#;
+
+    /// stand-in for GL meshes
+    struct Nothing;
+
+    impl Drawable for Nothing {
+        unsafe fn draw(&self, _shader: &Shader, _model: &Matrix4<f32>) {}
+    }
+
+    /// assets without GL objects; materials get no textures since those need a context
+    fn fake_assets(file: &SceneFile) -> SceneAssets {
+        let shaders: BTreeMap<&String, Rc<Shader>> = file
+            .shaders
+            .keys()
+            .enumerate()
+            .map(|(i, name)| (name, Rc::new(Shader { ID: i as u32 + 1 })))
+            .collect();
+        SceneAssets {
+            resources: SceneFile {
+                nodes: Vec::new(),
+                ..file.clone()
+            },
+            meshes: file
+                .meshes
+                .keys()
+                .map(|name| (name.clone(), Rc::new(Nothing) as Rc<dyn Drawable>))
+                .collect(),
+            materials: file
+                .materials
+                .iter()
+                .map(|(name, entry)| {
+                    let material = entry.material(shaders[&entry.shader].clone());
+                    (name.clone(), Rc::new(material))
+                })
+                .collect(),
+            id_shaders: file
+                .materials
+                .iter()
+                .filter_map(|(name, entry)| {
+                    let id_shader = entry.id_shader.as_ref()?;
+                    Some((name.clone(), shaders[id_shader].clone()))
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn parses_both_formats_the_same() {
+        let file = SceneFile::parse(EXAMPLE, SceneFormat::Ron).unwrap();
+        assert_eq!(
+            file.shaders["quad"].paths(),
+            (
+                "src/shaders/coordinate_1.vs".to_string(),
+                "src/shaders/coordinate_1.fs".to_string()
+            )
+        );
+        assert_eq!(
+            file.materials["crate"].textures["texture_diffuse1"],
+            "container"
+        );
+        let uniforms = &file.materials["crate"].uniforms;
+        assert_eq!(uniforms["diffuse_color"], Uniform::Vec3([1.0, 0.5, 0.25]));
+        assert_eq!(uniforms["lit"], Uniform::Bool(true));
+        assert_eq!(
+            file.materials["plain"].uniforms["alpha"],
+            Uniform::Float(0.5)
+        );
+        assert!(file.textures["container"].flip_v);
+        assert_eq!(file.nodes[0].children[1].light.unwrap().intensity, 1.0);
+        assert_eq!(file.nodes[0].children[0].rotation, [0.0, 0.0, 0.0, 1.0]);
+        assert!(!file.nodes[1].visible);
+
+        for format in [SceneFormat::Ron, SceneFormat::Json] {
+            let text = file.to_text(format).unwrap();
+            assert_eq!(SceneFile::parse(&text, format).unwrap(), file, "{}", text);
+        }
+    }
+
+    #[test]
+    fn errors_name_the_entry() {
+        let error = |text: &str| SceneFile::parse(text, SceneFormat::Ron).unwrap_err();
+        assert_eq!(
+            error(&EXAMPLE.replace(r#"mesh: "ball""#, r#"mesh: "bal""#)),
+            "node 'table/ball': unknown mesh 'bal'"
+        );
+        assert_eq!(
+            error(&EXAMPLE.replace(r#": "container" }"#, r#": "crate" }"#)),
+            "material 'crate': unknown texture 'crate'"
+        );
+        assert_eq!(
+            error(&EXAMPLE.replace(
+                r#"(shader: "quad","#,
+                r#"(shader: "quad", id_shader: "idz","#
+            )),
+            "material 'plain': unknown id_shader 'idz'"
+        );
+        assert_eq!(
+            error(&EXAMPLE.replace(r#", material: "plain""#, "")),
+            "node 'table/ball': mesh 'ball' needs a material"
+        );
+        assert_eq!(
+            error(&EXAMPLE.replace("inner_cone: 20.0", "inner_cone: 40.0")),
+            "node 'table/lamp': spot cone angles must satisfy 0 <= inner (40) <= outer (30) <= 90"
+        );
+        assert_eq!(
+            error(&EXAMPLE.replace("nodes: [", "shadows: (resolution: 0),\n    nodes: [")),
+            "shadows: resolution must be at least 1"
+        );
+        assert_eq!(
+            error(&EXAMPLE.replace("nodes: [", "shadows: (cascades: 5),\n    nodes: [")),
+            "shadows: 5 cascades is not between 1 and 4"
+        );
+        assert_eq!(
+            error(&EXAMPLE.replace("nodes: [", "shadows: (point_resolution: 0),\n    nodes: [")),
+            "shadows: point_resolution must be at least 1"
+        );
+        assert_eq!(
+            error(&EXAMPLE.replace("nodes: [", "shadows: (point_shadows: 5),\n    nodes: [")),
+            "shadows: 5 point_shadows is more than 4"
+        );
+        // syntax errors come with the position
+        let typo = error(&EXAMPLE.replace("translation: (0.0, 1.0", "translaton: (0.0, 1.0"));
+        assert!(typo.starts_with("24:"), "{}", typo);
+        assert!(typo.contains("translaton"), "{}", typo);
+        let json = SceneFile::parse(r#"{ "nodes": [{ "name": 3 }] }"#, SceneFormat::Json);
+        assert!(json.unwrap_err().contains("line 1"));
+    }
+
+    #[test]
+    fn instantiates_and_saves_the_current_scene() {
+        let file = SceneFile::parse(EXAMPLE, SceneFormat::Ron).unwrap();
+        let assets = fake_assets(&file);
+        let mut scene = Scene::new();
+        let roots = assets.instantiate(&file.nodes, &mut scene, None).unwrap();
+        assert_eq!(roots.len(), 2);
+        assert_eq!(scene.len(), 4);
+        let lamp = scene.find("lamp").unwrap();
+        let lights = scene.lights();
+        assert_eq!(lights.len(), 1);
+        assert_eq!(lights[0].node, lamp);
+        assert!((lights[0].position - Point3::new(0.0, 1.0, 0.0)).magnitude() < 1e-6);
+        match lights[0].light.kind {
+            LightKind::Spot { outer_cone, .. } => assert!((outer_cone - FRAC_PI_6).abs() < 1e-4),
+            kind => panic!("{:?}", kind),
+        }
+        assert!(scene.camera(scene.find("eye").unwrap()).is_some());
+        let table = scene.node(scene.find("table").unwrap());
+        let id_shader = table.renderable.as_ref().unwrap().id_shader.as_ref();
+        assert_eq!(id_shader.map(|shader| shader.ID), Some(1));
+        assert!(assets
+            .renderable("box", "plain")
+            .unwrap()
+            .id_shader
+            .is_none());
+
+        // the untouched scene saves as it was read
+        assert_eq!(assets.to_file(&scene), file);
+
+        // runtime changes are picked up, renderables not made from the assets are dropped
+        let table = scene.find("table").unwrap();
+        scene.set_translation(table, vec3(2.0, 0.0, 0.0));
+        let extra = scene.add_node("extra", Transform::default(), Some(table));
+        scene.set_renderable(extra, assets.renderable("ball", "crate"));
+        let foreign = scene.add_node("foreign", Transform::default(), None);
+        let mut renderable = assets.renderable("box", "plain").unwrap();
+        renderable.material = Rc::new(Material::new(renderable.material.shader.clone()));
+        scene.set_renderable(foreign, Some(renderable));
+        let saved = assets.to_file(&scene);
+        assert_eq!(saved.nodes[0].translation, [2.0, 0.0, 0.0]);
+        assert_eq!(saved.nodes[0].children[2].mesh.as_deref(), Some("ball"));
+        assert_eq!(
+            saved.nodes[0].children[2].material.as_deref(),
+            Some("crate")
+        );
+        assert_eq!(saved.nodes[2].name, "foreign");
+        assert_eq!(saved.nodes[2].mesh, None);
+        saved.validate().unwrap();
+    }
+}
@@ -6,7 +6,8 @@ use std::io::Read;
 use std::ptr;
 use std::str;
 
-use cgmath::{Matrix, Matrix4};
+use cgmath::prelude::*;
+use cgmath::{Matrix4, Vector3};
 use gl;
 use gl::types::*;
 
@@ -14,6 +15,16 @@ pub struct Shader {
     pub ID: u32,
 }
 
+/// A uniform the linker kept, as reported by `glGetActiveUniform`. Arrays are named after
+/// their first element, like `lights[0]`, with `size` elements.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActiveUniform {
+    pub name: String,
+    /// GL type, e.g. `gl::FLOAT_VEC3` or `gl::SAMPLER_2D`
+    pub kind: GLenum,
+    pub size: GLint,
+}
+
 #[derive(Debug)]
 enum ShaderType {
     VERTEX,
@@ -87,22 +98,34 @@ impl Shader {
         gl::Uniform1i(gl::GetUniformLocation(self.ID, name.as_ptr()), value);
     }
     /// ------------------------------------------------------------------------
+    pub unsafe fn setUint(&self, name: &CStr, value: u32) {
+        gl::Uniform1ui(gl::GetUniformLocation(self.ID, name.as_ptr()), value);
+    }
+    /// ------------------------------------------------------------------------
     pub unsafe fn setFloat(&self, name: &CStr, value: f32) {
         gl::Uniform1f(gl::GetUniformLocation(self.ID, name.as_ptr()), value);
     }
     /// ------------------------------------------------------------------------
-    // pub unsafe fn setVector3(&self, name: &CStr, value: &Vector3<f32>) {
-    //     gl::Uniform3fv(
-    //         gl::GetUniformLocation(self.ID, name.as_ptr()),
-    //         1,
-    //         value.as_ptr(),
-    //     );
-    // }
+    pub unsafe fn setVec2(&self, name: &CStr, x: f32, y: f32) {
+        gl::Uniform2f(gl::GetUniformLocation(self.ID, name.as_ptr()), x, y);
+    }
+    /// ------------------------------------------------------------------------
+    pub unsafe fn setVector3(&self, name: &CStr, value: &Vector3<f32>) {
+        gl::Uniform3fv(
+            gl::GetUniformLocation(self.ID, name.as_ptr()),
+            1,
+            value.as_ptr(),
+        );
+    }
+    /// ------------------------------------------------------------------------
+    pub unsafe fn setVec3(&self, name: &CStr, x: f32, y: f32, z: f32) {
+        gl::Uniform3f(gl::GetUniformLocation(self.ID, name.as_ptr()), x, y, z);
+    }
+    /// ------------------------------------------------------------------------
+    pub unsafe fn setVec4(&self, name: &CStr, x: f32, y: f32, z: f32, w: f32) {
+        gl::Uniform4f(gl::GetUniformLocation(self.ID, name.as_ptr()), x, y, z, w);
+    }
     /// ------------------------------------------------------------------------
-    // pub unsafe fn setVec3(&self, name: &CStr, x: f32, y: f32, z: f32) {
-    //     gl::Uniform3f(gl::GetUniformLocation(self.ID, name.as_ptr()), x, y, z);
-    // }
-    // /// ------------------------------------------------------------------------
     pub unsafe fn setMat4(&self, name: &CStr, mat: &Matrix4<f32>) {
         gl::UniformMatrix4fv(
             gl::GetUniformLocation(self.ID, name.as_ptr()),
@@ -111,6 +134,34 @@ impl Shader {
             mat.as_ptr(),
         );
     }
+    /// Uniforms of the linked program that are actually used; unused ones are optimised away.
+    /// ------------------------------------------------------------------------
+    pub unsafe fn active_uniforms(&self) -> Vec<ActiveUniform> {
+        let (mut count, mut max_length) = (0, 0);
+        gl::GetProgramiv(self.ID, gl::ACTIVE_UNIFORMS, &mut count);
+        gl::GetProgramiv(self.ID, gl::ACTIVE_UNIFORM_MAX_LENGTH, &mut max_length);
+        let mut name = vec![0u8; max_length.max(1) as usize];
+        (0..count.max(0) as GLuint)
+            .map(|index| {
+                let (mut length, mut size, mut kind) = (0, 0, 0);
+                gl::GetActiveUniform(
+                    self.ID,
+                    index,
+                    name.len() as GLsizei,
+                    &mut length,
+                    &mut size,
+                    &mut kind,
+                    name.as_mut_ptr() as *mut GLchar,
+                );
+                ActiveUniform {
+                    name: String::from_utf8_lossy(&name[..length.max(0) as usize]).into_owned(),
+                    kind,
+                    size,
+                }
+            })
+            .collect()
+    }
+
     unsafe fn checkCompileErrors(&self, shader: u32, type_: ShaderType) {
         let mut success = gl::FALSE as GLint;
         let mut infoLog = Vec::with_capacity(1024);
@@ -150,7 +201,6 @@ impl Shader {
                     );
                 }
             }
-            _ => println!("checkCompileErrors::UNKNOWN TYPE!!"),
         }
     }
 }
@@ -0,0 +1,238 @@
+#version 330 core
+out vec4 FragColor;
+
+in vec3 FragPos;
+in vec3 Normal;
+in vec2 TexCoords;
+
+// keep in step with MAX_*_LIGHTS in src/lighting.rs
+#define MAX_DIRECTIONAL_LIGHTS 4
+#define MAX_POINT_LIGHTS 16
+#define MAX_SPOT_LIGHTS 8
+// and MAX_SHADOW_MAPS in src/shadow.rs
+#define MAX_SHADOW_MAPS 8
+#define MAX_CASCADES 4
+#define MAX_POINT_SHADOWS 4
+
+struct DirectionalLight {
+    vec4 direction;     // the way the light travels; w: first cascade's layer + 1, 0 for none
+    vec4 color;         // rgb premultiplied by intensity
+};
+
+struct PointLight {
+    vec4 position;      // w: range, 0 for none
+    vec4 color;
+    vec4 attenuation;   // constant, linear, quadratic; w: shadow cubemap + 1, 0 for none
+};
+
+struct SpotLight {
+    vec4 position;      // w: range, 0 for none
+    vec4 direction;     // w: shadow map layer + 1, 0 for none
+    vec4 color;
+    vec4 attenuation;
+    vec4 cone;          // cosines of the inner and outer cone angles
+};
+
+// filled from a LightSet by LightBuffer, shared by every lit shader
+layout (std140) uniform Lights {
+    vec4 view_position; // w: 1 for Blinn-Phong, 0 for Phong
+    vec4 ambient;
+    vec4 light_counts;  // directional, point, spot
+    DirectionalLight directional_lights[MAX_DIRECTIONAL_LIGHTS];
+    PointLight point_lights[MAX_POINT_LIGHTS];
+    SpotLight spot_lights[MAX_SPOT_LIGHTS];
+    vec4 shadow_params; // bias, slope bias, PCF radius in texels; w: 1 to tint the cascades
+    vec4 point_shadow_far;  // the distance each cubemap's depth is divided by
+    vec4 view_forward;  // the camera's direction; w: cascades per directional light
+    vec4 cascade_splits;    // view depth where each cascade ends
+    mat4 shadow_matrices[MAX_SHADOW_MAPS];
+};
+
+// depth as seen from each shadow casting light, a layer per cascade or spot light
+uniform sampler2DArray shadow_maps;
+// distance from each shadowed point light, over its far distance
+uniform samplerCube point_shadow_maps[MAX_POINT_SHADOWS];
+
+// directions to spread point light PCF samples over, scaled by the filter radius
+const vec3 pcf_offsets[20] = vec3[](
+    vec3(1, 1, 1), vec3(1, -1, 1), vec3(-1, -1, 1), vec3(-1, 1, 1),
+    vec3(1, 1, -1), vec3(1, -1, -1), vec3(-1, -1, -1), vec3(-1, 1, -1),
+    vec3(1, 1, 0), vec3(1, -1, 0), vec3(-1, -1, 0), vec3(-1, 1, 0),
+    vec3(1, 0, 1), vec3(-1, 0, 1), vec3(1, 0, -1), vec3(-1, 0, -1),
+    vec3(0, 1, 1), vec3(0, -1, 1), vec3(0, -1, -1), vec3(0, 1, -1)
+);
+
+// maps are multiplied by their colours; use a white map to go by the colour alone
+struct Material {
+    sampler2D diffuse_map;
+    sampler2D specular_map;
+    sampler2D emissive_map;
+    vec3 diffuse_color;
+    vec3 specular_color;
+    vec3 emissive_color;
+    float shininess;
+};
+
+uniform Material material;
+
+// diffuse and specular from one light arriving along `to_light`
+vec3 shade(vec3 to_light, vec3 radiance, vec3 normal, vec3 to_view, vec3 diffuse, vec3 specular)
+{
+    float lambert = max(dot(normal, to_light), 0.0);
+    float highlight;
+    if (view_position.w > 0.5) {
+        vec3 halfway = normalize(to_light + to_view);
+        highlight = pow(max(dot(normal, halfway), 0.0), material.shininess);
+    } else {
+        vec3 reflected = reflect(-to_light, normal);
+        highlight = pow(max(dot(to_view, reflected), 0.0), material.shininess);
+    }
+    // no highlight on faces turned away from the light
+    if (lambert <= 0.0)
+        highlight = 0.0;
+    return radiance * (diffuse * lambert + specular * highlight);
+}
+
+// distance falloff, faded out smoothly to nothing at the range when there is one
+float falloff(vec4 position, vec4 attenuation)
+{
+    float d = length(position.xyz - FragPos);
+    float fade = 1.0 / (attenuation.x + attenuation.y * d + attenuation.z * d * d);
+    if (position.w > 0.0) {
+        float ratio = d / position.w;
+        fade *= pow(clamp(1.0 - ratio * ratio * ratio * ratio, 0.0, 1.0), 2.0);
+    }
+    return fade;
+}
+
+// how much of the light arriving along `to_light` reaches the fragment, 0 in full shadow
+float shadow(float layer, vec3 normal, vec3 to_light)
+{
+    if (layer < 0.5)
+        return 1.0;
+    vec4 clip = shadow_matrices[int(layer) - 1] * vec4(FragPos, 1.0);
+    vec3 coords = clip.xyz / clip.w * 0.5 + 0.5;
+    // past the far plane of the light nothing is drawn to cast a shadow
+    if (coords.z > 1.0)
+        return 1.0;
+    // surfaces at a grazing angle to the light cover more depth per texel
+    float facing = clamp(dot(normal, to_light), 0.05, 1.0);
+    float slope = sqrt(1.0 - facing * facing) / facing;
+    float depth = coords.z - (shadow_params.x + shadow_params.y * min(slope, 10.0));
+    // percentage closer filtering: the share of nearby texels the fragment is in front of
+    int radius = int(shadow_params.z);
+    vec2 texel = 1.0 / vec2(textureSize(shadow_maps, 0).xy);
+    float lit = 0.0;
+    for (int x = -radius; x <= radius; x++) {
+        for (int y = -radius; y <= radius; y++) {
+            vec2 uv = coords.xy + vec2(x, y) * texel;
+            float closest = texture(shadow_maps, vec3(uv, layer - 1.0)).r;
+            lit += depth > closest ? 0.0 : 1.0;
+        }
+    }
+    float side = float(2 * radius + 1);
+    return lit / (side * side);
+}
+
+// the cascade covering the fragment, MAX_CASCADES past the last one
+int cascade_index()
+{
+    float depth = dot(FragPos - view_position.xyz, view_forward.xyz);
+    for (int i = 0; i < MAX_CASCADES; i++) {
+        if (i < int(view_forward.w) && depth <= cascade_splits[i])
+            return i;
+    }
+    return MAX_CASCADES;
+}
+
+// debug colours for the cascades, nearest first
+const vec3 cascade_tints[MAX_CASCADES] = vec3[](
+    vec3(1.0, 0.3, 0.3), vec3(0.3, 1.0, 0.3), vec3(0.3, 0.3, 1.0), vec3(1.0, 1.0, 0.3)
+);
+
+// GLSL 3.30 only indexes sampler arrays with constants
+float point_shadow_depth(int cube, vec3 direction)
+{
+    if (cube == 0)
+        return texture(point_shadow_maps[0], direction).r;
+    if (cube == 1)
+        return texture(point_shadow_maps[1], direction).r;
+    if (cube == 2)
+        return texture(point_shadow_maps[2], direction).r;
+    return texture(point_shadow_maps[3], direction).r;
+}
+
+// `shadow` for a point light, comparing distances along the direction from the light
+float point_shadow(float cube, vec3 position, vec3 normal, vec3 to_light)
+{
+    if (cube < 0.5)
+        return 1.0;
+    int index = int(cube) - 1;
+    float far = point_shadow_far[index];
+    vec3 direction = FragPos - position;
+    float facing = clamp(dot(normal, to_light), 0.05, 1.0);
+    float slope = sqrt(1.0 - facing * facing) / facing;
+    float depth = length(direction) / far
+                  - (shadow_params.x + shadow_params.y * min(slope, 10.0));
+    if (depth > 1.0)
+        return 1.0;
+    if (shadow_params.z < 0.5)
+        return depth > point_shadow_depth(index, direction) ? 0.0 : 1.0;
+    // a cubemap texel covers about 2 * distance / resolution at that distance
+    float texel = 2.0 * length(direction) / float(textureSize(point_shadow_maps[0], 0).x);
+    float radius = shadow_params.z * texel;
+    float lit = 0.0;
+    for (int i = 0; i < 20; i++) {
+        float closest = point_shadow_depth(index, direction + pcf_offsets[i] * radius);
+        lit += depth > closest ? 0.0 : 1.0;
+    }
+    return lit / 20.0;
+}
+
+void main()
+{
+    vec3 normal = normalize(Normal);
+    // two-sided surfaces are lit from the side the camera sees
+    vec3 to_view = normalize(view_position.xyz - FragPos);
+    if (dot(normal, to_view) < 0.0)
+        normal = -normal;
+
+    vec4 albedo = texture(material.diffuse_map, TexCoords);
+    vec3 diffuse = albedo.rgb * material.diffuse_color;
+    vec3 specular = texture(material.specular_map, TexCoords).rgb * material.specular_color;
+    vec3 emissive = texture(material.emissive_map, TexCoords).rgb * material.emissive_color;
+
+    vec3 color = ambient.rgb * diffuse + emissive;
+    // directional lights shadow the view up to the last split, in cascades of their own
+    int cascade = cascade_index();
+    for (int i = 0; i < int(light_counts.x); i++) {
+        DirectionalLight light = directional_lights[i];
+        vec3 to_light = -normalize(light.direction.xyz);
+        float layer = light.direction.w;
+        if (layer > 0.5)
+            layer = cascade < MAX_CASCADES ? layer + float(cascade) : 0.0;
+        vec3 radiance = light.color.rgb * shadow(layer, normal, to_light);
+        color += shade(to_light, radiance, normal, to_view, diffuse, specular);
+    }
+    for (int i = 0; i < int(light_counts.y); i++) {
+        PointLight light = point_lights[i];
+        vec3 to_light = normalize(light.position.xyz - FragPos);
+        vec3 radiance = light.color.rgb * falloff(light.position, light.attenuation)
+                        * point_shadow(light.attenuation.w, light.position.xyz, normal, to_light);
+        color += shade(to_light, radiance, normal, to_view, diffuse, specular);
+    }
+    for (int i = 0; i < int(light_counts.z); i++) {
+        SpotLight light = spot_lights[i];
+        vec3 to_light = normalize(light.position.xyz - FragPos);
+        // full strength inside the inner cone, fading to nothing at the outer one
+        float angle = dot(-to_light, normalize(light.direction.xyz));
+        float cone = clamp((angle - light.cone.y) / max(light.cone.x - light.cone.y, 1e-4),
+                           0.0, 1.0);
+        vec3 radiance = light.color.rgb * falloff(light.position, light.attenuation) * cone
+                        * shadow(light.direction.w, normal, to_light);
+        color += shade(to_light, radiance, normal, to_view, diffuse, specular);
+    }
+    if (shadow_params.w > 0.5 && cascade < int(view_forward.w))
+        color *= cascade_tints[cascade];
+    FragColor = vec4(color, albedo.a);
+}
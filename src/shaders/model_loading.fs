@@ -0,0 +1,40 @@
+#version 330 core
+out vec4 FragColor;
+
+in vec2 TexCoords;
+in vec3 ViewNormal;
+in vec4 ViewTangent;
+in vec4 VertexColor;
+
+uniform sampler2D texture_diffuse1;
+uniform vec3 diffuse_color;
+// tangent space normal map, flat (0.5, 0.5, 1.0) when the material has none
+uniform sampler2D texture_normal;
+// scales the map's X and Y; 0, the default for materials that don't set it, ignores the map
+uniform float normal_scale;
+// fragments less opaque than this are dropped, 0 keeps them all
+uniform float alpha_cutoff;
+// the colours are linear and have to be encoded for the (non-sRGB) framebuffer
+uniform bool srgb_output;
+
+void main()
+{
+    vec3 normal = normalize(ViewNormal);
+    vec3 tangent = ViewTangent.xyz - normal * dot(normal, ViewTangent.xyz);
+    if (normal_scale != 0.0 && dot(tangent, tangent) > 1e-8) {
+        tangent = normalize(tangent);
+        vec3 bitangent = cross(normal, tangent) * ViewTangent.w;
+        vec3 mapped = texture(texture_normal, TexCoords).xyz * 2.0 - 1.0;
+        mapped.xy *= normal_scale;
+        normal = normalize(mat3(tangent, bitangent, normal) * mapped);
+    }
+    // light from the camera so the shape reads without a lighting setup
+    float light = 0.3 + 0.7 * max(normal.z, 0.0);
+    vec4 albedo = texture(texture_diffuse1, TexCoords) * vec4(diffuse_color, 1.0) * VertexColor;
+    if (albedo.a < alpha_cutoff)
+        discard;
+    vec3 color = albedo.rgb * light;
+    if (srgb_output)
+        color = pow(color, vec3(1.0 / 2.2));
+    FragColor = vec4(color, albedo.a);
+}
\ No newline at end of file
@@ -0,0 +1,27 @@
+#version 330 core
+layout (location = 0) in vec3 aPos;
+layout (location = 1) in vec3 aNormal;
+layout (location = 2) in vec2 aTexCoords;
+layout (location = 3) in vec4 aTangent;
+layout (location = 4) in vec4 aColor;
+
+out vec2 TexCoords;
+out vec3 ViewNormal;
+out vec4 ViewTangent;
+out vec4 VertexColor;
+
+uniform mat4 model;
+uniform mat4 view;
+uniform mat4 projection;
+
+void main()
+{
+    TexCoords = aTexCoords;
+    VertexColor = aColor;
+    ViewNormal = mat3(transpose(inverse(view * model))) * aNormal;
+    // tangents lie in the surface and transform like directions; a mirroring model matrix
+    // flips the handedness of the frame
+    float handedness = aTangent.w * sign(determinant(mat3(model)));
+    ViewTangent = vec4(mat3(view * model) * aTangent.xyz, handedness);
+    gl_Position = projection * view * model * vec4(aPos, 1.0);
+}
\ No newline at end of file
@@ -0,0 +1,10 @@
+#version 330 core
+// written to an R32UI attachment, 0 means nothing was drawn
+out uint ObjectId;
+
+uniform uint object_id;
+
+void main()
+{
+    ObjectId = object_id;
+}
@@ -0,0 +1,12 @@
+#version 330 core
+in vec3 FragPos;
+
+uniform vec3 light_position;
+uniform float far_plane;
+
+// the distance to the light rather than the projected depth, so one lookup along the
+// direction from the light compares the same thing on every face
+void main()
+{
+    gl_FragDepth = length(FragPos - light_position) / far_plane;
+}
@@ -0,0 +1,778 @@
+use std::ffi::{CStr, CString};
+
+use cgmath::prelude::*;
+use cgmath::{ortho, perspective, Deg, Matrix4, Point3, Rad, Vector3};
+use serde::{Deserialize, Serialize};
+
+use crate::bounds::Aabb;
+use crate::framebuffer::{DepthArrayTarget, DepthCubeTarget};
+use crate::gltf_scene::LightKind;
+use crate::lighting::DEFAULT_RANGE;
+use crate::scene::{NodeId, PlacedLight, Scene};
+use crate::shader::Shader;
+
+/// Shadow maps the `Lights` block has matrices for, shared between the cascades of
+/// directional lights and spot lights; keep in step with `src/shaders/lit.fs`.
+pub const MAX_SHADOW_MAPS: usize = 8;
+
+/// Cascades a directional light's shadow can be split into at most.
+pub const MAX_CASCADES: usize = 4;
+
+/// Point lights with a shadow cubemap at most; keep in step with `src/shaders/lit.fs`.
+pub const MAX_POINT_SHADOWS: usize = 4;
+
+/// texture unit of the shadow maps, well clear of the units materials use; the point light
+/// cubemaps follow on the next units
+pub const SHADOW_UNIT: u32 = 8;
+
+/// near plane of spot and point light shadow frusta
+const SPOT_NEAR: f32 = 0.1;
+
+/// view direction and up vector of the cubemap faces +X, -X, +Y, -Y, +Z and -Z
+const CUBE_FACES: [([f32; 3], [f32; 3]); 6] = [
+    ([1.0, 0.0, 0.0], [0.0, -1.0, 0.0]),
+    ([-1.0, 0.0, 0.0], [0.0, -1.0, 0.0]),
+    ([0.0, 1.0, 0.0], [0.0, 0.0, 1.0]),
+    ([0.0, -1.0, 0.0], [0.0, 0.0, -1.0]),
+    ([0.0, 0.0, 1.0], [0.0, -1.0, 0.0]),
+    ([0.0, 0.0, -1.0], [0.0, -1.0, 0.0]),
+];
+
+/// How shadows are rendered, set per scene file as `shadows: (resolution: 4096, ...)`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ShadowSettings {
+    /// width and height of each shadow map in texels
+    pub resolution: u32,
+    /// depth offset against shadow acne on surfaces facing the light
+    pub bias: f32,
+    /// extra offset growing with the slope of the surface away from the light
+    pub slope_bias: f32,
+    /// PCF kernel radius in texels, 0 for hard edges
+    pub pcf_radius: u32,
+    /// how far from the camera directional lights cast shadows
+    pub distance: f32,
+    /// shadow maps each directional light's share of the view is split into, nearest first,
+    /// from 1 to `MAX_CASCADES`
+    pub cascades: u32,
+    /// blend between evenly spaced (0) and logarithmic (1) cascade splits
+    pub split_lambda: f32,
+    /// fit cascades to spheres snapped to whole texels, so their shadows don't shimmer as
+    /// the camera moves, at the cost of some resolution
+    pub stabilize: bool,
+    /// size of each face of a point light's shadow cubemap in texels
+    pub point_resolution: u32,
+    /// point lights closest to the camera that get shadows, up to `MAX_POINT_SHADOWS`
+    pub point_shadows: u32,
+}
+
+impl Default for ShadowSettings {
+    fn default() -> ShadowSettings {
+        ShadowSettings {
+            resolution: 2048,
+            bias: 0.0005,
+            slope_bias: 0.002,
+            pcf_radius: 1,
+            distance: 40.0,
+            cascades: 3,
+            split_lambda: 0.75,
+            stabilize: true,
+            point_resolution: 1024,
+            point_shadows: MAX_POINT_SHADOWS as u32,
+        }
+    }
+}
+
+impl ShadowSettings {
+    /// cascades per directional light, clamped to what the `Lights` block supports
+    pub fn cascade_count(&self) -> usize {
+        (self.cascades as usize).clamp(1, MAX_CASCADES)
+    }
+
+    /// Check the map sizes against the GL limits from `texture_size_limits`.
+    /// ------------------------------------------------------------------------
+    pub fn check_limits(&self, (max_size, max_cube_size): (u32, u32)) -> Result<(), String> {
+        if self.resolution > max_size {
+            return Err(format!(
+                "shadows: resolution {} is more than GL_MAX_TEXTURE_SIZE {}",
+                self.resolution, max_size
+            ));
+        }
+        if self.point_resolution > max_cube_size {
+            return Err(format!(
+                "shadows: point_resolution {} is more than GL_MAX_CUBE_MAP_TEXTURE_SIZE {}",
+                self.point_resolution, max_cube_size
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// `GL_MAX_TEXTURE_SIZE` and `GL_MAX_CUBE_MAP_TEXTURE_SIZE`; needs a current GL context.
+/// ------------------------------------------------------------------------
+pub fn texture_size_limits() -> (u32, u32) {
+    let (mut max_size, mut max_cube_size) = (0, 0);
+    unsafe {
+        gl::GetIntegerv(gl::MAX_TEXTURE_SIZE, &mut max_size);
+        gl::GetIntegerv(gl::MAX_CUBE_MAP_TEXTURE_SIZE, &mut max_cube_size);
+    }
+    (max_size.max(0) as u32, max_cube_size.max(0) as u32)
+}
+
+/// Where a light's shadow map was rendered from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LightShadow {
+    pub node: NodeId,
+    /// light projection times light view: world space to the shadow map's clip space
+    pub light_space: Matrix4<f32>,
+    /// view depth the cascade reaches, for directional lights; 0 for spot lights
+    pub split: f32,
+}
+
+/// Where a point light's shadow cubemap was rendered from; it holds distances from the
+/// light divided by `far`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PointShadow {
+    pub node: NodeId,
+    pub far: f32,
+}
+
+/// View depths of a camera's near and far planes.
+/// ------------------------------------------------------------------------
+pub fn view_depth_range(view: &Matrix4<f32>, projection: &Matrix4<f32>) -> (f32, f32) {
+    let slice = frustum_slice(view, projection, 0.0, f32::MAX);
+    let depth = |point: Point3<f32>| -view.transform_point(point).z;
+    (depth(slice[0]), depth(slice[4]))
+}
+
+/// Far depths of `count` cascades covering view depths `near` to `far`: the "practical"
+/// scheme, blending logarithmic splits (`lambda` 1), which keep the texel density even on
+/// screen, with uniform ones (`lambda` 0), which don't crowd them all close to the camera.
+/// ------------------------------------------------------------------------
+pub fn cascade_splits(near: f32, far: f32, count: usize, lambda: f32) -> Vec<f32> {
+    let near = near.max(1e-3);
+    let far = far.max(near);
+    (1..=count)
+        .map(|i| {
+            let fraction = i as f32 / count as f32;
+            let logarithmic = near * (far / near).powf(fraction);
+            let uniform = near + (far - near) * fraction;
+            lambda * logarithmic + (1.0 - lambda) * uniform
+        })
+        .collect()
+}
+
+/// Corners of the part of a camera's view frustum between view distances `near` and `far`,
+/// clamped to the frustum: the near four first, each followed by the far corner on its edge.
+/// ------------------------------------------------------------------------
+pub fn frustum_slice(
+    view: &Matrix4<f32>,
+    projection: &Matrix4<f32>,
+    near: f32,
+    far: f32,
+) -> [Point3<f32>; 8] {
+    let inverse = (projection * view)
+        .invert()
+        .unwrap_or_else(Matrix4::identity);
+    let depth = |point: Point3<f32>| -view.transform_point(point).z;
+    let mut corners = [Point3::origin(); 8];
+    for (i, (x, y)) in [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)]
+        .into_iter()
+        .enumerate()
+    {
+        let start = inverse.transform_point(Point3::new(x, y, -1.0));
+        let end = inverse.transform_point(Point3::new(x, y, 1.0));
+        // view depth is linear along an edge, for perspective and orthographic cameras alike
+        let (from, to) = (depth(start), depth(end));
+        let at = |distance: f32| {
+            let t = ((distance - from) / (to - from)).clamp(0.0, 1.0);
+            start + (end - start) * t
+        };
+        corners[i] = at(near);
+        corners[i + 4] = at(far);
+    }
+    corners
+}
+
+/// An up vector for looking along `direction` that is never parallel to it.
+fn up_for(direction: Vector3<f32>) -> Vector3<f32> {
+    if direction.y.abs() > 0.99 {
+        Vector3::unit_z()
+    } else {
+        Vector3::unit_y()
+    }
+}
+
+/// Light space of a directional light shining along `direction`, its orthographic frustum
+/// fitted tightly around `slice` (see `frustum_slice`) and stretched back towards the light
+/// over `casters`, which can throw shadows into the slice from outside it.
+/// ------------------------------------------------------------------------
+pub fn fit_directional(
+    direction: Vector3<f32>,
+    slice: &[Point3<f32>; 8],
+    casters: &Aabb,
+) -> Matrix4<f32> {
+    let view = Matrix4::look_to_rh(Point3::centroid(slice), direction, up_for(direction));
+    let bounds = Aabb::from_points(slice.iter().map(|&corner| view.transform_point(corner)));
+    // the light looks down its -Z axis, so whatever is closer to it has a larger z
+    let mut closest = bounds.max.z;
+    if !casters.is_empty() {
+        closest = closest.max(casters.transformed(&view).max.z);
+    }
+    let projection = ortho(
+        bounds.min.x,
+        bounds.max.x,
+        bounds.min.y,
+        bounds.max.y,
+        -closest,
+        -bounds.min.z,
+    );
+    projection * view
+}
+
+/// `fit_directional` for cascades that stay still: the frustum fits the sphere around
+/// `slice`, whose size doesn't change as the camera turns, and moves in whole texels of a
+/// `resolution` sized map, so the same world positions keep landing on the same texels.
+/// ------------------------------------------------------------------------
+pub fn fit_stable(
+    direction: Vector3<f32>,
+    slice: &[Point3<f32>; 8],
+    casters: &Aabb,
+    resolution: u32,
+) -> Matrix4<f32> {
+    let center = Point3::centroid(slice);
+    let radius = slice
+        .iter()
+        .map(|corner| corner.distance(center))
+        .fold(0.0, f32::max);
+    // room for the snapping below, rounded so float noise doesn't change the texel size
+    let resolution = resolution.max(1) as f32;
+    let radius = (radius * (1.0 + 4.0 / resolution) * 16.0).ceil() / 16.0;
+    let texel = 2.0 * radius / resolution;
+    // rotation only, so the texel grid is fixed in the world
+    let view = Matrix4::look_to_rh(Point3::origin(), direction, up_for(direction));
+    let middle = view.transform_point(center);
+    let (x, y) = (
+        (middle.x / texel).floor() * texel,
+        (middle.y / texel).floor() * texel,
+    );
+    let mut closest = middle.z + radius;
+    if !casters.is_empty() {
+        closest = closest.max(casters.transformed(&view).max.z);
+    }
+    let projection = ortho(
+        x - radius,
+        x + radius,
+        y - radius,
+        y + radius,
+        -closest,
+        radius - middle.z,
+    );
+    projection * view
+}
+
+/// Light space of a spot light: a perspective frustum covering its outer cone, out to its
+/// range. `None` for other lights.
+/// ------------------------------------------------------------------------
+pub fn spot_light_space(light: &PlacedLight) -> Option<Matrix4<f32>> {
+    let LightKind::Spot { outer_cone, .. } = light.light.kind else {
+        return None;
+    };
+    let view = Matrix4::look_to_rh(light.position, light.direction, up_for(light.direction));
+    // a cone of 90 degrees would need an infinitely wide map
+    let fov = Rad((2.0 * outer_cone).min(Rad::from(Deg(170.0)).0));
+    let far = light.light.range.unwrap_or(DEFAULT_RANGE);
+    Some(perspective(fov, 1.0, SPOT_NEAR, far.max(SPOT_NEAR * 2.0)) * view)
+}
+
+/// Light space of each face of a point light's cubemap, in `CUBE_FACES` order: 90 degree
+/// frusta from `position` out to `far`.
+/// ------------------------------------------------------------------------
+pub fn cube_light_spaces(position: Point3<f32>, far: f32) -> [Matrix4<f32>; 6] {
+    let projection = perspective(Deg(90.0), 1.0, SPOT_NEAR, far.max(SPOT_NEAR * 2.0));
+    CUBE_FACES.map(|(direction, up)| {
+        projection * Matrix4::look_to_rh(position, direction.into(), up.into())
+    })
+}
+
+/// The point lights that get a shadow cubemap: up to `budget` of them, closest to `eye`
+/// first, as indices into `lights`.
+/// ------------------------------------------------------------------------
+pub fn shadowed_point_lights(
+    lights: &[PlacedLight],
+    eye: Point3<f32>,
+    budget: usize,
+) -> Vec<usize> {
+    let mut points: Vec<usize> = (0..lights.len())
+        .filter(|&i| matches!(lights[i].light.kind, LightKind::Point))
+        .collect();
+    points.sort_by(|&a, &b| {
+        let distance = |i: usize| lights[i].position.distance2(eye);
+        distance(a).total_cmp(&distance(b))
+    });
+    points.truncate(budget.min(MAX_POINT_SHADOWS));
+    points
+}
+
+/// Shadow map layers `light_spaces` fills for `lights`: the cascades of each directional
+/// light and one per spot light, leaving out the same lights once `MAX_SHADOW_MAPS` is full.
+/// ------------------------------------------------------------------------
+pub fn shadow_layers(settings: &ShadowSettings, lights: &[PlacedLight]) -> usize {
+    let cascades = settings.cascade_count();
+    lights
+        .iter()
+        .fold(0, |layers, light| match light.light.kind {
+            LightKind::Directional if layers + cascades <= MAX_SHADOW_MAPS => layers + cascades,
+            LightKind::Spot { .. } if layers < MAX_SHADOW_MAPS => layers + 1,
+            _ => layers,
+        })
+}
+
+/// The shadow maps for directional and spot `lights`, in order, for a camera with `view`
+/// and `projection`: the cascades of directional lights split the view out to
+/// `settings.distance`. Lights whose maps don't all fit in `MAX_SHADOW_MAPS` are left out.
+/// ------------------------------------------------------------------------
+pub fn light_spaces(
+    settings: &ShadowSettings,
+    lights: &[PlacedLight],
+    casters: &Aabb,
+    view: &Matrix4<f32>,
+    projection: &Matrix4<f32>,
+) -> Vec<LightShadow> {
+    let (near, far) = view_depth_range(view, projection);
+    let far = far.min(settings.distance);
+    let splits = cascade_splits(near, far, settings.cascade_count(), settings.split_lambda);
+    let mut shadows = Vec::new();
+    for light in lights {
+        match light.light.kind {
+            LightKind::Directional if shadows.len() + splits.len() <= MAX_SHADOW_MAPS => {
+                let mut from = near;
+                for &split in &splits {
+                    let slice = frustum_slice(view, projection, from, split);
+                    let light_space = if settings.stabilize {
+                        fit_stable(light.direction, &slice, casters, settings.resolution)
+                    } else {
+                        fit_directional(light.direction, &slice, casters)
+                    };
+                    shadows.push(LightShadow {
+                        node: light.node,
+                        light_space,
+                        split,
+                    });
+                    from = split;
+                }
+            }
+            LightKind::Spot { .. } if shadows.len() < MAX_SHADOW_MAPS => {
+                if let Some(light_space) = spot_light_space(light) {
+                    shadows.push(LightShadow {
+                        node: light.node,
+                        light_space,
+                        split: 0.0,
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+    shadows
+}
+
+/// Renders the shadow maps of a scene's directional and spot lights into the layers of a
+/// depth texture array, `cascade_count` per directional light and one per spot light up to
+/// `MAX_SHADOW_MAPS`, and the distance cubemaps of point lights. Lit shaders find the
+/// matrices in the `Lights` block, the maps on `SHADOW_UNIT` and the cubemaps on the units
+/// after it.
+pub struct ShadowMaps {
+    pub settings: ShadowSettings,
+    /// layers of `target`, what the lights it was made for need
+    layers: usize,
+    target: DepthArrayTarget,
+    cubes: Vec<DepthCubeTarget>,
+    shader: Shader,
+    point_shader: Shader,
+}
+
+impl ShadowMaps {
+    /// Create a depth texture array with the layers `lights` need and a cubemap for each
+    /// point light that can get a shadow; needs a current GL context.
+    /// ------------------------------------------------------------------------
+    pub fn new(settings: ShadowSettings, lights: &[PlacedLight]) -> ShadowMaps {
+        let layers = shadow_layers(&settings, lights);
+        let points = lights
+            .iter()
+            .filter(|light| matches!(light.light.kind, LightKind::Point))
+            .count();
+        let cubes = points
+            .min(settings.point_shadows as usize)
+            .min(MAX_POINT_SHADOWS);
+        ShadowMaps {
+            settings,
+            layers,
+            target: DepthArrayTarget::new(settings.resolution as i32, layers as i32),
+            cubes: (0..cubes)
+                .map(|_| DepthCubeTarget::new(settings.point_resolution as i32))
+                .collect(),
+            shader: Shader::new("src/shaders/shadow_depth.vs", "src/shaders/shadow_depth.fs"),
+            point_shader: Shader::new("src/shaders/point_shadow.vs", "src/shaders/point_shadow.fs"),
+        }
+    }
+
+    /// Point `shader`'s `shadow_maps` and `point_shadow_maps` samplers at their units; shaders
+    /// without them ignore it. Lit shaders need this even when there are no maps to bind, so
+    /// their samplers of different types don't all share unit 0.
+    /// ------------------------------------------------------------------------
+    pub fn attach(shader: &Shader) {
+        unsafe {
+            shader.useProgram();
+            shader.setInt(c_str!("shadow_maps"), SHADOW_UNIT as i32);
+            for cube in 0..MAX_POINT_SHADOWS {
+                let name = CString::new(format!("point_shadow_maps[{}]", cube)).unwrap();
+                shader.setInt(&name, (SHADOW_UNIT + 1) as i32 + cube as i32);
+            }
+        }
+    }
+
+    /// Render the shadow maps of `light_spaces`, a layer each. Leaves the default
+    /// framebuffer bound with the viewport at the map size, so bind the real target
+    /// afterwards.
+    /// ------------------------------------------------------------------------
+    pub unsafe fn render(
+        &self,
+        scene: &Scene,
+        lights: &[PlacedLight],
+        view: &Matrix4<f32>,
+        projection: &Matrix4<f32>,
+    ) -> Vec<LightShadow> {
+        if lights
+            .iter()
+            .all(|light| matches!(light.light.kind, LightKind::Point))
+        {
+            return Vec::new();
+        }
+        let (everything, _) = scene.draw_list(None);
+        let casters = everything.iter().fold(Aabb::empty(), |bounds, &id| {
+            bounds.union(&scene.world_bounds(id))
+        });
+        let mut shadows = light_spaces(&self.settings, lights, &casters, view, projection);
+        // lights added since the maps were made go without
+        shadows.truncate(self.layers);
+
+        gl::Enable(gl::DEPTH_TEST);
+        for (layer, shadow) in shadows.iter().enumerate() {
+            let (mut nodes, _) = scene.view_list(&Matrix4::identity(), &shadow.light_space);
+            // a lamp drawn around the light would shadow everything
+            nodes.retain(|&id| id != shadow.node);
+            self.target.bind_layer(layer as i32);
+            scene.draw_depth(&nodes, &shadow.light_space, &self.shader);
+        }
+        gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        shadows
+    }
+
+    /// Render the distance cubemaps of the point lights picked by `shadowed_point_lights`
+    /// for a camera at `eye`, six faces each. Leaves the default framebuffer bound like
+    /// `render`.
+    /// ------------------------------------------------------------------------
+    pub unsafe fn render_points(
+        &self,
+        scene: &Scene,
+        lights: &[PlacedLight],
+        eye: Point3<f32>,
+    ) -> Vec<PointShadow> {
+        let shadowed = shadowed_point_lights(lights, eye, self.settings.point_shadows as usize);
+        let mut shadows = Vec::new();
+        gl::Enable(gl::DEPTH_TEST);
+        for (light, cube) in shadowed.into_iter().map(|i| &lights[i]).zip(&self.cubes) {
+            let far = light.light.range.unwrap_or(DEFAULT_RANGE);
+            self.point_shader.useProgram();
+            let p = light.position;
+            self.point_shader
+                .setVec3(c_str!("light_position"), p.x, p.y, p.z);
+            self.point_shader.setFloat(c_str!("far_plane"), far);
+            for (face, light_space) in cube_light_spaces(p, far).iter().enumerate() {
+                let (mut nodes, _) = scene.view_list(&Matrix4::identity(), light_space);
+                nodes.retain(|&id| id != light.node);
+                cube.bind_face(face as u32);
+                scene.draw_depth(&nodes, light_space, &self.point_shader);
+            }
+            shadows.push(PointShadow {
+                node: light.node,
+                far,
+            });
+        }
+        gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        shadows
+    }
+
+    /// bind the maps to `SHADOW_UNIT` and the cubemaps after it for drawing the lit scene
+    pub unsafe fn bind(&self) {
+        self.target.bind_texture(SHADOW_UNIT);
+        for (i, cube) in self.cubes.iter().enumerate() {
+            cube.bind_texture(SHADOW_UNIT + 1 + i as u32);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene::Light;
+    use cgmath::{vec3, Vector4};
+
+    fn clip(light_space: &Matrix4<f32>, point: Point3<f32>) -> Point3<f32> {
+        Point3::from_homogeneous(light_space * point.to_homogeneous())
+    }
+
+    fn inside(point: Point3<f32>) -> bool {
+        let limit = 1.0 + 1e-4;
+        point.x.abs() <= limit && point.y.abs() <= limit && point.z.abs() <= limit
+    }
+
+    #[test]
+    fn slices_cut_the_view_frustum_at_distances() {
+        let view = Matrix4::look_at_rh(
+            Point3::new(0.0, 0.0, 5.0),
+            Point3::origin(),
+            Vector3::unit_y(),
+        );
+        let projection = perspective(Deg(90.0), 2.0, 0.1, 100.0);
+        let slice = frustum_slice(&view, &projection, 1.0, 10.0);
+        let depth = |point: Point3<f32>| -view.transform_point(point).z;
+        for corner in &slice[..4] {
+            assert!((depth(*corner) - 1.0).abs() < 1e-3);
+        }
+        // a 90 degree frustum is as tall as it is far, and twice as wide at this aspect
+        assert!((slice[6] - Point3::new(20.0, 10.0, -5.0)).magnitude() < 1e-2);
+        // distances past the far plane stop at it
+        let whole = frustum_slice(&view, &projection, 0.0, 1000.0);
+        assert!((depth(whole[0]) - 0.1).abs() < 1e-3 && (depth(whole[4]) - 100.0).abs() < 1e-2);
+    }
+
+    #[test]
+    fn directional_frustum_covers_the_slice_and_casters() {
+        let view = Matrix4::look_at_rh(
+            Point3::new(0.0, 2.0, 5.0),
+            Point3::origin(),
+            Vector3::unit_y(),
+        );
+        let projection = perspective(Deg(60.0), 1.5, 0.1, 100.0);
+        let slice = frustum_slice(&view, &projection, 0.1, 20.0);
+        let direction = vec3(-1.0, -2.0, -0.5).normalize();
+        // a tall tower behind the camera still shadows what it looks at
+        let tower = Aabb::new(Point3::new(-1.0, 0.0, 8.0), Point3::new(1.0, 40.0, 10.0));
+        let light_space = fit_directional(direction, &slice, &tower);
+        for corner in slice {
+            assert!(inside(clip(&light_space, corner)), "{:?}", corner);
+        }
+        // every tower corner is in front of the near plane
+        for corner in tower.corners() {
+            assert!(clip(&light_space, corner).z >= -1.0 - 1e-4);
+        }
+        // straight down needs a different up vector
+        let noon = fit_directional(-Vector3::unit_y(), &slice, &Aabb::empty());
+        let column = noon * Vector4::new(0.0, 1.0, 0.0, 0.0);
+        assert!(column.x.abs() < 1e-6 && column.y.abs() < 1e-6);
+    }
+
+    #[test]
+    fn cascade_splits_blend_uniform_and_logarithmic() {
+        let close = |a: &[f32], b: &[f32]| a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-3);
+        let uniform = cascade_splits(1.0, 100.0, 4, 0.0);
+        assert!(
+            close(&uniform, &[25.75, 50.5, 75.25, 100.0]),
+            "{:?}",
+            uniform
+        );
+        let logarithmic = cascade_splits(1.0, 100.0, 4, 1.0);
+        assert!(
+            close(&logarithmic, &[3.1623, 10.0, 31.623, 100.0]),
+            "{:?}",
+            logarithmic
+        );
+        // in between, every split lies between the two and the last still ends at far
+        let blended = cascade_splits(1.0, 100.0, 4, 0.5);
+        for i in 0..4 {
+            assert!(logarithmic[i] <= blended[i] && blended[i] <= uniform[i]);
+        }
+        assert!(logarithmic[0] < blended[0] && blended[0] < uniform[0]);
+        assert!((blended[3] - 100.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn stable_fit_keeps_the_texel_grid() {
+        let direction = vec3(-1.0, -2.0, -0.5).normalize();
+        let projection = perspective(Deg(60.0), 1.5, 0.1, 100.0);
+        let fit = |eye: Point3<f32>, target: Point3<f32>| {
+            let view = Matrix4::look_at_rh(eye, target, Vector3::unit_y());
+            let slice = frustum_slice(&view, &projection, 0.1, 20.0);
+            fit_stable(direction, &slice, &Aabb::empty(), 1024)
+        };
+        // where a world point falls on the map, in texels
+        let texel = |light_space: &Matrix4<f32>, point: Point3<f32>| {
+            let clip = clip(light_space, point);
+            ((clip.x + 1.0) * 512.0, (clip.y + 1.0) * 512.0)
+        };
+        let point = Point3::new(0.3, 0.2, -2.0);
+        let before = texel(&fit(Point3::new(0.0, 2.0, 5.0), Point3::origin()), point);
+        // moving less than a texel keeps the point on the same spot in its texel; the map
+        // only ever moves by whole texels
+        let step = vec3(0.004, 0.0, 0.003);
+        let moved = fit(Point3::new(0.0, 2.0, 5.0) + step, Point3::origin() + step);
+        let after = texel(&moved, point);
+        let (dx, dy) = (after.0 - before.0, after.1 - before.1);
+        assert!((dx - dx.round()).abs() < 1e-2, "{} {}", dx, dy);
+        assert!((dy - dy.round()).abs() < 1e-2, "{} {}", dx, dy);
+        // turning the camera changes the slice but not the size of a texel
+        let scale = |light_space: Matrix4<f32>| {
+            vec3(light_space.x.x, light_space.y.x, light_space.z.x).magnitude()
+        };
+        let eye = Point3::new(0.0, 2.0, 5.0);
+        let ahead = scale(fit(eye, Point3::origin()));
+        for yaw in [0.3f32, 1.1, 2.5, 4.0] {
+            let target = eye + vec3(yaw.sin(), -0.3, -yaw.cos());
+            let turned = scale(fit(eye, target));
+            assert!(
+                (turned - ahead).abs() < 1e-6 * ahead,
+                "{} {}",
+                turned,
+                ahead
+            );
+        }
+    }
+
+    #[test]
+    fn spot_frustum_covers_the_cone() {
+        let spot = PlacedLight {
+            node: crate::scene::Scene::new().add_node("spot", Default::default(), None),
+            position: Point3::new(0.0, 4.0, 0.0),
+            direction: -Vector3::unit_y(),
+            light: Light {
+                kind: LightKind::Spot {
+                    inner_cone: 0.2,
+                    outer_cone: 0.5,
+                },
+                color: vec3(1.0, 1.0, 1.0),
+                intensity: 1.0,
+                range: Some(10.0),
+            },
+        };
+        let light_space = spot_light_space(&spot).unwrap();
+        let centre = clip(&light_space, Point3::new(0.0, 0.0, 0.0));
+        assert!(centre.x.abs() < 1e-5 && centre.y.abs() < 1e-5 && inside(centre));
+        // the edge of the outer cone on the floor is just inside the map
+        let edge = clip(
+            &light_space,
+            Point3::new(4.0 * 0.5f32.tan() * 0.99, 0.0, 0.0),
+        );
+        assert!(inside(edge) && edge.x.abs() > 0.98);
+        // beyond the range is outside
+        assert!(!inside(clip(&light_space, Point3::new(0.0, -7.0, 0.0))));
+        let point = PlacedLight {
+            light: Light {
+                kind: LightKind::Point,
+                ..spot.light
+            },
+            ..spot
+        };
+        assert_eq!(spot_light_space(&point), None);
+    }
+
+    #[test]
+    fn cube_faces_look_along_the_axes() {
+        let position = Point3::new(1.0, 2.0, 3.0);
+        let faces = cube_light_spaces(position, 10.0);
+        let axes = [
+            Vector3::unit_x(),
+            -Vector3::unit_x(),
+            Vector3::unit_y(),
+            -Vector3::unit_y(),
+            Vector3::unit_z(),
+            -Vector3::unit_z(),
+        ];
+        for (face, axis) in faces.iter().zip(axes) {
+            // straight ahead lands in the middle of the face, the other axes outside it
+            let ahead = clip(face, position + axis * 5.0);
+            assert!(ahead.x.abs() < 1e-5 && ahead.y.abs() < 1e-5 && inside(ahead));
+            assert!(!inside(clip(face, position - axis * 5.0)));
+            assert!(!inside(clip(face, position + axis * 11.0)));
+        }
+    }
+
+    #[test]
+    fn closest_point_lights_get_shadows() {
+        let mut scene = Scene::new();
+        let mut add = |name: &str, x: f32, kind: LightKind| {
+            let node = scene.add_node(name, Default::default(), None);
+            scene.set_translation(node, vec3(x, 0.0, 0.0));
+            scene.set_light(
+                node,
+                Some(Light {
+                    kind,
+                    color: vec3(1.0, 1.0, 1.0),
+                    intensity: 1.0,
+                    range: None,
+                }),
+            );
+        };
+        add("far", 9.0, LightKind::Point);
+        add("sun", 0.0, LightKind::Directional);
+        add("near", 1.0, LightKind::Point);
+        add("middle", -4.0, LightKind::Point);
+        let lights = scene.lights();
+        let eye = Point3::origin();
+        assert_eq!(shadowed_point_lights(&lights, eye, 2), [2, 3]);
+        assert_eq!(shadowed_point_lights(&lights, eye, 10), [2, 3, 0]);
+        assert!(shadowed_point_lights(&lights, eye, 0).is_empty());
+    }
+
+    #[test]
+    fn layers_follow_the_lights() {
+        let mut scene = Scene::new();
+        let add = |scene: &mut Scene, kind: LightKind| {
+            let node = scene.add_node("light", Default::default(), None);
+            scene.set_light(
+                node,
+                Some(Light {
+                    kind,
+                    color: vec3(1.0, 1.0, 1.0),
+                    intensity: 1.0,
+                    range: None,
+                }),
+            );
+        };
+        let spot = LightKind::Spot {
+            inner_cone: 0.2,
+            outer_cone: 0.5,
+        };
+        add(&mut scene, LightKind::Directional);
+        add(&mut scene, LightKind::Point);
+        add(&mut scene, spot);
+        let settings = ShadowSettings::default();
+        assert_eq!(shadow_layers(&settings, &scene.lights()), 3 + 1);
+        // a second sun doesn't fit next to the first one's cascades, more spots still do
+        add(&mut scene, LightKind::Directional);
+        for _ in 0..6 {
+            add(&mut scene, spot);
+        }
+        assert_eq!(shadow_layers(&settings, &scene.lights()), MAX_SHADOW_MAPS);
+        assert_eq!(shadow_layers(&settings, &[]), 0);
+    }
+
+    #[test]
+    fn map_sizes_are_checked_against_the_limits() {
+        let settings = ShadowSettings {
+            resolution: 8192,
+            point_resolution: 4096,
+            ..ShadowSettings::default()
+        };
+        assert!(settings.check_limits((8192, 4096)).is_ok());
+        assert_eq!(
+            settings.check_limits((4096, 4096)).unwrap_err(),
+            "shadows: resolution 8192 is more than GL_MAX_TEXTURE_SIZE 4096"
+        );
+        assert_eq!(
+            settings.check_limits((16384, 2048)).unwrap_err(),
+            "shadows: point_resolution 4096 is more than GL_MAX_CUBE_MAP_TEXTURE_SIZE 2048"
+        );
+    }
+}
@@ -0,0 +1,283 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use cgmath::prelude::*;
+use cgmath::{vec3, Vector3};
+
+use crate::config::ConfigError;
+use crate::mesh::{MeshData, Vertex};
+
+/// Encoding of an STL file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StlFormat {
+    Ascii,
+    Binary,
+}
+
+/// Read an ASCII or binary STL file. Every facet keeps its own normal; corners shared by
+/// facets with the same normal are welded, and the result is reordered for the vertex cache.
+/// ------------------------------------------------------------------------
+pub fn load_stl<P: AsRef<Path>>(path: P) -> Result<MeshData, ConfigError> {
+    let path = path.as_ref();
+    let bytes = fs::read(path).map_err(|err| ConfigError::Io(path.to_path_buf(), err))?;
+    let mut mesh = parse_stl(&bytes).map_err(|err| ConfigError::Parse(path.to_path_buf(), err))?;
+    mesh.optimize();
+    Ok(mesh)
+}
+
+/// Write the triangles of `mesh` as STL with normals computed from the positions.
+/// ------------------------------------------------------------------------
+pub fn save_stl<P: AsRef<Path>>(
+    path: P,
+    mesh: &MeshData,
+    format: StlFormat,
+) -> Result<(), ConfigError> {
+    let path = path.as_ref();
+    let io_error = |err| ConfigError::Io(path.to_path_buf(), err);
+    let name = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let mut out = BufWriter::new(File::create(path).map_err(io_error)?);
+    write_stl(&mut out, mesh, format, &name).map_err(io_error)?;
+    out.flush().map_err(io_error)
+}
+
+pub fn write_stl<W: Write>(
+    out: &mut W,
+    mesh: &MeshData,
+    format: StlFormat,
+    name: &str,
+) -> io::Result<()> {
+    let facets = mesh.triangles().map(|triangle| {
+        let normal = mesh.face_normal(triangle);
+        let normal = if normal.magnitude2() > 0.0 {
+            normal.normalize()
+        } else {
+            Vector3::zero()
+        };
+        let [a, b, c] = triangle.map(|index| mesh.vertices[index as usize].position);
+        [normal, a, b, c]
+    });
+    match format {
+        StlFormat::Ascii => {
+            writeln!(out, "solid {}", name)?;
+            for [normal, a, b, c] in facets {
+                writeln!(out, "  facet normal {} {} {}", normal.x, normal.y, normal.z)?;
+                writeln!(out, "    outer loop")?;
+                for p in [a, b, c].iter() {
+                    writeln!(out, "      vertex {} {} {}", p.x, p.y, p.z)?;
+                }
+                writeln!(out, "    endloop")?;
+                writeln!(out, "  endfacet")?;
+            }
+            writeln!(out, "endsolid {}", name)?;
+        }
+        StlFormat::Binary => {
+            // the 80 byte header must not start with "solid" or readers take it for ASCII
+            let mut header = [b' '; 80];
+            let text = format!("binary STL {}", name);
+            let length = text.len().min(80);
+            header[..length].copy_from_slice(&text.as_bytes()[..length]);
+            out.write_all(&header)?;
+            out.write_all(&(mesh.triangle_count() as u32).to_le_bytes())?;
+            for facet in facets {
+                for vector in facet.iter() {
+                    for component in [vector.x, vector.y, vector.z].iter() {
+                        out.write_all(&component.to_le_bytes())?;
+                    }
+                }
+                // attribute byte count, unused
+                out.write_all(&[0, 0])?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Parse STL from memory. Binary files are recognised by their size matching the triangle
+/// count in the header, since some binary exporters also start the header with "solid".
+/// ------------------------------------------------------------------------
+pub fn parse_stl(bytes: &[u8]) -> Result<MeshData, String> {
+    let binary_size = bytes
+        .get(80..84)
+        .map(|count| 84 + 50 * u32::from_le_bytes(count.try_into().unwrap()) as u64);
+    let facets = if binary_size == Some(bytes.len() as u64) {
+        parse_binary(bytes)
+    } else if bytes.trim_ascii_start().starts_with(b"solid") {
+        parse_ascii(&String::from_utf8_lossy(bytes))?
+    } else if let Some(size) = binary_size {
+        return Err(format!(
+            "binary STL should be {} bytes for its triangle count, file has {}",
+            size,
+            bytes.len()
+        ));
+    } else {
+        return Err("not an STL file".to_string());
+    };
+
+    let mut mesh = MeshData::default();
+    for [normal, a, b, c] in facets {
+        let computed = (b - a).cross(c - a);
+        let normal = if normal.magnitude2() > 1e-12 {
+            normal.normalize()
+        } else if computed.magnitude2() > 0.0 {
+            computed.normalize()
+        } else {
+            Vector3::unit_z()
+        };
+        let base = mesh.vertices.len() as u32;
+        for position in [a, b, c].iter() {
+            mesh.vertices.push(Vertex {
+                position: *position,
+                normal,
+                ..Vertex::default()
+            });
+        }
+        mesh.indices.extend_from_slice(&[base, base + 1, base + 2]);
+    }
+    mesh.weld(0.0);
+    mesh.compute_tangents();
+    Ok(mesh)
+}
+
+/// a facet as normal and three corners
+type Facet = [Vector3<f32>; 4];
+
+fn parse_binary(bytes: &[u8]) -> Vec<Facet> {
+    bytes[84..]
+        .chunks_exact(50)
+        .map(|facet| {
+            let float =
+                |offset: usize| f32::from_le_bytes(facet[offset..offset + 4].try_into().unwrap());
+            let vector = |i: usize| vec3(float(i * 12), float(i * 12 + 4), float(i * 12 + 8));
+            [vector(0), vector(1), vector(2), vector(3)]
+        })
+        .collect()
+}
+
+fn parse_ascii(text: &str) -> Result<Vec<Facet>, String> {
+    let mut facets = Vec::new();
+    let mut normal = None;
+    let mut corners: Vec<Vector3<f32>> = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let error = |message: String| format!("line {}: {}", number + 1, message);
+        let vector = |words: &[&str]| -> Result<Vector3<f32>, String> {
+            let values = words
+                .iter()
+                .map(|word| {
+                    word.parse::<f32>()
+                        .map_err(|_| error(format!("'{}' is not a number", word)))
+                })
+                .collect::<Result<Vec<f32>, String>>()?;
+            match values.as_slice() {
+                [x, y, z] => Ok(vec3(*x, *y, *z)),
+                _ => Err(error(format!("expected 3 numbers, found {}", values.len()))),
+            }
+        };
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            [] | ["solid", ..] | ["endsolid", ..] | ["outer", "loop"] | ["endloop"] => {}
+            ["facet", "normal", rest @ ..] => {
+                if normal.is_some() {
+                    return Err(error("facet inside a facet".to_string()));
+                }
+                normal = Some(vector(rest)?);
+            }
+            ["vertex", rest @ ..] => {
+                if normal.is_none() {
+                    return Err(error("vertex outside a facet".to_string()));
+                }
+                corners.push(vector(rest)?);
+            }
+            ["endfacet"] => {
+                let normal = normal
+                    .take()
+                    .ok_or_else(|| error("endfacet without facet".to_string()))?;
+                match corners.as_slice() {
+                    [a, b, c] => facets.push([normal, *a, *b, *c]),
+                    _ => {
+                        return Err(error(format!(
+                            "facet has {} vertices, expected 3",
+                            corners.len()
+                        )))
+                    }
+                }
+                corners.clear();
+            }
+            _ => return Err(error(format!("unexpected '{}'", line.trim()))),
+        }
+    }
+    if normal.is_some() {
+        return Err("unexpected end of file inside a facet".to_string());
+    }
+    Ok(facets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_keep_triangles() {
+        let cube = MeshData::cube(2.0);
+        for format in [StlFormat::Ascii, StlFormat::Binary] {
+            let mut bytes = Vec::new();
+            write_stl(&mut bytes, &cube, format, "cube").unwrap();
+            if format == StlFormat::Binary {
+                assert_eq!(bytes.len(), 84 + 12 * 50);
+            }
+            let read = parse_stl(&bytes).unwrap();
+            assert_eq!(read.triangle_count(), 12, "{:?}", format);
+            // welded back to four corners per side
+            assert_eq!(read.vertex_count(), 24, "{:?}", format);
+            for [a, b, c] in read.triangles() {
+                let normal = read.face_normal([a, b, c]).normalize();
+                assert!((read.vertices[a as usize].normal - normal).magnitude() < 1e-6);
+            }
+            assert_eq!(read.bounds(), cube.bounds());
+        }
+    }
+
+    #[test]
+    fn ascii_errors_name_the_line() {
+        let text = "solid t
+  facet normal 0 0 1
+    outer loop
+      vertex 0 0 0
+      vertex 1 0 0
+    endloop
+  endfacet
+endsolid t
+";
+        assert_eq!(
+            parse_stl(text.as_bytes()).unwrap_err(),
+            "line 7: facet has 2 vertices, expected 3"
+        );
+        let text = text.replace("vertex 1 0 0", "vertex 1 0 zero");
+        assert_eq!(
+            parse_stl(text.as_bytes()).unwrap_err(),
+            "line 5: 'zero' is not a number"
+        );
+        assert_eq!(
+            parse_stl(&[0; 90]).unwrap_err(),
+            "binary STL should be 84 bytes for its triangle count, file has 90"
+        );
+    }
+
+    #[test]
+    fn zero_normals_come_from_the_winding() {
+        let text = "solid
+facet normal 0 0 0
+outer loop
+vertex 0 0 0
+vertex 0 0 1
+vertex 0 1 0
+endloop
+endfacet
+endsolid";
+        let mesh = parse_stl(text.as_bytes()).unwrap();
+        assert_eq!(mesh.vertices[0].normal, vec3(-1.0, 0.0, 0.0));
+    }
+}
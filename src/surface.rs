@@ -0,0 +1,81 @@
+use glfw::WindowEvent;
+
+/// Size information of the surface we render into.
+///
+/// The window size is measured in screen coordinates while the framebuffer size is measured in
+/// pixels; on HiDPI / retina displays the two differ by the content scale. Anything that touches
+/// the viewport, the projection or an offscreen target must use the framebuffer size.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Surface {
+    pub window_size: (i32, i32),
+    pub framebuffer_size: (i32, i32),
+    pub content_scale: (f32, f32),
+}
+
+/// Something that has to be rebuilt when the framebuffer changes size (projection, offscreen
+/// render targets, ...).
+pub trait ResizeListener {
+    fn on_resize(&mut self, surface: &Surface);
+}
+
+impl Surface {
+    pub fn from_window(window: &glfw::Window) -> Surface {
+        Surface {
+            window_size: window.get_size(),
+            framebuffer_size: window.get_framebuffer_size(),
+            content_scale: window.get_content_scale(),
+        }
+    }
+
+    /// width / height of the framebuffer
+    /// ------------------------------------------------------------------------
+    pub fn aspect(&self) -> f32 {
+        let (width, height) = self.framebuffer_size;
+        if width <= 0 || height <= 0 {
+            // minimized window: any finite value will do, nothing gets presented anyway
+            1.0
+        } else {
+            width as f32 / height as f32
+        }
+    }
+
+    /// true while the window is minimized and the framebuffer has no area
+    /// ------------------------------------------------------------------------
+    pub fn is_empty(&self) -> bool {
+        self.framebuffer_size.0 <= 0 || self.framebuffer_size.1 <= 0
+    }
+
+    /// Update the tracked sizes from a window event.
+    /// Returns true when the framebuffer size changed and listeners should be notified.
+    /// ------------------------------------------------------------------------
+    pub fn handle_event(&mut self, event: &WindowEvent) -> bool {
+        match *event {
+            WindowEvent::Size(width, height) => {
+                self.window_size = (width, height);
+                false
+            }
+            WindowEvent::ContentScale(x, y) => {
+                self.content_scale = (x, y);
+                false
+            }
+            WindowEvent::FramebufferSize(width, height) => {
+                let changed = self.framebuffer_size != (width, height);
+                self.framebuffer_size = (width, height);
+                changed
+            }
+            _ => false,
+        }
+    }
+
+    /// Tell every resize-dependent resource about the current size.
+    /// Listeners are skipped while the window is minimized.
+    /// ------------------------------------------------------------------------
+    pub fn notify(&self, listeners: &mut [&mut dyn ResizeListener]) {
+        if self.is_empty() {
+            return;
+        }
+        for listener in listeners.iter_mut() {
+            listener.on_resize(self);
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use std::os::raw::c_void;
+use std::path::Path;
+
+use gl::types::*;
+use image::DynamicImage;
+
+use crate::config::ConfigError;
+
+/// A 2D RGBA texture with mipmaps and repeat wrapping.
+pub struct Texture {
+    pub id: GLuint,
+}
+
+impl Texture {
+    /// Load an image file. OBJ style UVs start at the bottom left and need `flip_v`, glTF UVs
+    /// start at the top left like the image rows do.
+    /// ------------------------------------------------------------------------
+    pub fn load<P: AsRef<Path>>(path: P, flip_v: bool) -> Result<Texture, ConfigError> {
+        let path = path.as_ref();
+        let img = image::open(path)
+            .map_err(|err| ConfigError::Parse(path.to_path_buf(), err.to_string()))?;
+        let img = if flip_v { img.flipv() } else { img };
+        Ok(Texture::from_image(&img))
+    }
+
+    pub fn from_image(img: &DynamicImage) -> Texture {
+        let rgba = img.to_rgba8();
+        Texture::from_rgba(rgba.width(), rgba.height(), &rgba)
+    }
+
+    /// Upload tightly packed 8-bit RGBA pixels, first row at the bottom.
+    /// ------------------------------------------------------------------------
+    pub fn from_rgba(width: u32, height: u32, pixels: &[u8]) -> Texture {
+        Texture::upload(width, height, pixels, gl::RGBA)
+    }
+
+    /// Like `from_rgba` for colours stored in sRGB: sampling turns them into linear values.
+    /// Data such as normals or roughness has to stay `from_rgba`.
+    /// ------------------------------------------------------------------------
+    pub fn from_srgba(width: u32, height: u32, pixels: &[u8]) -> Texture {
+        Texture::upload(width, height, pixels, gl::SRGB8_ALPHA8)
+    }
+
+    fn upload(width: u32, height: u32, pixels: &[u8], internal_format: GLenum) -> Texture {
+        assert_eq!(pixels.len(), (width * height * 4) as usize);
+        let mut id = 0;
+        unsafe {
+            gl::GenTextures(1, &mut id);
+            gl::BindTexture(gl::TEXTURE_2D, id);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::REPEAT as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::REPEAT as i32);
+            gl::TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_MIN_FILTER,
+                gl::LINEAR_MIPMAP_LINEAR as i32,
+            );
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+            // rows of odd widths aren't 4-byte aligned
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                internal_format as i32,
+                width as i32,
+                height as i32,
+                0,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                pixels.as_ptr() as *const c_void,
+            );
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 4);
+            gl::GenerateMipmap(gl::TEXTURE_2D);
+        }
+        Texture { id }
+    }
+
+    /// 1x1 texture of a single colour, stands in for missing maps
+    pub fn solid(rgba: [u8; 4]) -> Texture {
+        Texture::from_rgba(1, 1, &rgba)
+    }
+
+    /// bind to texture unit `unit` (0 for `GL_TEXTURE0`)
+    pub unsafe fn bind(&self, unit: u32) {
+        gl::ActiveTexture(gl::TEXTURE0 + unit);
+        gl::BindTexture(gl::TEXTURE_2D, self.id);
+    }
+}
+
+impl Drop for Texture {
+    fn drop(&mut self) {
+        unsafe { gl::DeleteTextures(1, &self.id) }
+    }
+}
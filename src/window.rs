@@ -0,0 +1,299 @@
+use std::sync::mpsc::Receiver;
+
+use glfw::{Context, Glfw, OpenGlProfileHint, SwapInterval, Window, WindowEvent, WindowHint};
+
+use crate::config::{DisplayMode, GlProfile, WindowConfig};
+use crate::surface::Surface;
+
+pub type Events = Receiver<(f64, WindowEvent)>;
+
+/// Create the window and GL context described by `config`, make it current and load the GL
+/// function pointers.
+/// ------------------------------------------------------------------------
+pub fn create_window(glfw: &mut Glfw, config: &WindowConfig) -> (Window, Events) {
+    apply_hints(glfw, config);
+
+    let (created, borderless_pos) = glfw.with_connected_monitors(|glfw, monitors| {
+        // fall back to the primary monitor if the configured one is gone
+        let monitor = monitors.get(config.monitor).or_else(|| monitors.first());
+        match (config.display_mode, monitor) {
+            (DisplayMode::Fullscreen, Some(monitor)) => {
+                let mode = glfw::WindowMode::FullScreen(monitor);
+                let created = glfw.create_window(config.width, config.height, &config.title, mode);
+                (created, None)
+            }
+            (DisplayMode::Borderless, Some(monitor)) => {
+                let (width, height) = monitor
+                    .get_video_mode()
+                    .map_or((config.width, config.height), |mode| {
+                        (mode.width, mode.height)
+                    });
+                glfw.window_hint(WindowHint::Decorated(false));
+                let mode = glfw::WindowMode::Windowed;
+                let created = glfw.create_window(width, height, &config.title, mode);
+                (created, Some(monitor.get_pos()))
+            }
+            _ => {
+                let mode = glfw::WindowMode::Windowed;
+                let created = glfw.create_window(config.width, config.height, &config.title, mode);
+                (created, None)
+            }
+        }
+    });
+    let (mut window, events) = created.expect("Failed to create GFLW Window");
+    if let Some((x, y)) = borderless_pos {
+        window.set_pos(x, y);
+    }
+
+    window.make_current();
+    glfw.set_swap_interval(swap_interval(config.vsync));
+
+    gl::load_with(|symbol| window.get_proc_address(symbol) as *const _);
+
+    (window, events)
+}
+
+fn apply_hints(glfw: &mut Glfw, config: &WindowConfig) {
+    glfw.default_window_hints();
+    let (major, minor) = config.gl_version;
+    glfw.window_hint(WindowHint::ContextVersion(major, minor));
+    glfw.window_hint(WindowHint::OpenGlProfile(match config.gl_profile {
+        GlProfile::Core => OpenGlProfileHint::Core,
+        GlProfile::Compat => OpenGlProfileHint::Compat,
+        GlProfile::Any => OpenGlProfileHint::Any,
+    }));
+    #[cfg(target_os = "macos")]
+    glfw.window_hint(WindowHint::OpenGlForwardCompat(true));
+    glfw.window_hint(WindowHint::OpenGlDebugContext(config.debug_context));
+    // the default framebuffer stays single-sampled: `config.samples` is applied by an offscreen
+    // `MsaaTarget` so it can change at runtime, and a blit needs a single-sampled destination
+    glfw.window_hint(WindowHint::Samples(Some(0)));
+    glfw.window_hint(WindowHint::Resizable(config.resizable));
+    glfw.window_hint(WindowHint::Visible(config.visible));
+}
+
+pub fn swap_interval(vsync: bool) -> SwapInterval {
+    if vsync {
+        SwapInterval::Sync(1)
+    } else {
+        SwapInterval::None
+    }
+}
+
+/// Runtime display settings of a window: windowed / fullscreen / borderless on a chosen monitor
+/// and vsync. Remembers the windowed position and size so leaving fullscreen restores them.
+pub struct DisplayState {
+    pub mode: DisplayMode,
+    pub monitor: usize,
+    pub vsync: bool,
+    windowed_pos: (i32, i32),
+    windowed_size: (i32, i32),
+}
+
+impl DisplayState {
+    pub fn new(window: &Window, config: &WindowConfig) -> DisplayState {
+        let windowed_size = if config.display_mode == DisplayMode::Windowed {
+            window.get_size()
+        } else {
+            (config.width as i32, config.height as i32)
+        };
+        DisplayState {
+            mode: config.display_mode,
+            monitor: config.monitor,
+            vsync: config.vsync,
+            windowed_pos: window.get_pos(),
+            windowed_size,
+        }
+    }
+
+    /// Switch display mode and/or monitor.
+    /// ------------------------------------------------------------------------
+    pub fn set_mode(
+        &mut self,
+        glfw: &mut Glfw,
+        window: &mut Window,
+        mode: DisplayMode,
+        monitor: usize,
+    ) {
+        if self.mode == DisplayMode::Windowed {
+            self.windowed_pos = window.get_pos();
+            self.windowed_size = window.get_size();
+        }
+
+        let applied = glfw.with_connected_monitors(|_, monitors| {
+            let (index, monitor) = match monitors.get(monitor) {
+                Some(found) => (monitor, found),
+                None => (0, monitors.first()?),
+            };
+            let video_mode = monitor.get_video_mode()?;
+            match mode {
+                DisplayMode::Windowed => {
+                    let (x, y) = self.windowed_pos;
+                    let (width, height) = self.windowed_size;
+                    window.set_monitor(
+                        glfw::WindowMode::Windowed,
+                        x,
+                        y,
+                        width as u32,
+                        height as u32,
+                        None,
+                    );
+                    window.set_decorated(true);
+                }
+                DisplayMode::Fullscreen => window.set_monitor(
+                    glfw::WindowMode::FullScreen(monitor),
+                    0,
+                    0,
+                    video_mode.width,
+                    video_mode.height,
+                    Some(video_mode.refresh_rate),
+                ),
+                DisplayMode::Borderless => {
+                    let (x, y) = monitor.get_pos();
+                    window.set_decorated(false);
+                    window.set_monitor(
+                        glfw::WindowMode::Windowed,
+                        x,
+                        y,
+                        video_mode.width,
+                        video_mode.height,
+                        None,
+                    );
+                }
+            }
+            Some(index)
+        });
+
+        match applied {
+            Some(index) => {
+                self.mode = mode;
+                self.monitor = index;
+                // some drivers reset the swap interval when the window changes monitor
+                glfw.set_swap_interval(swap_interval(self.vsync));
+            }
+            None => println!("WARNING::WINDOW:: no monitor available for {:?}", mode),
+        }
+    }
+
+    /// Toggle between windowed and fullscreen on the current monitor.
+    /// ------------------------------------------------------------------------
+    pub fn toggle_fullscreen(&mut self, glfw: &mut Glfw, window: &mut Window) {
+        let mode = if self.mode == DisplayMode::Windowed {
+            DisplayMode::Fullscreen
+        } else {
+            DisplayMode::Windowed
+        };
+        self.set_mode(glfw, window, mode, self.monitor);
+    }
+
+    /// Needs the window's context to be current.
+    /// ------------------------------------------------------------------------
+    pub fn set_vsync(&mut self, glfw: &mut Glfw, vsync: bool) {
+        self.vsync = vsync;
+        glfw.set_swap_interval(swap_interval(vsync));
+    }
+
+    pub fn toggle_vsync(&mut self, glfw: &mut Glfw) {
+        self.set_vsync(glfw, !self.vsync);
+    }
+}
+
+pub type WindowId = usize;
+
+/// A window with its own event queue and surface size.
+pub struct AppWindow {
+    pub window: Window,
+    pub surface: Surface,
+    events: Events,
+}
+
+impl AppWindow {
+    fn new(window: Window, events: Events) -> AppWindow {
+        AppWindow {
+            surface: Surface::from_window(&window),
+            window,
+            events,
+        }
+    }
+
+    /// drain the events routed to this window
+    /// ------------------------------------------------------------------------
+    pub fn flush_events(&self) -> Vec<WindowEvent> {
+        glfw::flush_messages(&self.events)
+            .map(|(_, event)| event)
+            .collect()
+    }
+}
+
+/// All open windows. Every window after the first is created with a context shared with the
+/// main window, so textures, buffers, renderbuffers and shader programs can be used from any of
+/// them. Container objects (VAOs, framebuffers) are NOT shared and must be created per window
+/// while that window's context is current.
+pub struct Windows {
+    windows: Vec<Option<AppWindow>>,
+    current: Option<WindowId>,
+}
+
+impl Windows {
+    pub const MAIN: WindowId = 0;
+
+    /// Takes the window returned by `create_window`, whose context is current.
+    pub fn new(window: Window, events: Events) -> Windows {
+        Windows {
+            windows: vec![Some(AppWindow::new(window, events))],
+            current: Some(Windows::MAIN),
+        }
+    }
+
+    /// Open another windowed window sharing the main window's context objects.
+    /// Its context is made current once to disable vsync: with several vsynced windows every
+    /// `swap_buffers` would wait for its own vblank and divide the frame rate.
+    /// ------------------------------------------------------------------------
+    pub fn create_shared(
+        &mut self,
+        glfw: &mut Glfw,
+        width: u32,
+        height: u32,
+        title: &str,
+    ) -> WindowId {
+        glfw.window_hint(WindowHint::Decorated(true));
+        let (window, events) = self
+            .main()
+            .window
+            .create_shared(width, height, title, glfw::WindowMode::Windowed)
+            .expect("Failed to create shared GFLW Window");
+
+        let id = self.windows.len();
+        self.windows.push(Some(AppWindow::new(window, events)));
+        self.make_current(id);
+        glfw.set_swap_interval(SwapInterval::None);
+        id
+    }
+
+    /// Make the window's context current, skipping the switch if it already is.
+    /// ------------------------------------------------------------------------
+    pub fn make_current(&mut self, id: WindowId) -> &mut AppWindow {
+        let switch = self.current != Some(id);
+        let app = self.windows[id].as_mut().expect("window already closed");
+        if switch {
+            app.window.make_current();
+        }
+        self.current = Some(id);
+        app
+    }
+
+    pub fn main(&self) -> &AppWindow {
+        self.windows[Windows::MAIN].as_ref().unwrap()
+    }
+
+    /// Destroy a secondary window. The main window lives as long as `Windows`.
+    /// Per-window GL objects (VAOs) must be deleted before this, with its context current.
+    /// ------------------------------------------------------------------------
+    pub fn close(&mut self, id: WindowId) {
+        assert_ne!(id, Windows::MAIN, "the main window owns the shared context");
+        if self.current == Some(id) {
+            self.make_current(Windows::MAIN);
+        }
+        self.windows[id] = None;
+    }
+}